@@ -0,0 +1,338 @@
+/**
+ * cheats.rs
+ *
+ * Cheat codes, in the two formats used by Gameboy cheat devices. Game Genie
+ *  codes patch what the game reads from ROM, and GameShark codes write to RAM
+ *  once per frame (at VBlank).
+**/
+use core::fmt;
+
+/// The number of cheats which can be enabled at once.
+pub const MAX_CHEATS: usize = 32;
+
+#[derive(Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "debug_structs", derive(Debug))]
+pub enum CheatError {
+    /// The code isn't a Game Genie or GameShark code.
+    InvalidCode,
+    /// The code uses a GameShark code type which isn't supported.
+    UnsupportedType(u8),
+    /// The GameShark code writes somewhere other than RAM.
+    UnsupportedAddress(u16),
+    /// All the cheat slots are in use.
+    TooManyCheats,
+}
+
+impl fmt::Display for CheatError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CheatError::InvalidCode => write!(f, "not a Game Genie or GameShark code"),
+            CheatError::UnsupportedType(code_type) => {
+                write!(f, "unsupported GameShark code type {:02X}", code_type)
+            }
+            CheatError::UnsupportedAddress(address) => {
+                write!(f, "GameShark codes can't write to {:04X}", address)
+            }
+            CheatError::TooManyCheats => write!(f, "too many cheats (at most {})", MAX_CHEATS),
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "debug_structs", derive(Debug))]
+pub enum Cheat {
+    /// Replaces a byte of ROM, optionally only when the original byte matches
+    ///  `compare` (so that only one ROM bank is affected).
+    GameGenie {
+        address: u16,
+        value: u8,
+        compare: Option<u8>,
+    },
+    /// Writes a byte to RAM every frame. `cart_bank` is set for codes which
+    ///  write to a specific bank of cartridge RAM.
+    GameShark {
+        address: u16,
+        value: u8,
+        cart_bank: Option<u8>,
+    },
+}
+
+/// Parses a string of hex digits, ignoring dashes.
+fn parse_digits(code: &str, digits: &mut [u8]) -> Result<(), CheatError> {
+    let mut count = 0;
+
+    for c in code.chars().filter(|&c| c != '-') {
+        let digit = c.to_digit(16).ok_or(CheatError::InvalidCode)?;
+        *digits.get_mut(count).ok_or(CheatError::InvalidCode)? = digit as u8;
+        count += 1;
+    }
+
+    if count == digits.len() {
+        Ok(())
+    } else {
+        Err(CheatError::InvalidCode)
+    }
+}
+
+impl Cheat {
+    /// Parses a Game Genie code (`ABC-DEF` or `ABC-DEF-GHI`) or a GameShark
+    ///  code (`TTVVLLHH`).
+    pub fn parse(code: &str) -> Result<Cheat, CheatError> {
+        let code = code.trim();
+
+        if code.contains('-') {
+            let length = code.chars().filter(|&c| c != '-').count();
+            if length != 6 && length != 9 {
+                return Err(CheatError::InvalidCode);
+            }
+
+            let mut digits = [0; 9];
+            parse_digits(code, &mut digits[..length])?;
+
+            // The address is scrambled, and its top digit inverted
+            let address = ((digits[5] ^ 0xF) as u16) << 12
+                | (digits[2] as u16) << 8
+                | (digits[3] as u16) << 4
+                | digits[4] as u16;
+            if address >= 0x8000 {
+                return Err(CheatError::InvalidCode);
+            }
+
+            // The 8th digit is a unused checksum
+            let compare = if length == 9 {
+                Some((digits[6] << 4 | digits[8]).rotate_right(2) ^ 0xBA)
+            } else {
+                None
+            };
+
+            Ok(Cheat::GameGenie {
+                address,
+                value: digits[0] << 4 | digits[1],
+                compare,
+            })
+        } else {
+            let mut digits = [0; 8];
+            parse_digits(code, &mut digits)?;
+
+            let byte = |index: usize| digits[index * 2] << 4 | digits[index * 2 + 1];
+            let code_type = byte(0);
+            let cart_bank = match code_type {
+                // 9X codes select a WRAM bank, which only matters on the CGB
+                0x00 | 0x01 | 0x90..=0x97 => None,
+                0x80..=0x8F => Some(code_type & 0xF),
+                _ => return Err(CheatError::UnsupportedType(code_type)),
+            };
+
+            // Writes elsewhere would hit the MBC or I/O registers every frame
+            let address = (byte(3) as u16) << 8 | byte(2) as u16;
+            match (address, cart_bank) {
+                (0xA000..=0xBFFF, _) => {}
+                (0xC000..=0xDFFF, None) | (0xFF80..=0xFFFE, None) => {}
+                _ => return Err(CheatError::UnsupportedAddress(address)),
+            }
+
+            Ok(Cheat::GameShark {
+                address,
+                value: byte(1),
+                cart_bank,
+            })
+        }
+    }
+}
+
+impl fmt::Display for Cheat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Cheat::GameGenie {
+                address,
+                value,
+                compare,
+            } => {
+                write!(f, "ROM {:04X} = {:02X}", address, value)?;
+                if let Some(compare) = compare {
+                    write!(f, " if {:02X}", compare)?;
+                }
+                Ok(())
+            }
+            Cheat::GameShark {
+                address,
+                value,
+                cart_bank: Some(bank),
+            } => write!(f, "RAM {:02X}:{:04X} = {:02X}", bank, address, value),
+            Cheat::GameShark { address, value, .. } => {
+                write!(f, "RAM {:04X} = {:02X}", address, value)
+            }
+        }
+    }
+}
+
+#[derive(Copy, Clone, Default)]
+pub struct Cheats {
+    slots: [Option<Cheat>; MAX_CHEATS],
+    /// If any Game Genie codes are enabled, so ROM reads can be skipped
+    ///  quickly
+    patching: bool,
+}
+
+impl Cheats {
+    /// Parses and enables a code. Several codes can be given at once,
+    ///  separated by `+` or whitespace (as libretro cheat files do), in which
+    ///  case either all or none of them are enabled.
+    pub fn add(&mut self, codes: &str) -> Result<(), CheatError> {
+        let codes = codes
+            .split(|c: char| c == '+' || c.is_whitespace())
+            .filter(|code| !code.is_empty());
+
+        let mut count = 0;
+        for code in codes.clone() {
+            Cheat::parse(code)?;
+            count += 1;
+        }
+        if count > self.slots.iter().filter(|slot| slot.is_none()).count() {
+            return Err(CheatError::TooManyCheats);
+        }
+
+        for code in codes {
+            let cheat = Cheat::parse(code)?;
+            if let Some(slot) = self.slots.iter_mut().find(|slot| slot.is_none()) {
+                *slot = Some(cheat);
+            }
+
+            if let Cheat::GameGenie { .. } = cheat {
+                self.patching = true;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn clear(&mut self) {
+        self.slots = [None; MAX_CHEATS];
+        self.patching = false;
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Cheat> {
+        self.slots.iter().flatten()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.iter().all(Option::is_none)
+    }
+
+    /// Applies any Game Genie codes to a byte read from ROM.
+    #[inline(always)]
+    pub fn patch(&self, address: u16, original: u8) -> u8 {
+        if !self.patching {
+            return original;
+        }
+
+        for cheat in self.iter() {
+            if let Cheat::GameGenie {
+                address: target,
+                value,
+                compare,
+            } = *cheat
+            {
+                if target == address && compare.is_none_or(|compare| compare == original) {
+                    return value;
+                }
+            }
+        }
+
+        original
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::format;
+    use std::string::String;
+    use std::string::ToString;
+
+    /// Parses a code, returning what it does (or why it was rejected).
+    fn parse(code: &str) -> String {
+        match Cheat::parse(code) {
+            Ok(cheat) => cheat.to_string(),
+            Err(error) => error.to_string(),
+        }
+    }
+
+    #[test]
+    fn parses_game_genie_codes() {
+        assert_eq!(parse("421-04F"), "ROM 0104 = 42");
+        assert_eq!(parse(" 00A-17B "), "ROM 4A17 = 00");
+        // The compare value is 0xEE rotated right by 2, xored with 0xBA
+        assert_eq!(parse("3E9-6EF-E6E"), "ROM 096E = 3E if 01");
+        // The checksum digit is ignored
+        assert_eq!(parse("3E9-6EF-E0E"), "ROM 096E = 3E if 01");
+        assert_eq!(parse("FF1-23C-7A5"), "ROM 3123 = FF if E7");
+    }
+
+    #[test]
+    fn rejects_invalid_game_genie_codes() {
+        let invalid = CheatError::InvalidCode.to_string();
+
+        assert_eq!(parse("421-04"), invalid);
+        assert_eq!(parse("421-04F-00"), invalid);
+        assert_eq!(parse("42G-04F"), invalid);
+        // Game Genie codes can only patch ROM
+        assert_eq!(parse("000-007"), invalid);
+    }
+
+    #[test]
+    fn parses_gameshark_codes() {
+        assert_eq!(parse("01FF13C1"), "RAM C113 = FF");
+        assert_eq!(parse("00630AD0"), "RAM D00A = 63");
+        assert_eq!(parse("010980FF"), "RAM FF80 = 09");
+        assert_eq!(parse("010034A1"), "RAM A134 = 00");
+        // 9X codes select a WRAM bank, which is ignored
+        assert_eq!(parse("93100CD0"), "RAM D00C = 10");
+        assert_eq!(parse("81051BA0"), "RAM 01:A01B = 05");
+        assert_eq!(parse("8F05FFBF"), "RAM 0F:BFFF = 05");
+    }
+
+    #[test]
+    fn rejects_unsupported_gameshark_codes() {
+        let invalid = CheatError::InvalidCode.to_string();
+
+        assert_eq!(parse("01FF13C"), invalid);
+        assert_eq!(parse("01FF13CG"), invalid);
+        assert_eq!(parse("A0FF13C1"), "unsupported GameShark code type A0");
+        assert_eq!(parse("98FF13C1"), "unsupported GameShark code type 98");
+
+        // Writes to the MBC, VRAM, OAM and I/O registers
+        for &address in &[0x2000u16, 0x9800, 0xFE00, 0xFF46, 0xFFFF] {
+            let code = format!("0101{:02X}{:02X}", address & 0xFF, address >> 8);
+            assert_eq!(
+                parse(&code),
+                format!("GameShark codes can't write to {:04X}", address)
+            );
+        }
+
+        // Cart RAM banks only apply to cart RAM
+        assert_eq!(parse("810100C0"), "GameShark codes can't write to C000");
+    }
+
+    #[test]
+    fn adds_all_or_none() {
+        let mut cheats = Cheats::default();
+
+        assert!(cheats.add("01FF13C1+A0FF13C1").is_err());
+        assert!(cheats.is_empty());
+
+        assert!(cheats.add("01FF13C1 421-04F").is_ok());
+        assert_eq!(cheats.iter().count(), 2);
+    }
+
+    #[test]
+    fn patches_rom() {
+        let mut cheats = Cheats::default();
+        assert!(cheats.add("421-04F+3E9-6EF-E6E").is_ok());
+
+        assert_eq!(cheats.patch(0x0104, 0xCE), 0x42);
+        assert_eq!(cheats.patch(0x0105, 0xCE), 0xCE);
+        assert_eq!(cheats.patch(0x096E, 0x01), 0x3E);
+        assert_eq!(cheats.patch(0x096E, 0x02), 0x02);
+    }
+}
@@ -0,0 +1,112 @@
+/**
+ * bus.rs
+ *
+ * The interface between the CPU and the rest of the system. `GBMemory` is the
+ *  Game Boy's implementation, but the CPU can be run against anything which
+ *  can be read from and written to (e.g. a flat 64KiB array for testing).
+ *  `Bus` covers memory and the passing of time, and `GameboyBus` the hooks
+ *  for interrupts, STOP and the like, which have defaults.
+**/
+use cpu::interrupts::InterruptType;
+#[cfg(feature = "debugger")]
+use watchpoint::Watchpoints;
+
+/// The address of the interrupt flag (IF) register.
+pub const INTERRUPT_FLAG: u16 = 0xFF0F;
+/// The address of the interrupt enable (IE) register.
+pub const INTERRUPT_ENABLE: u16 = 0xFFFF;
+
+pub trait Bus {
+    /// Reads a value from memory.
+    fn read(&self, ptr: u16) -> u8;
+
+    /// Writes a value to memory.
+    fn write(&mut self, ptr: u16, val: u8);
+
+    /// Advances the rest of the system by the specified number of CPU cycles.
+    ///  Returns true if a frame was completed.
+    fn tick(&mut self, cycles: u8) -> bool;
+
+    /// Reads a little endian short.
+    fn read_short(&self, ptr: u16) -> u16 {
+        (self.read(ptr) as u16) | ((self.read(ptr.wrapping_add(1)) as u16) << 8)
+    }
+
+    /// Writes a little endian short.
+    fn write_short(&mut self, ptr: u16, val: u16) {
+        self.write(ptr, (val & 0xFF) as u8);
+        self.write(ptr.wrapping_add(1), ((val >> 8) & 0xFF) as u8);
+    }
+}
+
+/// The rest of the Game Boy, as seen by the CPU beyond plain memory accesses.
+///  Every method has a default suited to a bus which is only memory, so such
+///  a bus can implement this with a empty `impl`.
+pub trait GameboyBus: Bus {
+    /// Returns the interrupts which are both requested and enabled, as IF/IE
+    ///  bits.
+    fn get_pending_interrupts(&self) -> u8 {
+        self.read(INTERRUPT_FLAG) & self.read(INTERRUPT_ENABLE) & 0b11111
+    }
+
+    /// Requests a interrupt.
+    fn request_interrupt(&mut self, interrupt: InterruptType) {
+        let flags = self.read(INTERRUPT_FLAG);
+        self.write(INTERRUPT_FLAG, flags | (1 << interrupt as u8));
+    }
+
+    /// Clears a requested interrupt, as the CPU is about to service it.
+    fn acknowledge_interrupt(&mut self, interrupt: InterruptType) {
+        let flags = self.read(INTERRUPT_FLAG);
+        self.write(INTERRUPT_FLAG, flags & !(1 << interrupt as u8));
+    }
+
+    /// Returns if a button is held, which stops STOP (or wakes the CPU from it).
+    fn is_button_held(&self) -> bool {
+        false
+    }
+
+    /// Called when the CPU executes STOP. Returns true if a CGB speed switch
+    ///  was performed instead of stopping the clock.
+    fn stop(&mut self) -> bool {
+        false
+    }
+
+    /// Returns if the CPU is running in CGB double speed mode.
+    fn is_double_speed(&self) -> bool {
+        false
+    }
+
+    /// Returns if the display is running, and so will be completing frames.
+    fn is_display_enabled(&self) -> bool {
+        false
+    }
+
+    /// Returns the ROM bank mapped in at 0x4000-0x7FFF.
+    fn get_rom_bank(&self) -> u8 {
+        1
+    }
+
+    /// Returns the bank of cart RAM mapped in at 0xA000-0xBFFF.
+    fn get_ram_bank(&self) -> u8 {
+        0
+    }
+
+    /// Returns the bank of the memory mapped in at the address, as used in
+    ///  symbol files and other emulators' debuggers.
+    fn get_bank(&self, address: u16) -> u8 {
+        match address {
+            0x4000..=0x7FFF => self.get_rom_bank(),
+            0xA000..=0xBFFF => self.get_ram_bank(),
+            0xD000..=0xDFFF => 1,
+            _ => 0,
+        }
+    }
+
+    /// Returns the watchpoints to check memory accesses against, if any are
+    ///  set.
+    #[cfg(feature = "debugger")]
+    fn get_watchpoints(&self) -> Option<&Watchpoints> {
+        None
+    }
+}
@@ -0,0 +1,407 @@
+/**
+ * disasm.rs
+ *
+ * Decodes instructions from memory, for debuggers, tracers and other tools.
+**/
+use cpu::bus::Bus;
+
+use core::fmt;
+
+const REGISTERS: [&str; 8] = ["b", "c", "d", "e", "h", "l", "(hl)", "a"];
+const PAIRS: [&str; 4] = ["bc", "de", "hl", "sp"];
+const STACK_PAIRS: [&str; 4] = ["bc", "de", "hl", "af"];
+const CONDITIONS: [&str; 4] = ["nz", "z", "nc", "c"];
+const ALU: [&str; 8] = ["add", "adc", "sub", "sbc", "and", "xor", "or", "cp"];
+const ROTATIONS: [&str; 8] = ["rlc", "rrc", "rl", "rr", "sla", "sra", "swap", "srl"];
+const ACCUMULATOR_OPS: [&str; 8] = ["rlca", "rrca", "rla", "rra", "daa", "cpl", "scf", "ccf"];
+
+#[derive(Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "debug_structs", derive(Debug))]
+pub enum Operand {
+    /// A register or register pair (e.g. `a` or `hl`).
+    Register(&'static str),
+    /// Memory pointed to by a register (e.g. `(hl)` or `(hl+)`).
+    Indirect(&'static str),
+    /// A branch condition (e.g. `nz`).
+    Condition(&'static str),
+    /// A 8-bit immediate value.
+    Immediate8(u8),
+    /// A 16-bit immediate value.
+    Immediate16(u16),
+    /// Memory at a fixed address.
+    Address(u16),
+    /// The destination of a jump, call or restart.
+    Target(u16),
+    /// A bit index, for bit/res/set.
+    Bit(u8),
+    /// A signed offset added to SP (`add sp,d`).
+    Offset(i8),
+    /// SP plus a signed offset (`ld hl,sp+d`).
+    StackOffset(i8),
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Operand::Register(name) | Operand::Indirect(name) | Operand::Condition(name) => {
+                write!(f, "{}", name)
+            }
+            Operand::Immediate8(value) => write!(f, "${:02X}", value),
+            Operand::Immediate16(value) | Operand::Target(value) => write!(f, "${:04X}", value),
+            Operand::Address(address) => write!(f, "(${:04X})", address),
+            Operand::Bit(bit) => write!(f, "{}", bit),
+            Operand::Offset(offset) => write!(f, "{}", offset),
+            Operand::StackOffset(offset) => write!(f, "sp{:+}", offset),
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "debug_structs", derive(Debug))]
+pub struct Instruction {
+    /// Where the instruction was read from.
+    pub address: u16,
+    /// The opcode, with the second byte in the high byte for CB prefixed
+    ///  instructions (as with `execute_instruction`).
+    pub opcode: u16,
+    pub mnemonic: &'static str,
+    pub operands: [Option<Operand>; 2],
+    /// The length of the instruction in bytes, including operands.
+    pub length: u16,
+    /// The number of cycles taken (if a branch, when it isn't taken).
+    pub cycles: u8,
+    /// The number of cycles taken by a conditional branch, if it is taken.
+    pub branch_cycles: Option<u8>,
+}
+
+impl Instruction {
+    /// Returns the operands the instruction has.
+    pub fn get_operands(&self) -> impl Iterator<Item = &Operand> {
+        self.operands.iter().flatten()
+    }
+
+    /// Returns the address of the next instruction in memory.
+    pub fn get_next_address(&self) -> u16 {
+        self.address.wrapping_add(self.length)
+    }
+
+    /// Returns if this calls a subroutine which will return to the next
+    ///  instruction.
+    pub fn is_call(&self) -> bool {
+        self.mnemonic == "call" || self.mnemonic == "rst"
+    }
+
+    /// Returns if this is a valid instruction.
+    pub fn is_valid(&self) -> bool {
+        self.mnemonic != "db"
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.mnemonic)?;
+
+        for (i, operand) in self.get_operands().enumerate() {
+            write!(f, "{}{}", if i == 0 { " " } else { "," }, operand)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns a operand for one of the 8-bit registers (or (hl)), as encoded in
+///  a opcode.
+fn register(index: u8) -> Operand {
+    match index {
+        6 => Operand::Indirect(REGISTERS[6]),
+        _ => Operand::Register(REGISTERS[index as usize]),
+    }
+}
+
+/// Decodes the instruction at the specified address.
+pub fn disassemble<B: Bus>(bus: &B, address: u16) -> Instruction {
+    let opcode = bus.read(address);
+    let n = bus.read(address.wrapping_add(1));
+    let nn = n as u16 | (bus.read(address.wrapping_add(2)) as u16) << 8;
+    // Relative jumps are from the end of the instruction
+    let relative = address.wrapping_add(2).wrapping_add(n as i8 as u16);
+
+    let x = opcode >> 6;
+    let y = (opcode >> 3) & 0x7;
+    let z = opcode & 0x7;
+    let p = (y >> 1) as usize;
+    let q = y & 0x1;
+
+    // (HL) operands take an extra memory access
+    let hl_cycles = |index: u8, base: u8, extra: u8| if index == 6 { base + extra } else { base };
+
+    use self::Operand::*;
+
+    let (mnemonic, operands, length, cycles, branch_cycles) = match (x, z) {
+        (0, 0) => match y {
+            0 => ("nop", [None, None], 1, 4, None),
+            1 => ("ld", [Some(Address(nn)), Some(Register("sp"))], 3, 20, None),
+            2 => ("stop", [None, None], 2, 4, None),
+            3 => ("jr", [Some(Target(relative)), None], 2, 12, None),
+            _ => (
+                "jr",
+                [Some(Condition(CONDITIONS[y as usize - 4])), Some(Target(relative))],
+                2,
+                8,
+                Some(12),
+            ),
+        },
+        (0, 1) if q == 0 => ("ld", [Some(Register(PAIRS[p])), Some(Immediate16(nn))], 3, 12, None),
+        (0, 1) => ("add", [Some(Register("hl")), Some(Register(PAIRS[p]))], 1, 8, None),
+        (0, 2) => {
+            let pointer = Indirect(["(bc)", "(de)", "(hl+)", "(hl-)"][p]);
+            if q == 0 {
+                ("ld", [Some(pointer), Some(Register("a"))], 1, 8, None)
+            } else {
+                ("ld", [Some(Register("a")), Some(pointer)], 1, 8, None)
+            }
+        }
+        (0, 3) => {
+            let mnemonic = if q == 0 { "inc" } else { "dec" };
+            (mnemonic, [Some(Register(PAIRS[p])), None], 1, 8, None)
+        }
+        (0, 4) => ("inc", [Some(register(y)), None], 1, hl_cycles(y, 4, 8), None),
+        (0, 5) => ("dec", [Some(register(y)), None], 1, hl_cycles(y, 4, 8), None),
+        (0, 6) => (
+            "ld",
+            [Some(register(y)), Some(Immediate8(n))],
+            2,
+            hl_cycles(y, 8, 4),
+            None,
+        ),
+        (0, _) => (ACCUMULATOR_OPS[y as usize], [None, None], 1, 4, None),
+        (1, _) if opcode == 0x76 => ("halt", [None, None], 1, 4, None),
+        (1, _) => (
+            "ld",
+            [Some(register(y)), Some(register(z))],
+            1,
+            hl_cycles(y, hl_cycles(z, 4, 4), 4),
+            None,
+        ),
+        (2, _) => (
+            ALU[y as usize],
+            [Some(Register("a")), Some(register(z))],
+            1,
+            hl_cycles(z, 4, 4),
+            None,
+        ),
+        (_, 0) => match y {
+            0..=3 => (
+                "ret",
+                [Some(Condition(CONDITIONS[y as usize])), None],
+                1,
+                8,
+                Some(20),
+            ),
+            4 => ("ldh", [Some(Address(0xFF00 | n as u16)), Some(Register("a"))], 2, 12, None),
+            5 => ("add", [Some(Register("sp")), Some(Offset(n as i8))], 2, 16, None),
+            6 => ("ldh", [Some(Register("a")), Some(Address(0xFF00 | n as u16))], 2, 12, None),
+            _ => ("ld", [Some(Register("hl")), Some(StackOffset(n as i8))], 2, 12, None),
+        },
+        (_, 1) if q == 0 => ("pop", [Some(Register(STACK_PAIRS[p])), None], 1, 12, None),
+        (_, 1) => match p {
+            0 => ("ret", [None, None], 1, 16, None),
+            1 => ("reti", [None, None], 1, 16, None),
+            2 => ("jp", [Some(Register("hl")), None], 1, 4, None),
+            _ => ("ld", [Some(Register("sp")), Some(Register("hl"))], 1, 8, None),
+        },
+        (_, 2) => match y {
+            0..=3 => (
+                "jp",
+                [Some(Condition(CONDITIONS[y as usize])), Some(Target(nn))],
+                3,
+                12,
+                Some(16),
+            ),
+            4 => ("ld", [Some(Indirect("($FF00+c)")), Some(Register("a"))], 1, 8, None),
+            5 => ("ld", [Some(Address(nn)), Some(Register("a"))], 3, 16, None),
+            6 => ("ld", [Some(Register("a")), Some(Indirect("($FF00+c)"))], 1, 8, None),
+            _ => ("ld", [Some(Register("a")), Some(Address(nn))], 3, 16, None),
+        },
+        (_, 3) => match y {
+            0 => ("jp", [Some(Target(nn)), None], 3, 16, None),
+            1 => return disassemble_cb(address, n),
+            6 => ("di", [None, None], 1, 4, None),
+            7 => ("ei", [None, None], 1, 4, None),
+            _ => ("db", [Some(Immediate8(opcode)), None], 1, 4, None),
+        },
+        (_, 4) if y < 4 => (
+            "call",
+            [Some(Condition(CONDITIONS[y as usize])), Some(Target(nn))],
+            3,
+            12,
+            Some(24),
+        ),
+        (_, 5) if q == 0 => ("push", [Some(Register(STACK_PAIRS[p])), None], 1, 16, None),
+        (_, 5) if p == 0 => ("call", [Some(Target(nn)), None], 3, 24, None),
+        (_, 6) => (
+            ALU[y as usize],
+            [Some(Register("a")), Some(Immediate8(n))],
+            2,
+            8,
+            None,
+        ),
+        (_, 7) => ("rst", [Some(Target(y as u16 * 8)), None], 1, 16, None),
+        _ => ("db", [Some(Immediate8(opcode)), None], 1, 4, None),
+    };
+
+    Instruction {
+        address,
+        opcode: opcode as u16,
+        mnemonic,
+        operands,
+        length,
+        cycles,
+        branch_cycles,
+    }
+}
+
+/// Decodes a CB prefixed instruction, given its second byte.
+fn disassemble_cb(address: u16, opcode: u8) -> Instruction {
+    let y = (opcode >> 3) & 0x7;
+    let z = opcode & 0x7;
+
+    let (mnemonic, operands) = match opcode >> 6 {
+        0 => (ROTATIONS[y as usize], [Some(register(z)), None]),
+        1 => ("bit", [Some(Operand::Bit(y)), Some(register(z))]),
+        2 => ("res", [Some(Operand::Bit(y)), Some(register(z))]),
+        _ => ("set", [Some(Operand::Bit(y)), Some(register(z))]),
+    };
+
+    // BIT only reads (HL), rather than reading and writing it back
+    let cycles = match (z, opcode >> 6) {
+        (6, 1) => 12,
+        (6, _) => 16,
+        _ => 8,
+    };
+
+    Instruction {
+        address,
+        opcode: 0xCB | (opcode as u16) << 8,
+        mnemonic,
+        operands,
+        length: 2,
+        cycles,
+        branch_cycles: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::string::ToString;
+    use std::vec;
+    use std::vec::Vec;
+
+    /// Flat RAM, holding a single instruction at 0x100.
+    struct FlatRam {
+        memory: Vec<u8>,
+    }
+
+    impl Bus for FlatRam {
+        fn read(&self, ptr: u16) -> u8 {
+            self.memory[ptr as usize]
+        }
+
+        fn write(&mut self, ptr: u16, val: u8) {
+            self.memory[ptr as usize] = val;
+        }
+
+        fn tick(&mut self, _: u8) -> bool {
+            false
+        }
+    }
+
+    fn disassemble_bytes(bytes: &[u8]) -> Instruction {
+        let mut bus = FlatRam {
+            memory: vec![0; 0x10000],
+        };
+        bus.memory[0x100..0x100 + bytes.len()].copy_from_slice(bytes);
+
+        disassemble(&bus, 0x100)
+    }
+
+    /// Bytes, text, length, cycles, and cycles if the branch is taken.
+    type Case = (&'static [u8], &'static str, u16, u8, Option<u8>);
+
+    #[test]
+    fn decodes_instructions() {
+        let cases: &[Case] = &[
+            (&[0x00], "nop", 1, 4, None),
+            (&[0x01, 0xCD, 0xAB], "ld bc,$ABCD", 3, 12, None),
+            (&[0x08, 0x34, 0x12], "ld ($1234),sp", 3, 20, None),
+            (&[0x10, 0x00], "stop", 2, 4, None),
+            (&[0x18, 0xFE], "jr $0100", 2, 12, None),
+            (&[0x20, 0x05], "jr nz,$0107", 2, 8, Some(12)),
+            (&[0x38, 0x80], "jr c,$0082", 2, 8, Some(12)),
+            (&[0x22], "ld (hl+),a", 1, 8, None),
+            (&[0x35], "dec (hl)", 1, 12, None),
+            (&[0x3E, 0x42], "ld a,$42", 2, 8, None),
+            (&[0x7E], "ld a,(hl)", 1, 8, None),
+            (&[0x76], "halt", 1, 4, None),
+            (&[0x86], "add a,(hl)", 1, 8, None),
+            (&[0xC0], "ret nz", 1, 8, Some(20)),
+            (&[0xC3, 0x50, 0x01], "jp $0150", 3, 16, None),
+            (&[0xC4, 0x00, 0x40], "call nz,$4000", 3, 12, Some(24)),
+            (&[0xE0, 0x44], "ldh ($FF44),a", 2, 12, None),
+            (&[0xE2], "ld ($FF00+c),a", 1, 8, None),
+            (&[0xE8, 0x05], "add sp,5", 2, 16, None),
+            (&[0xEA, 0x00, 0xC0], "ld ($C000),a", 3, 16, None),
+            (&[0xF0, 0x44], "ldh a,($FF44)", 2, 12, None),
+            (&[0xF5], "push af", 1, 16, None),
+            (&[0xF8, 0xFE], "ld hl,sp-2", 2, 12, None),
+            (&[0xFF], "rst $0038", 1, 16, None),
+            (&[0xCB, 0x36], "swap (hl)", 2, 16, None),
+            (&[0xCB, 0x46], "bit 0,(hl)", 2, 12, None),
+            (&[0xCB, 0x7C], "bit 7,h", 2, 8, None),
+            (&[0xCB, 0x86], "res 0,(hl)", 2, 16, None),
+            (&[0xCB, 0xFF], "set 7,a", 2, 8, None),
+            (&[0xD3], "db $D3", 1, 4, None),
+            (&[0xFD], "db $FD", 1, 4, None),
+        ];
+
+        for &(bytes, text, length, cycles, branch_cycles) in cases {
+            let instruction = disassemble_bytes(bytes);
+            assert_eq!(instruction.to_string(), text, "{:02X?}", bytes);
+            assert_eq!(instruction.length, length, "{}", text);
+            assert_eq!(instruction.cycles, cycles, "{}", text);
+            assert_eq!(instruction.branch_cycles, branch_cycles, "{}", text);
+            assert_eq!(instruction.get_next_address(), 0x100 + length, "{}", text);
+        }
+    }
+
+    #[test]
+    fn classifies_instructions() {
+        let prefixed = disassemble_bytes(&[0xCB, 0x7C]);
+        assert_eq!(prefixed.opcode, 0x7CCB);
+
+        assert!(disassemble_bytes(&[0xCD, 0x00, 0x40]).is_call());
+        assert!(disassemble_bytes(&[0xC7]).is_call());
+        assert!(!disassemble_bytes(&[0xC3, 0x00, 0x40]).is_call());
+
+        let illegal = disassemble_bytes(&[0xDD]);
+        assert!(!illegal.is_valid());
+        assert_eq!(illegal.get_operands().count(), 1);
+        assert!(disassemble_bytes(&[0x00]).is_valid());
+    }
+
+    #[test]
+    fn wraps_around_memory() {
+        let mut bus = FlatRam {
+            memory: vec![0; 0x10000],
+        };
+        bus.memory[0xFFFF] = 0xC3;
+        bus.memory[0x0000] = 0x34;
+        bus.memory[0x0001] = 0x12;
+
+        let instruction = disassemble(&bus, 0xFFFF);
+        assert_eq!(instruction.to_string(), "jp $1234");
+        assert_eq!(instruction.get_next_address(), 0x0002);
+    }
+}
@@ -3,21 +3,22 @@
  *
  * Operations to work on bits.
 **/
+use cpu::bus::Bus;
 use cpu::CPU;
 
 /// **0xCB 0x40~0x7D** - *BIT (HL),b* - Test bit X in (hl)
-pub fn bit_phl(cpu: &mut CPU, digit: u8) -> u8 {
+pub fn bit_phl<B: Bus>(cpu: &mut CPU<B>, digit: u8) -> u8 {
     let cur_value = cpu.mem.read(cpu.regs.get_hl());
     cpu.regs.set_flag_z((cur_value >> digit) & 0x1 == 0);
     cpu.regs.set_flag_n(false);
     cpu.regs.set_flag_h(true);
-    16 /* Cycles */
+    12 /* Cycles */
 }
 
 /// *0xCB 0x40~0x7D** - *BIT X,b* - Test bit X in Y
 macro_rules! bit {
     ($name:ident, $reg:ident) => {
-        pub fn $name(cpu: &mut CPU, digit: u8) -> u8 {
+        pub fn $name<B: Bus>(cpu: &mut CPU<B>, digit: u8) -> u8 {
             let cur_value = cpu.regs.$reg;
             cpu.regs.set_flag_z((cur_value >> digit) & 0x1 == 0);
             cpu.regs.set_flag_n(false);
@@ -38,7 +39,7 @@ bit!(bit_a, a);
 /// **0xCB 0xC0 ~ 0xCB 0xFF** - *SET X,Y* - Set bit X in register Y
 macro_rules! set {
     ($name:ident, $reg:ident) => {
-        pub fn $name(cpu: &mut CPU, digit: u8) -> u8 {
+        pub fn $name<B: Bus>(cpu: &mut CPU<B>, digit: u8) -> u8 {
             cpu.regs.$reg |= 1 << digit;
             8 /* Cycles */
         }
@@ -54,7 +55,7 @@ set!(set_l, l);
 set!(set_a, a);
 
 /// **0xCB 0xC0 ~ 0xCB 0xFF** - *SET X,(hl)* - Set bit X in (hl)
-pub fn set_x_hl(cpu: &mut CPU, digit: u8) -> u8 {
+pub fn set_x_hl<B: Bus>(cpu: &mut CPU<B>, digit: u8) -> u8 {
     let read_data = cpu.mem.read(cpu.regs.get_hl()) | (1 << digit);
     cpu.mem.write(cpu.regs.get_hl(), read_data);
 
@@ -64,7 +65,7 @@ pub fn set_x_hl(cpu: &mut CPU, digit: u8) -> u8 {
 /// **0xCB 0x80 ~ 0xCB 0xBF** - *RES X,Y* - Reset bit X in register Y
 macro_rules! res {
     ($name:ident, $reg:ident) => {
-        pub fn $name(cpu: &mut CPU, digit: u8) -> u8 {
+        pub fn $name<B: Bus>(cpu: &mut CPU<B>, digit: u8) -> u8 {
             cpu.regs.$reg &= !(1 << digit);
             8 /* Cycles */
         }
@@ -80,7 +81,7 @@ res!(res_l, l);
 res!(res_a, a);
 
 /// **0xCB 0x80 ~ 0xCB 0xBF** - *RES X,(hl)* - Reset bit X in (hl)
-pub fn res_x_hl(cpu: &mut CPU, digit: u8) -> u8 {
+pub fn res_x_hl<B: Bus>(cpu: &mut CPU<B>, digit: u8) -> u8 {
     let read_data = cpu.mem.read(cpu.regs.get_hl()) & !(1 << digit);
     cpu.mem.write(cpu.regs.get_hl(), read_data);
 
@@ -3,11 +3,12 @@
  *
  * Operations to rotate bits.
 **/
+use cpu::bus::Bus;
 use cpu::CPU;
 
 /// Helper for RL operations.
 #[inline]
-fn rl_helper(cpu: &mut CPU, value: u8) -> u8 {
+fn rl_helper<B: Bus>(cpu: &mut CPU<B>, value: u8) -> u8 {
     let old_flag = if cpu.regs.get_flag_c() { 1 } else { 0 };
 
     let result = ((value << 1) & !1) | old_flag;
@@ -20,7 +21,7 @@ fn rl_helper(cpu: &mut CPU, value: u8) -> u8 {
 }
 
 /// **0x17** - *RLA* - Rotate a left through Carry.
-pub fn rla(cpu: &mut CPU) -> u8 {
+pub fn rla<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     let current_value = cpu.regs.a;
     cpu.regs.a = rl_helper(cpu, current_value);
     cpu.regs.set_flag_z(false);
@@ -29,7 +30,7 @@ pub fn rla(cpu: &mut CPU) -> u8 {
 }
 
 /// **0xCB 0x16** - *RL (hl)* - Rotate (hl) left through Carry.
-pub fn rl_phl(cpu: &mut CPU) -> u8 {
+pub fn rl_phl<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     let current_value = cpu.mem.read(cpu.regs.get_hl());
     let result = rl_helper(cpu, current_value);
     cpu.mem.write(cpu.regs.get_hl(), result);
@@ -39,7 +40,7 @@ pub fn rl_phl(cpu: &mut CPU) -> u8 {
 
 macro_rules! rl {
     ($func:ident, $reg:ident) => {
-        pub fn $func(cpu: &mut CPU) -> u8 {
+        pub fn $func<B: Bus>(cpu: &mut CPU<B>) -> u8 {
             let current_value = cpu.regs.$reg;
             cpu.regs.$reg = rl_helper(cpu, current_value);
             8 /* Cycles */
@@ -57,7 +58,7 @@ rl!(rl_a, a);
 
 /// Helper for RR operations.
 #[inline]
-fn rr_helper(cpu: &mut CPU, value: u8) -> u8 {
+fn rr_helper<B: Bus>(cpu: &mut CPU<B>, value: u8) -> u8 {
     let old_flag = if cpu.regs.get_flag_c() { 1 << 7 } else { 0 };
 
     let result = (value >> 1) | old_flag;
@@ -70,7 +71,7 @@ fn rr_helper(cpu: &mut CPU, value: u8) -> u8 {
 }
 
 /// **0x1F** - *RRA* - Rotate a right through Carry.
-pub fn rra(cpu: &mut CPU) -> u8 {
+pub fn rra<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     let current_value = cpu.regs.a;
     cpu.regs.a = rr_helper(cpu, current_value);
     cpu.regs.set_flag_z(false);
@@ -79,7 +80,7 @@ pub fn rra(cpu: &mut CPU) -> u8 {
 }
 
 /// **0xCB 0x1E** - *RR (hl)* - Rotate (hl) right through Carry.
-pub fn rr_phl(cpu: &mut CPU) -> u8 {
+pub fn rr_phl<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     let current_value = cpu.mem.read(cpu.regs.get_hl());
     let result = rr_helper(cpu, current_value);
     cpu.mem.write(cpu.regs.get_hl(), result);
@@ -89,7 +90,7 @@ pub fn rr_phl(cpu: &mut CPU) -> u8 {
 
 macro_rules! rr {
     ($func:ident, $reg:ident) => {
-        pub fn $func(cpu: &mut CPU) -> u8 {
+        pub fn $func<B: Bus>(cpu: &mut CPU<B>) -> u8 {
             let current_value = cpu.regs.$reg;
             cpu.regs.$reg = rr_helper(cpu, current_value);
             8 /* Cycles */
@@ -107,7 +108,7 @@ rr!(rr_a, a);
 
 /// Helper for RLC operations.
 #[inline]
-fn rlc_helper(cpu: &mut CPU, value: u8) -> u8 {
+fn rlc_helper<B: Bus>(cpu: &mut CPU<B>, value: u8) -> u8 {
     let result = ((value << 1) & !1) | ((value >> 7) & 0x1);
 
     cpu.regs.f = 0;
@@ -118,7 +119,7 @@ fn rlc_helper(cpu: &mut CPU, value: u8) -> u8 {
 }
 
 /// **0x07** - *RLCA* - Rotate a left. Bit 7 into Carry.
-pub fn rlca(cpu: &mut CPU) -> u8 {
+pub fn rlca<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     let current_value = cpu.regs.a;
     cpu.regs.a = rlc_helper(cpu, current_value);
     cpu.regs.set_flag_z(false);
@@ -127,7 +128,7 @@ pub fn rlca(cpu: &mut CPU) -> u8 {
 }
 
 /// **0xCB 0x06** - *RLC (hl)* - Rotate a left. Bit 7 into Carry.
-pub fn rlc_phl(cpu: &mut CPU) -> u8 {
+pub fn rlc_phl<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     let current_value = cpu.mem.read(cpu.regs.get_hl());
     let result = rlc_helper(cpu, current_value);
     cpu.mem.write(cpu.regs.get_hl(), result);
@@ -137,7 +138,7 @@ pub fn rlc_phl(cpu: &mut CPU) -> u8 {
 
 macro_rules! rlc {
     ($func:ident, $reg:ident) => {
-        pub fn $func(cpu: &mut CPU) -> u8 {
+        pub fn $func<B: Bus>(cpu: &mut CPU<B>) -> u8 {
             let current_value = cpu.regs.$reg;
             cpu.regs.$reg = rlc_helper(cpu, current_value);
             8 /* Cycles */
@@ -155,7 +156,7 @@ rlc!(rlc_a, a);
 
 /// Helper for RRC operations.
 #[inline]
-fn rrc_helper(cpu: &mut CPU, value: u8) -> u8 {
+fn rrc_helper<B: Bus>(cpu: &mut CPU<B>, value: u8) -> u8 {
     let result = ((value >> 1) & !(1 << 7)) | ((value & 1) << 7);
 
     cpu.regs.f = 0;
@@ -166,7 +167,7 @@ fn rrc_helper(cpu: &mut CPU, value: u8) -> u8 {
 }
 
 /// **0x07** - *RRCA* - Rotate a left. Bit 7 into Carry.
-pub fn rrca(cpu: &mut CPU) -> u8 {
+pub fn rrca<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     let current_value = cpu.regs.a;
     cpu.regs.a = rrc_helper(cpu, current_value);
     cpu.regs.set_flag_z(false);
@@ -175,7 +176,7 @@ pub fn rrca(cpu: &mut CPU) -> u8 {
 }
 
 /// **0xCB 0x0E** - *RRC (hl)* - Rotate (hl) right. Bit 0 into Carry.
-pub fn rrc_phl(cpu: &mut CPU) -> u8 {
+pub fn rrc_phl<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     let current_value = cpu.mem.read(cpu.regs.get_hl());
     let result = rrc_helper(cpu, current_value);
     cpu.mem.write(cpu.regs.get_hl(), result);
@@ -185,7 +186,7 @@ pub fn rrc_phl(cpu: &mut CPU) -> u8 {
 
 macro_rules! rrc {
     ($func:ident, $reg:ident) => {
-        pub fn $func(cpu: &mut CPU) -> u8 {
+        pub fn $func<B: Bus>(cpu: &mut CPU<B>) -> u8 {
             let current_value = cpu.regs.$reg;
             cpu.regs.$reg = rrc_helper(cpu, current_value);
             8 /* Cycles */
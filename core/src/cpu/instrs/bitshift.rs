@@ -3,10 +3,11 @@
  *
  * Operations to shift bits.
 **/
+use cpu::bus::Bus;
 use cpu::CPU;
 
 /// **0xCB 0x26** - *SLA (hl)* - Shift (hl) left into Carry. LSB of a set to 0.
-pub fn sla_phl(cpu: &mut CPU) -> u8 {
+pub fn sla_phl<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     let current_value = cpu.mem.read(cpu.regs.get_hl());
     let new_value = (current_value << 1) & !1; // & (0b01111111);
 
@@ -21,7 +22,7 @@ pub fn sla_phl(cpu: &mut CPU) -> u8 {
 /// **0xCB 0x23** - *SLA X* - Shift X left into Carry. LSB of a set to 0.
 macro_rules! sla {
     ($name:ident, $reg:ident) => {
-        pub fn $name(cpu: &mut CPU) -> u8 {
+        pub fn $name<B: Bus>(cpu: &mut CPU<B>) -> u8 {
             let current_value = cpu.regs.$reg;
             let new_value = (current_value << 1) & !1; // & (0b01111111);
 
@@ -44,7 +45,7 @@ sla!(sla_l, l);
 sla!(sla_a, a);
 
 /// **0xCB 0x3E** - *SRL (hl)* - Shift (hl) right through Carry.
-pub fn srl_phl(cpu: &mut CPU) -> u8 {
+pub fn srl_phl<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     let current_value = cpu.mem.read(cpu.regs.get_hl());
     let new_value = current_value >> 1;
 
@@ -54,13 +55,13 @@ pub fn srl_phl(cpu: &mut CPU) -> u8 {
     cpu.regs.f = 0;
     cpu.regs.set_flag_c(current_value & 0x1 == 1);
     cpu.regs.set_flag_z(new_value == 0);
-    8 /* Cycles */
+    16 /* Cycles */
 }
 
 /// **0xCB 0x38 ~ 0xCB 0x3F** - *SRL X* - Shift X right through Carry.
 macro_rules! srl {
     ($name:ident, $reg:ident) => {
-        pub fn $name(cpu: &mut CPU) -> u8 {
+        pub fn $name<B: Bus>(cpu: &mut CPU<B>) -> u8 {
             let current_value = cpu.regs.$reg;
             let new_value = current_value >> 1;
 
@@ -84,7 +85,7 @@ srl!(srl_l, l);
 srl!(srl_a, a);
 
 /// **0xCB 0x2E** - *SRA (hl)* - Shift (hl) right through Carry.
-pub fn sra_phl(cpu: &mut CPU) -> u8 {
+pub fn sra_phl<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     let current_value = cpu.mem.read(cpu.regs.get_hl());
     let new_value = (current_value >> 1) | (current_value & 0b10000000);
 
@@ -99,7 +100,7 @@ pub fn sra_phl(cpu: &mut CPU) -> u8 {
 /// **0xCB 0x28 ~ 0xCB 0x2F** - *SRA X* - Shift X right through Carry.
 macro_rules! sra {
     ($name:ident, $reg:ident) => {
-        pub fn $name(cpu: &mut CPU) -> u8 {
+        pub fn $name<B: Bus>(cpu: &mut CPU<B>) -> u8 {
             let current_value = cpu.regs.$reg;
             let new_value = (current_value >> 1) | (current_value & 0b10000000);
 
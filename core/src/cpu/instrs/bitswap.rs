@@ -3,10 +3,11 @@
  *
  * Operations to swap nibbles in bits.
 **/
+use cpu::bus::Bus;
 use cpu::CPU;
 
 /// **0xCB 0x36** - *SWAP (hl)* - Swaps upper and lower nibbles of (hl).
-pub fn swap_phl(cpu: &mut CPU) -> u8 {
+pub fn swap_phl<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     let current_value = cpu.mem.read(cpu.regs.get_hl());
     // TODO: Check output
     cpu.regs.f = 0;
@@ -22,7 +23,7 @@ pub fn swap_phl(cpu: &mut CPU) -> u8 {
 /// **0xCB 0x30 ~ 0x37** - *SWAP X* - Swaps upper and lower nibbles of X.
 macro_rules! swap {
     ($name:ident, $reg:ident) => {
-        pub fn $name(cpu: &mut CPU) -> u8 {
+        pub fn $name<B: Bus>(cpu: &mut CPU<B>) -> u8 {
             let current_value = cpu.regs.$reg;
             // TODO: Check output
             cpu.regs.f = 0;
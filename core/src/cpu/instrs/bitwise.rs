@@ -4,6 +4,7 @@ use cpu::regs::Registers;
  *
  * Bitwise operations.
 **/
+use cpu::bus::Bus;
 use cpu::CPU;
 
 /// Helper to XOR something into A.
@@ -18,7 +19,7 @@ fn reg_xor(registers: &mut Registers, y_val: u8) {
 }
 
 /// **0xA8 ~ 0xAF** - *XOR X* - Xor X with a into a
-pub fn xor(x: u8, cpu: &mut CPU) -> u8 {
+pub fn xor<B: Bus>(x: u8, cpu: &mut CPU<B>) -> u8 {
     reg_xor(&mut cpu.regs, x);
 
     4 /* Cycles */
@@ -27,7 +28,7 @@ pub fn xor(x: u8, cpu: &mut CPU) -> u8 {
 /**
  * **0xAE** - *XOR (hl)* - Xor \*hl with a into a
  */
-pub fn xor_hl(cpu: &mut CPU) -> u8 {
+pub fn xor_hl<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     // TODO: Check that this is correct
     let value = cpu.mem.read(cpu.regs.get_hl());
     reg_xor(&mut cpu.regs, value);
@@ -38,7 +39,7 @@ pub fn xor_hl(cpu: &mut CPU) -> u8 {
 /**
  * **0xEE** - *XOR #* - Xor # with a into a
  */
-pub fn xor_n(cpu: &mut CPU) -> u8 {
+pub fn xor_n<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     let value = cpu.mem.read(cpu.regs.pc);
     cpu.regs.pc += 1;
     reg_xor(&mut cpu.regs, value);
@@ -66,7 +67,7 @@ fn reg_or(registers: &mut Registers, y_val: u8) {
 /**
  * **0xB0 ~ 0xB7** - *OR X* - Or X with a into a
  */
-pub fn or(x: u8, cpu: &mut CPU) -> u8 {
+pub fn or<B: Bus>(x: u8, cpu: &mut CPU<B>) -> u8 {
     reg_or(&mut cpu.regs, x);
 
     4 /* Cycles */
@@ -75,7 +76,7 @@ pub fn or(x: u8, cpu: &mut CPU) -> u8 {
 /**
  * **0xB6** - *OR (hl)* - Or *hl with a into a
  */
-pub fn or_phl(cpu: &mut CPU) -> u8 {
+pub fn or_phl<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     // TODO: Check these *hl's
     let value = cpu.mem.read(cpu.regs.get_hl());
     reg_or(&mut cpu.regs, value);
@@ -86,7 +87,7 @@ pub fn or_phl(cpu: &mut CPU) -> u8 {
 /**
  * **0xF6** - *OR #* - or # with a into a
  */
-pub fn or_n(cpu: &mut CPU) -> u8 {
+pub fn or_n<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     let value = cpu.mem.read(cpu.regs.pc);
     cpu.regs.pc += 1;
     reg_or(&mut cpu.regs, value);
@@ -115,7 +116,7 @@ fn reg_and(registers: &mut Registers, y_val: u8) {
 /**
  * **0xA0 ~ 0xA7** - *AND X* - AND X with a into a
  */
-pub fn and(x: u8, cpu: &mut CPU) -> u8 {
+pub fn and<B: Bus>(x: u8, cpu: &mut CPU<B>) -> u8 {
     reg_and(&mut cpu.regs, x);
 
     4 /* Cycles */
@@ -124,7 +125,7 @@ pub fn and(x: u8, cpu: &mut CPU) -> u8 {
 /**
  * **0xA6** - *AND (hl)* - And (hl) with a into a
  */
-pub fn and_phl(cpu: &mut CPU) -> u8 {
+pub fn and_phl<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     let value = cpu.mem.read(cpu.regs.get_hl());
     reg_and(&mut cpu.regs, value);
 
@@ -134,7 +135,7 @@ pub fn and_phl(cpu: &mut CPU) -> u8 {
 /**
  * **0xE6** - *AND #* - And # with a into a
  */
-pub fn and_n(cpu: &mut CPU) -> u8 {
+pub fn and_n<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     let value = cpu.mem.read(cpu.regs.pc);
     cpu.regs.pc += 1;
     reg_and(&mut cpu.regs, value);
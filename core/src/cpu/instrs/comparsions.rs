@@ -4,6 +4,7 @@ use cpu::regs::Registers;
  *
  * Operations to compare registers/memory against each other.
 **/
+use cpu::bus::Bus;
 use cpu::CPU;
 
 /// Helper to compare registers
@@ -18,7 +19,7 @@ fn compare_registers(registers: &mut Registers, x: u8, y: u8) {
 }
 
 /// **0xB8 ~ 0xBE** - *CP X* - Compares a with field X
-pub fn cp(x: u8, cpu: &mut CPU) -> u8 {
+pub fn cp<B: Bus>(x: u8, cpu: &mut CPU<B>) -> u8 {
     let y = cpu.regs.a;
     compare_registers(&mut cpu.regs, y, x);
 
@@ -26,7 +27,7 @@ pub fn cp(x: u8, cpu: &mut CPU) -> u8 {
 }
 
 /// **0xBE** - *CP (hl)* - Compare a with (hl)
-pub fn cp_phl(cpu: &mut CPU) -> u8 {
+pub fn cp_phl<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     let value = cpu.mem.read(cpu.regs.get_hl());
     let y = cpu.regs.a;
     compare_registers(&mut cpu.regs, y, value);
@@ -35,7 +36,7 @@ pub fn cp_phl(cpu: &mut CPU) -> u8 {
 }
 
 /// **0xFE** - *CP #* - Compare a with #
-pub fn cp_n(cpu: &mut CPU) -> u8 {
+pub fn cp_n<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     let value = cpu.mem.read(cpu.regs.pc);
     cpu.regs.pc += 1;
     let y = cpu.regs.a;
@@ -3,6 +3,7 @@
  *
  * General arithmetic.
 **/
+use cpu::bus::Bus;
 use cpu::CPU;
 
 use cpu::instrs::utils::*;
@@ -10,7 +11,7 @@ use cpu::instrs::utils::*;
 /// **0x80 ~ 0xE8** - *ADD X* - Add X to a.
 macro_rules! add {
     ($name:ident, $reg:ident) => {
-        pub fn $name(cpu: &mut CPU) -> u8 {
+        pub fn $name<B: Bus>(cpu: &mut CPU<B>) -> u8 {
             let x = cpu.regs.a;
             let y = cpu.regs.$reg;
             let new_value = x.wrapping_add(y);
@@ -37,7 +38,7 @@ add!(add_a, a);
 /// **0x80 ~ 0xE8** - *ADC X* - Add X to a with carry.
 macro_rules! adc {
     ($name:ident, $reg:ident) => {
-        pub fn $name(cpu: &mut CPU) -> u8 {
+        pub fn $name<B: Bus>(cpu: &mut CPU<B>) -> u8 {
             let x = cpu.regs.a;
             let y = cpu.regs.$reg;
             let carry = if cpu.regs.get_flag_c() { 1 } else { 0 };
@@ -66,7 +67,7 @@ adc!(adc_a, a);
 /**
  * **0xC6** - *ADD a,#* - Add # to a.
  */
-pub fn add_a_n(cpu: &mut CPU) -> u8 {
+pub fn add_a_n<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     let prev_value = cpu.regs.a;
     let value = get_n(cpu);
     let new_value = prev_value.wrapping_add(value);
@@ -86,7 +87,7 @@ pub fn add_a_n(cpu: &mut CPU) -> u8 {
 /**
  * **0x86** - *ADD (hl)* - Add *hl to a.
  */
-pub fn add_a_phl(cpu: &mut CPU) -> u8 {
+pub fn add_a_phl<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     let prev_value = cpu.regs.a;
     let value = cpu.mem.read(cpu.regs.get_hl());
     let new_value = prev_value.wrapping_add(value);
@@ -106,7 +107,7 @@ pub fn add_a_phl(cpu: &mut CPU) -> u8 {
 /**
  * **0xD6** - *SUB a,#* - Subtract # to a.
  */
-pub fn sub_a_n(cpu: &mut CPU) -> u8 {
+pub fn sub_a_n<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     let prev_value = cpu.regs.a;
     let value = get_n(cpu);
     let new_value = prev_value.wrapping_sub(value);
@@ -124,7 +125,7 @@ pub fn sub_a_n(cpu: &mut CPU) -> u8 {
 }
 
 /// **0x19 ~ 0x39** - *ADD HL,X* - Add XX to HL.
-pub fn add_hl_x(val: u16, cpu: &mut CPU) -> u8 {
+pub fn add_hl_x<B: Bus>(val: u16, cpu: &mut CPU<B>) -> u8 {
     // TODO: Check accuracy of flags
     let prev_value = cpu.regs.get_hl();
     cpu.regs.set_hl(prev_value.wrapping_add(val));
@@ -140,7 +141,7 @@ pub fn add_hl_x(val: u16, cpu: &mut CPU) -> u8 {
 }
 
 /// **0xCE** - *ADC a,#* - Add # + Carry flag to a.
-pub fn adc_a_n(cpu: &mut CPU) -> u8 {
+pub fn adc_a_n<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     let value = get_n(cpu);
     let old_value = cpu.regs.a;
     let new_value = old_value
@@ -164,7 +165,7 @@ pub fn adc_a_n(cpu: &mut CPU) -> u8 {
 }
 
 /// **0xCE** - *ADC a,#* - Add # + Carry flag to a.
-pub fn adc_a_phl(cpu: &mut CPU) -> u8 {
+pub fn adc_a_phl<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     let value = cpu.mem.read(cpu.regs.get_hl());
     let old_value = cpu.regs.a;
     let new_value = old_value
@@ -188,7 +189,7 @@ pub fn adc_a_phl(cpu: &mut CPU) -> u8 {
 }
 
 /// **0xE8** - *ADD sp,#S* - Add signed value # to sp.
-pub fn add_sp_ns(cpu: &mut CPU) -> u8 {
+pub fn add_sp_ns<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     let prev_value = cpu.regs.sp;
     let value = get_n(cpu) as i8;
     let result = (prev_value as i16).wrapping_add(value as i16) as u16;
@@ -215,7 +216,7 @@ pub fn add_sp_ns(cpu: &mut CPU) -> u8 {
 /// **0x90 ~ 0x95** - *SUB A,X* - Subtract X from A.
 macro_rules! sub {
     ($name:ident, $reg:ident) => {
-        pub fn $name(cpu: &mut CPU) -> u8 {
+        pub fn $name<B: Bus>(cpu: &mut CPU<B>) -> u8 {
             let x = cpu.regs.$reg;
             let prev_value = cpu.regs.a;
             let new_value = prev_value.wrapping_sub(x);
@@ -241,7 +242,7 @@ sub!(sub_l, l);
 sub!(sub_a, a);
 
 /// **0x96** - *SUB (hl)* - Subtract *hl from a.
-pub fn sub_phl(cpu: &mut CPU) -> u8 {
+pub fn sub_phl<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     let x = cpu.mem.read(cpu.regs.get_hl());
     let prev_value = cpu.regs.a;
     let new_value = prev_value.wrapping_sub(x);
@@ -259,7 +260,7 @@ pub fn sub_phl(cpu: &mut CPU) -> u8 {
 /// **0x80 ~ 0xE8** - *SBC X* - Subtract X from a with carry.
 macro_rules! sbc {
     ($name:ident, $reg:ident) => {
-        pub fn $name(cpu: &mut CPU) -> u8 {
+        pub fn $name<B: Bus>(cpu: &mut CPU<B>) -> u8 {
             let x = cpu.regs.a;
             let y = cpu.regs.$reg;
             let carry = if cpu.regs.get_flag_c() { 1 } else { 0 };
@@ -287,7 +288,7 @@ sbc!(sbc_l, l);
 sbc!(sbc_a, a);
 
 /// **0x9E** - *SBC a,(hl)* - Subtract (hl) + Carry flag from A.
-pub fn sbc_phl(cpu: &mut CPU) -> u8 {
+pub fn sbc_phl<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     let prev_value = cpu.regs.a;
     let value = cpu.mem.read(cpu.regs.get_hl());
     let flag = if cpu.regs.get_flag_c() { 1 } else { 0 };
@@ -306,7 +307,7 @@ pub fn sbc_phl(cpu: &mut CPU) -> u8 {
 }
 
 /// **0xDE** - *SBC n* -  Subtract n + Carry from a.
-pub fn sbc_n(cpu: &mut CPU) -> u8 {
+pub fn sbc_n<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     let prev_value = cpu.regs.a;
     let value = get_n(cpu);
     let flag = if cpu.regs.get_flag_c() { 1 } else { 0 };
@@ -3,12 +3,13 @@
  *
  * Operations to branch code.
 **/
+use cpu::bus::Bus;
 use cpu::CPU;
 
 use cpu::instrs::utils::*;
 
 /// **0x18** - *JR n* - Jump to pc+n
-pub fn jr_n(cpu: &mut CPU) -> u8 {
+pub fn jr_n<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     let n = cpu.mem.read(cpu.regs.pc) as i8 as i16;
     cpu.regs.pc = ((cpu.regs.pc as i16).wrapping_add(n.wrapping_add(1))) as u16 /* +1 for n size */;
 
@@ -23,7 +24,7 @@ pub fn jr_n(cpu: &mut CPU) -> u8 {
 }
 
 /// **0x20** - *JR NZ,n* - Jump if Z flag is reset
-pub fn jr_nz_n(cpu: &mut CPU) -> u8 {
+pub fn jr_nz_n<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     if !cpu.regs.get_flag_z() {
         let n = get_n(cpu) as i8;
         cpu.regs.pc = (cpu.regs.pc as i16 + n as i16) as u16;
@@ -35,7 +36,7 @@ pub fn jr_nz_n(cpu: &mut CPU) -> u8 {
 }
 
 /// **0x28** - *JR Z,n* - Jump if Z flag is set
-pub fn jr_z_n(cpu: &mut CPU) -> u8 {
+pub fn jr_z_n<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     if cpu.regs.get_flag_z() {
         let n = cpu.mem.read(cpu.regs.pc) as i8 as i16;
         cpu.regs.pc = ((cpu.regs.pc as i16).wrapping_add(n.wrapping_add(1))) as u16 /* +1 for n size */;
@@ -47,7 +48,7 @@ pub fn jr_z_n(cpu: &mut CPU) -> u8 {
 }
 
 /// **0x30** - *JR NC,n* - Jump if C flag is reset
-pub fn jr_nc_n(cpu: &mut CPU) -> u8 {
+pub fn jr_nc_n<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     if !cpu.regs.get_flag_c() {
         let n = cpu.mem.read(cpu.regs.pc) as i8 as i16;
         cpu.regs.pc = ((cpu.regs.pc as i16).wrapping_add(n.wrapping_add(1))) as u16 /* +1 for n size */;
@@ -59,7 +60,7 @@ pub fn jr_nc_n(cpu: &mut CPU) -> u8 {
 }
 
 /// **0x38** - *JR C,n* - Jump if C flag is set
-pub fn jr_c_n(cpu: &mut CPU) -> u8 {
+pub fn jr_c_n<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     if cpu.regs.get_flag_c() {
         let n = cpu.mem.read(cpu.regs.pc) as i8 as i16;
         cpu.regs.pc = ((cpu.regs.pc as i16).wrapping_add(n.wrapping_add(1))) as u16 /* +1 for n size */;
@@ -71,7 +72,7 @@ pub fn jr_c_n(cpu: &mut CPU) -> u8 {
 }
 
 /// **0xC2** - *JP NZ,nn* - Jump to address nn (two byte) if Z flag is reset
-pub fn jp_nz_nn(cpu: &mut CPU) -> u8 {
+pub fn jp_nz_nn<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     if !cpu.regs.get_flag_z() {
         cpu.regs.pc = cpu.mem.read_short(cpu.regs.pc);
         16 /* Cycles */
@@ -82,14 +83,14 @@ pub fn jp_nz_nn(cpu: &mut CPU) -> u8 {
 }
 
 /// **0xC3** - *JMP nn* - Jump to address nn (two byte)
-pub fn jmp_nn(cpu: &mut CPU) -> u8 {
+pub fn jmp_nn<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     cpu.regs.pc = cpu.mem.read_short(cpu.regs.pc);
 
     16 /* Cycles */
 }
 
 /// **0xD2** - *JP NC,nn* - Jump to address nn (two byte) if C flag is reset
-pub fn jp_nc_nn(cpu: &mut CPU) -> u8 {
+pub fn jp_nc_nn<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     if !cpu.regs.get_flag_c() {
         cpu.regs.pc = cpu.mem.read_short(cpu.regs.pc);
 
@@ -102,7 +103,7 @@ pub fn jp_nc_nn(cpu: &mut CPU) -> u8 {
 }
 
 /// **0xDA** - *JP C,nn* - Jump to address nn (two byte) if C flag is set
-pub fn jp_c_nn(cpu: &mut CPU) -> u8 {
+pub fn jp_c_nn<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     if cpu.regs.get_flag_c() {
         cpu.regs.pc = cpu.mem.read_short(cpu.regs.pc);
 
@@ -117,7 +118,7 @@ pub fn jp_c_nn(cpu: &mut CPU) -> u8 {
 /// -- Calls. --
 
 /// **0xC4** - *CALL NZ,nn* - If Z is false jump to address nn and store current pc in stack
-pub fn call_nz_nn(cpu: &mut CPU) -> u8 {
+pub fn call_nz_nn<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     if !cpu.regs.get_flag_z() {
         cpu.regs.sp = cpu.regs.sp.wrapping_sub(2);
         cpu.mem
@@ -133,7 +134,7 @@ pub fn call_nz_nn(cpu: &mut CPU) -> u8 {
 }
 
 /// **0xCA** - *JP Z,nn* - Jump to address nn (two byte) if Z flag is set
-pub fn jp_z_nn(cpu: &mut CPU) -> u8 {
+pub fn jp_z_nn<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     if cpu.regs.get_flag_z() {
         cpu.regs.pc = cpu.mem.read_short(cpu.regs.pc);
 
@@ -146,7 +147,7 @@ pub fn jp_z_nn(cpu: &mut CPU) -> u8 {
 }
 
 /// **0xCC** - *CALL Z,nn* - If Z is true jump to address nn and store current pc in stack
-pub fn call_z_nn(cpu: &mut CPU) -> u8 {
+pub fn call_z_nn<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     if cpu.regs.get_flag_z() {
         cpu.regs.sp -= 2;
         cpu.mem.write_short(cpu.regs.sp, cpu.regs.pc + 2);
@@ -161,7 +162,7 @@ pub fn call_z_nn(cpu: &mut CPU) -> u8 {
 }
 
 /// **0xCD** - *CALL nn* - Jump to address nn and store current pc in stack
-pub fn call_nn(cpu: &mut CPU) -> u8 {
+pub fn call_nn<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     cpu.regs.sp -= 2;
     cpu.mem.write_short(cpu.regs.sp, cpu.regs.pc + 2);
     cpu.regs.pc = cpu.mem.read_short(cpu.regs.pc);
@@ -170,7 +171,7 @@ pub fn call_nn(cpu: &mut CPU) -> u8 {
 }
 
 /// **0xD4** - *CALL NC,nn* - If N is false jump to address nn and store current pc in stack
-pub fn call_nc_nn(cpu: &mut CPU) -> u8 {
+pub fn call_nc_nn<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     if !cpu.regs.get_flag_c() {
         cpu.regs.sp -= 2;
         cpu.mem.write_short(cpu.regs.sp, cpu.regs.pc + 2);
@@ -185,7 +186,7 @@ pub fn call_nc_nn(cpu: &mut CPU) -> u8 {
 }
 
 /// **0xDC** - *CALL C,nn* - If N is true jump to address nn and store current pc in stack
-pub fn call_c_nn(cpu: &mut CPU) -> u8 {
+pub fn call_c_nn<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     if cpu.regs.get_flag_c() {
         cpu.regs.sp -= 2;
         cpu.mem.write_short(cpu.regs.sp, cpu.regs.pc + 2);
@@ -202,7 +203,7 @@ pub fn call_c_nn(cpu: &mut CPU) -> u8 {
 /// -- Returns. --
 
 /// **0xC0** - *RET nz* - Return if Z flag is reset
-pub fn ret_nz(cpu: &mut CPU) -> u8 {
+pub fn ret_nz<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     if !cpu.regs.get_flag_z() {
         cpu.regs.pc = cpu.mem.read_short(cpu.regs.sp);
         cpu.regs.sp += 2;
@@ -214,7 +215,7 @@ pub fn ret_nz(cpu: &mut CPU) -> u8 {
 }
 
 /// **0xC8** - *RET z* - Return if Z flag is set
-pub fn ret_z(cpu: &mut CPU) -> u8 {
+pub fn ret_z<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     if cpu.regs.get_flag_z() {
         cpu.regs.pc = cpu.mem.read_short(cpu.regs.sp);
         cpu.regs.sp = cpu.regs.sp.wrapping_add(2);
@@ -226,7 +227,7 @@ pub fn ret_z(cpu: &mut CPU) -> u8 {
 }
 
 /// **0xC9** - *RET* - Pop from stack, and jump to this address
-pub fn ret(cpu: &mut CPU) -> u8 {
+pub fn ret<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     cpu.regs.pc = cpu.mem.read_short(cpu.regs.sp);
     cpu.regs.sp = cpu.regs.sp.wrapping_add(2);
 
@@ -234,7 +235,7 @@ pub fn ret(cpu: &mut CPU) -> u8 {
 }
 
 /// **0xD0** - *RET nc* - Return if C flag is reset
-pub fn ret_nc(cpu: &mut CPU) -> u8 {
+pub fn ret_nc<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     if !cpu.regs.get_flag_c() {
         cpu.regs.pc = cpu.mem.read_short(cpu.regs.sp);
         cpu.regs.sp = cpu.regs.sp.wrapping_add(2);
@@ -246,7 +247,7 @@ pub fn ret_nc(cpu: &mut CPU) -> u8 {
 }
 
 /// **0xD8** - *RET c* - Return if C flag is set
-pub fn ret_c(cpu: &mut CPU) -> u8 {
+pub fn ret_c<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     if cpu.regs.get_flag_c() {
         cpu.regs.pc = cpu.mem.read_short(cpu.regs.sp);
         cpu.regs.sp = cpu.regs.sp.wrapping_add(2);
@@ -258,7 +259,7 @@ pub fn ret_c(cpu: &mut CPU) -> u8 {
 }
 
 /// **0xD9** - *RETI* - Return and enable interrupts
-pub fn reti(cpu: &mut CPU) -> u8 {
+pub fn reti<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     cpu.regs.pc = cpu.mem.read_short(cpu.regs.sp);
     cpu.regs.sp = cpu.regs.sp.wrapping_add(2);
 
@@ -268,17 +269,17 @@ pub fn reti(cpu: &mut CPU) -> u8 {
 }
 
 /// **0xE9** - *JMP hl* - Jump to hl.
-pub fn jmp_hl(cpu: &mut CPU) -> u8 {
+pub fn jmp_hl<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     cpu.regs.pc = cpu.regs.get_hl();
 
     4 /* Cycles */
 }
 
 /// -- Restarts. --
-pub fn rst(cpu: &mut CPU, step: u16) -> u8 {
+pub fn rst<B: Bus>(cpu: &mut CPU<B>, step: u16) -> u8 {
     cpu.regs.sp = cpu.regs.sp.wrapping_sub(2);
     cpu.mem.write_short(cpu.regs.sp, cpu.regs.pc);
     cpu.regs.pc = step;
 
-    32 /* Cycles */
+    16 /* Cycles */
 }
@@ -3,12 +3,13 @@
  *
  * Instructions to store information into registers/memory.
 **/
+use cpu::bus::Bus;
 use cpu::CPU;
 
 use cpu::instrs::utils::*;
 
 /// **0x01** - *LD bc,nnnn* - Put nnnn in bc
-pub fn ld_bc_nnnn(cpu: &mut CPU) -> u8 {
+pub fn ld_bc_nnnn<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     let value = cpu.mem.read_short(cpu.regs.pc);
     cpu.regs.set_bc(value);
     cpu.regs.pc += 2;
@@ -17,7 +18,7 @@ pub fn ld_bc_nnnn(cpu: &mut CPU) -> u8 {
 }
 
 /// **0x08** - *LD (nn),sp* - Put sp at n
-pub fn ld_pnn_sp(cpu: &mut CPU) -> u8 {
+pub fn ld_pnn_sp<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     let pointer = cpu.mem.read_short(cpu.regs.pc);
     cpu.mem.write_short(pointer, cpu.regs.sp);
     cpu.regs.pc += 2;
@@ -26,7 +27,7 @@ pub fn ld_pnn_sp(cpu: &mut CPU) -> u8 {
 }
 
 /// **0x11** - *LD de,nn* - Put nn in de
-pub fn ld_de_nn(cpu: &mut CPU) -> u8 {
+pub fn ld_de_nn<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     let value = cpu.mem.read_short(cpu.regs.pc);
     cpu.regs.set_de(value);
     cpu.regs.pc += 2;
@@ -35,7 +36,7 @@ pub fn ld_de_nn(cpu: &mut CPU) -> u8 {
 }
 
 /// **0x21** - *LD hl,nnnn* - Put nnnn in hl
-pub fn ld_hl_nnnn(cpu: &mut CPU) -> u8 {
+pub fn ld_hl_nnnn<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     let value = cpu.mem.read_short(cpu.regs.pc);
     cpu.regs.set_hl(value);
     cpu.regs.pc += 2;
@@ -44,7 +45,7 @@ pub fn ld_hl_nnnn(cpu: &mut CPU) -> u8 {
 }
 
 /// **0x31** - *LD sp,nn* - Put nn in sp
-pub fn ld_sp_nn(cpu: &mut CPU) -> u8 {
+pub fn ld_sp_nn<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     cpu.regs.sp = cpu.mem.read_short(cpu.regs.pc);
     cpu.regs.pc += 2;
 
@@ -52,7 +53,7 @@ pub fn ld_sp_nn(cpu: &mut CPU) -> u8 {
 }
 
 /// **0xC1** - *POP bc* - Pop stack element into bc
-pub fn pop_bc(cpu: &mut CPU) -> u8 {
+pub fn pop_bc<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     let value = cpu.mem.read_short(cpu.regs.sp);
     cpu.regs.set_bc(value);
     cpu.regs.sp += 2;
@@ -61,7 +62,7 @@ pub fn pop_bc(cpu: &mut CPU) -> u8 {
 }
 
 /// **0xC5** - *PUSH bc* - Push bc onto the stack
-pub fn push_bc(cpu: &mut CPU) -> u8 {
+pub fn push_bc<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     cpu.regs.sp -= 2;
     cpu.mem.write_short(cpu.regs.sp, cpu.regs.get_bc());
 
@@ -69,7 +70,7 @@ pub fn push_bc(cpu: &mut CPU) -> u8 {
 }
 
 /// **0xD1** - *POP de* - Pop stack element into de
-pub fn pop_de(cpu: &mut CPU) -> u8 {
+pub fn pop_de<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     let value = cpu.mem.read_short(cpu.regs.sp);
     cpu.regs.set_de(value);
     cpu.regs.sp += 2;
@@ -78,7 +79,7 @@ pub fn pop_de(cpu: &mut CPU) -> u8 {
 }
 
 /// **0xD5** - *PUSH de* - Push de onto the stack
-pub fn push_de(cpu: &mut CPU) -> u8 {
+pub fn push_de<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     cpu.regs.sp -= 2;
     cpu.mem.write_short(cpu.regs.sp, cpu.regs.get_de());
 
@@ -86,7 +87,7 @@ pub fn push_de(cpu: &mut CPU) -> u8 {
 }
 
 /// **0xE0** - *LDH (n),a* - Put a in memory address *($FF00+n)
-pub fn ldh_pn_a(cpu: &mut CPU) -> u8 {
+pub fn ldh_pn_a<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     let value = 0xFF00 + (cpu.mem.read(cpu.regs.pc) as u16);
     cpu.regs.pc += 1;
     cpu.mem.write(value, cpu.regs.a);
@@ -95,7 +96,7 @@ pub fn ldh_pn_a(cpu: &mut CPU) -> u8 {
 }
 
 /// **0xE1** - *POP hl* - Pop stack element into hl
-pub fn pop_hl(cpu: &mut CPU) -> u8 {
+pub fn pop_hl<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     let value = cpu.mem.read_short(cpu.regs.sp);
     cpu.regs.set_hl(value);
     cpu.regs.sp += 2;
@@ -104,7 +105,7 @@ pub fn pop_hl(cpu: &mut CPU) -> u8 {
 }
 
 /// **0xE2** - *LD (c),a* - Put a in memory address *($FF00+c)
-pub fn ld_pc(cpu: &mut CPU) -> u8 {
+pub fn ld_pc<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     let value = 0xFF00 + ((cpu.regs.c as u16) & 0xFF);
     cpu.mem.write(value, cpu.regs.a);
 
@@ -112,7 +113,7 @@ pub fn ld_pc(cpu: &mut CPU) -> u8 {
 }
 
 /// **0xE5** - *PUSH hl* - Push hl onto the stack
-pub fn push_hl(cpu: &mut CPU) -> u8 {
+pub fn push_hl<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     cpu.regs.sp -= 2;
     cpu.mem.write_short(cpu.regs.sp, cpu.regs.get_hl());
 
@@ -120,7 +121,7 @@ pub fn push_hl(cpu: &mut CPU) -> u8 {
 }
 
 /// **0xEA** - *LD (nn),a* - Put a in \*nn
-pub fn ld_pnn_a(cpu: &mut CPU) -> u8 {
+pub fn ld_pnn_a<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     // Read PC short
     let value = cpu.mem.read_short(cpu.regs.pc);
     cpu.regs.pc += 2;
@@ -131,7 +132,7 @@ pub fn ld_pnn_a(cpu: &mut CPU) -> u8 {
 }
 
 /// **0xF0** - *LDH a,(n)* - Put memory address *($FF00+n) in A
-pub fn ldh_a_pn(cpu: &mut CPU) -> u8 {
+pub fn ldh_a_pn<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     let value = 0xFF00 + (cpu.mem.read(cpu.regs.pc) as u16 & 0xFF);
     //println!("New value: {:04x}", value);
     cpu.regs.a = cpu.mem.read(value);
@@ -141,7 +142,7 @@ pub fn ldh_a_pn(cpu: &mut CPU) -> u8 {
 }
 
 /// **0xF1** - *POP af* - Pop stack element into af
-pub fn pop_af(cpu: &mut CPU) -> u8 {
+pub fn pop_af<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     let value = cpu.mem.read_short(cpu.regs.sp);
     cpu.regs.set_af(value);
     cpu.regs.sp += 2;
@@ -150,7 +151,7 @@ pub fn pop_af(cpu: &mut CPU) -> u8 {
 }
 
 /// **0xF2** - *LD a,(c)* - Put *($FF00+c) into a
-pub fn ld_a_ptrc(cpu: &mut CPU) -> u8 {
+pub fn ld_a_ptrc<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     let value = 0xFF00 + ((cpu.regs.c as u16) & 0xFF);
     cpu.regs.a = cpu.mem.read(value);
 
@@ -158,7 +159,7 @@ pub fn ld_a_ptrc(cpu: &mut CPU) -> u8 {
 }
 
 /// **0xF5** - *PUSH af* - Push af onto the stack
-pub fn push_af(cpu: &mut CPU) -> u8 {
+pub fn push_af<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     cpu.regs.sp -= 2;
     cpu.mem.write_short(cpu.regs.sp, cpu.regs.get_af());
 
@@ -166,7 +167,7 @@ pub fn push_af(cpu: &mut CPU) -> u8 {
 }
 
 /// **0xF8** - *LDHL SP,n* - Put sp + n effective address into hl
-pub fn ldhl_sp_n(cpu: &mut CPU) -> u8 {
+pub fn ldhl_sp_n<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     let prev_value = cpu.regs.sp;
     let cur_value = get_n(cpu) as i8;
 
@@ -191,14 +192,14 @@ pub fn ldhl_sp_n(cpu: &mut CPU) -> u8 {
 }
 
 /// **0xF9** - *LD sp,hl* - Put hl in sp
-pub fn ld_sp_hl(cpu: &mut CPU) -> u8 {
+pub fn ld_sp_hl<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     cpu.regs.sp = cpu.regs.get_hl();
 
     8 /* Cycles */
 }
 
 /// **0xFA** - *LD a,(nn)* - Read \*nn into a
-pub fn ld_a_pnn(cpu: &mut CPU) -> u8 {
+pub fn ld_a_pnn<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     // TODO: WTF?
     cpu.regs.a = cpu.mem.read(cpu.mem.read_short(cpu.regs.pc));
     cpu.regs.pc += 2;
@@ -20,6 +20,11 @@ mod comparsions;
 mod general;
 mod increments;
 
+#[cfg(test)]
+mod tests;
+
+use cpu::bus::Bus;
+use cpu::bus::GameboyBus;
 use cpu::CPU;
 
 use cpu::instrs::jumps::*;
@@ -37,7 +42,7 @@ use cpu::instrs::general::*;
 use cpu::instrs::increments::*;
 
 #[inline]
-pub fn execute_instruction(cpu: &mut CPU, instr: u16, origin: u16) -> u8 {
+pub fn execute_instruction<B: GameboyBus>(cpu: &mut CPU<B>, instr: u16, origin: u16) -> u8 {
     match instr & 0xFF {
         0x00 => nop(cpu),
         0x01 => ld_bc_nnnn(cpu),
@@ -303,7 +308,7 @@ pub fn execute_instruction(cpu: &mut CPU, instr: u16, origin: u16) -> u8 {
 }
 
 #[inline]
-fn cb(cpu: &mut CPU, instr: u16, origin: u16) -> u8 {
+fn cb<B: Bus>(cpu: &mut CPU<B>, instr: u16, origin: u16) -> u8 {
     match (instr >> 8) & 0xFF {
         0x00 => rlc_b(cpu),
         0x01 => rlc_c(cpu),
@@ -3,21 +3,53 @@
  *
  * Special CPU instructions.
 **/
+use cpu::bus::Bus;
+use cpu::bus::GameboyBus;
 use cpu::CPU;
 
+/// How long the CPU is paused for while switching speeds.
+const SPEED_SWITCH_CYCLES: u32 = 8200;
+
 /// **0x00** - *NOP* - No operation.
-pub fn nop(_: &mut CPU) -> u8 {
+pub fn nop<B: Bus>(_: &mut CPU<B>) -> u8 {
     4 /* Cycles */
 }
 
 /// **0x10** (0x00) - *STOP* - Halt CPU & LCD display until button pressed.
-pub fn stop(cpu: &mut CPU) -> u8 {
+pub fn stop<B: GameboyBus>(cpu: &mut CPU<B>) -> u8 {
+    let button_held = cpu.mem.is_button_held();
+    let interrupt_pending = cpu.mem.get_pending_interrupts() != 0;
+
+    // STOP is two bytes long (0x10 0x00), unless a interrupt is pending, in which
+    //  case the second byte is executed as a instruction.
+    if !interrupt_pending {
+        cpu.regs.pc = cpu.regs.pc.wrapping_add(1);
+    }
+
+    if button_held {
+        // The joypad would immediately wake us up again - this ends up as a HALT
+        //  instead, and DIV is left alone.
+        if !interrupt_pending {
+            cpu.halted = true;
+        }
+
+        return 4; /* Cycles */
+    }
+
+    if cpu.mem.stop() {
+        // A speed switch was performed instead
+        cpu.speed_switch_countdown = SPEED_SWITCH_CYCLES;
+
+        return 4; /* Cycles */
+    }
+
     cpu.stopped = true;
+
     4 /* Cycles */
 }
 
 /// **0x27** - *DAA* - Convert to a Binary Coded Decimal
-pub fn daa(cpu: &mut CPU) -> u8 {
+pub fn daa<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     let mut value = cpu.regs.a;
 
     // Uses Blarrg's implementation
@@ -50,7 +82,7 @@ pub fn daa(cpu: &mut CPU) -> u8 {
 }
 
 /// **0x2F** - *CPL* - Complement register a
-pub fn cpl(cpu: &mut CPU) -> u8 {
+pub fn cpl<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     cpu.regs.a = !cpu.regs.a;
 
     cpu.regs.set_flag_n(true);
@@ -60,7 +92,7 @@ pub fn cpl(cpu: &mut CPU) -> u8 {
 }
 
 /// **0x37** - *SCF* - Set carry flag.
-pub fn scf(cpu: &mut CPU) -> u8 {
+pub fn scf<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     cpu.regs.set_flag_n(false);
     cpu.regs.set_flag_h(false);
     cpu.regs.set_flag_c(true);
@@ -69,7 +101,7 @@ pub fn scf(cpu: &mut CPU) -> u8 {
 }
 
 /// **0x3F** - *CCF* - Compliment carry.
-pub fn ccf(cpu: &mut CPU) -> u8 {
+pub fn ccf<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     cpu.regs.set_flag_n(false);
     cpu.regs.set_flag_h(false);
     let new_flag = !cpu.regs.get_flag_c();
@@ -79,7 +111,7 @@ pub fn ccf(cpu: &mut CPU) -> u8 {
 }
 
 /// **0xF3** - *DI* - Disable interrupts
-pub fn di(cpu: &mut CPU) -> u8 {
+pub fn di<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     // TODO: On next instruction?
     cpu.interrupts_countdown = -1;
     cpu.interrupts_enabled = false;
@@ -88,14 +120,14 @@ pub fn di(cpu: &mut CPU) -> u8 {
 }
 
 /// **0xFB** - *EI* - Enable interrupts
-pub fn ei(cpu: &mut CPU) -> u8 {
+pub fn ei<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     cpu.interrupts_countdown = 1; // Countdown to enable
 
     4 /* Cycles */
 }
 
 /// **0x76** - *HALT* - Halt the CPU until a interrupt occurs.
-pub fn halt(cpu: &mut CPU) -> u8 {
+pub fn halt<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     cpu.halted = true;
 
     4 /* Cycles */
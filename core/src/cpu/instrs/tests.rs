@@ -0,0 +1,498 @@
+/**
+ * tests.rs
+ *
+ * Runs each instruction against the community single-step SM83 test vectors
+ *  (https://github.com/SingleStepTests/sm83), which give the CPU state and
+ *  memory before and after executing a single instruction, along with each
+ *  bus cycle it takes.
+ *
+ * The vectors are read from the directory in OXIDGB_SM83_TESTS (containing
+ *  "00.json" through "cb ff.json"); the test is skipped if it isn't set. As
+ *  there are a lot of vectors, running in release mode is recommended:
+ *
+ *  OXIDGB_SM83_TESTS=path/to/sm83/v1 cargo test --release cpu::instrs
+**/
+use cpu::bus::Bus;
+use cpu::bus::GameboyBus;
+use cpu::bus::INTERRUPT_ENABLE;
+use cpu::bus::INTERRUPT_FLAG;
+use cpu::CPU;
+
+use serde_json::Value;
+
+use core::cell::RefCell;
+use core::fmt;
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::string::String;
+use std::string::ToString;
+use std::vec::Vec;
+use std::{format, println, vec};
+
+/// Opcodes (named as their test files are) which are known not to pass yet.
+///  Remove entries from here as they are fixed.
+const KNOWN_FAILURES: &[&str] = &[];
+
+/// Cycles taken by each unprefixed opcode, as (not taken, taken) for
+///  conditional instructions. Illegal opcodes, STOP and the CB prefix are 0.
+#[rustfmt::skip]
+const OPCODE_CYCLES: [(u8, u8); 256] = {
+    const fn row(cycles: [u8; 16]) -> [(u8, u8); 16] {
+        let mut row = [(0, 0); 16];
+        let mut i = 0;
+        while i < 16 {
+            row[i] = (cycles[i], cycles[i]);
+            i += 1;
+        }
+        row
+    }
+
+    let rows = [
+        row([4, 12, 8, 8, 4, 4, 8, 4, 20, 8, 8, 8, 4, 4, 8, 4]),
+        row([0, 12, 8, 8, 4, 4, 8, 4, 12, 8, 8, 8, 4, 4, 8, 4]),
+        row([8, 12, 8, 8, 4, 4, 8, 4, 8, 8, 8, 8, 4, 4, 8, 4]),
+        row([8, 12, 8, 8, 12, 12, 12, 4, 8, 8, 8, 8, 4, 4, 8, 4]),
+        row([4, 4, 4, 4, 4, 4, 8, 4, 4, 4, 4, 4, 4, 4, 8, 4]),
+        row([4, 4, 4, 4, 4, 4, 8, 4, 4, 4, 4, 4, 4, 4, 8, 4]),
+        row([4, 4, 4, 4, 4, 4, 8, 4, 4, 4, 4, 4, 4, 4, 8, 4]),
+        row([8, 8, 8, 8, 8, 8, 4, 8, 4, 4, 4, 4, 4, 4, 8, 4]),
+        row([4, 4, 4, 4, 4, 4, 8, 4, 4, 4, 4, 4, 4, 4, 8, 4]),
+        row([4, 4, 4, 4, 4, 4, 8, 4, 4, 4, 4, 4, 4, 4, 8, 4]),
+        row([4, 4, 4, 4, 4, 4, 8, 4, 4, 4, 4, 4, 4, 4, 8, 4]),
+        row([4, 4, 4, 4, 4, 4, 8, 4, 4, 4, 4, 4, 4, 4, 8, 4]),
+        row([8, 12, 12, 16, 12, 16, 8, 16, 8, 16, 12, 0, 12, 24, 8, 16]),
+        row([8, 12, 12, 0, 12, 16, 8, 16, 8, 16, 12, 0, 12, 0, 8, 16]),
+        row([12, 12, 8, 0, 0, 16, 8, 16, 16, 4, 16, 0, 0, 0, 8, 16]),
+        row([12, 12, 8, 4, 0, 16, 8, 16, 12, 8, 16, 4, 0, 0, 8, 16]),
+    ];
+
+    let mut cycles = [(0, 0); 256];
+    let mut i = 0;
+    while i < 256 {
+        cycles[i] = rows[i >> 4][i & 0xF];
+        i += 1;
+    }
+
+    // Conditional jumps, returns and calls
+    let mut i = 0;
+    while i < 4 {
+        let base = (i & 1) * 8 + (i >> 1) * 0x10;
+        cycles[0x20 + base].1 = 12;
+        cycles[0xC0 + base].1 = 20;
+        cycles[0xC2 + base].1 = 16;
+        cycles[0xC4 + base].1 = 24;
+        i += 1;
+    }
+
+    cycles
+};
+
+/// Cycles taken by a CB prefixed opcode.
+fn get_cb_cycles(opcode: u8) -> u8 {
+    match opcode {
+        // BIT only reads (hl)
+        0x40..=0x7F if opcode & 0x7 == 6 => 12,
+        _ if opcode & 0x7 == 6 => 16,
+        _ => 8,
+    }
+}
+
+/// A read or write on the bus, as listed in a test's cycles.
+#[derive(PartialEq)]
+struct Access {
+    address: u16,
+    value: u8,
+    write: bool,
+}
+
+impl fmt::Display for Access {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kind = if self.write { "write" } else { "read" };
+        write!(f, "{} {:02X} @ {:04X}", kind, self.value, self.address)
+    }
+}
+
+/// 64KiB of flat RAM, so tests can place data at any address. Every access
+///  is recorded, to be compared against the test's bus cycles.
+struct FlatRam {
+    memory: Vec<u8>,
+    accesses: RefCell<Vec<Access>>,
+}
+
+impl Bus for FlatRam {
+    fn read(&self, ptr: u16) -> u8 {
+        let value = self.memory[ptr as usize];
+        self.accesses.borrow_mut().push(Access {
+            address: ptr,
+            value,
+            write: false,
+        });
+        value
+    }
+
+    fn write(&mut self, ptr: u16, val: u8) {
+        self.memory[ptr as usize] = val;
+        self.accesses.borrow_mut().push(Access {
+            address: ptr,
+            value: val,
+            write: true,
+        });
+    }
+
+    fn tick(&mut self, _: u8) -> bool {
+        false
+    }
+}
+
+impl GameboyBus for FlatRam {
+    // The CPU checks for interrupts internally, rather than over the bus
+    fn get_pending_interrupts(&self) -> u8 {
+        self.memory[INTERRUPT_FLAG as usize] & self.memory[INTERRUPT_ENABLE as usize] & 0b11111
+    }
+}
+
+fn build_cpu() -> CPU<'static, FlatRam> {
+    CPU::build_with_bus(FlatRam {
+        memory: vec![0; 0x10000],
+        accesses: RefCell::new(Vec::new()),
+    })
+}
+
+fn get_u16(state: &Value, name: &str) -> u16 {
+    state[name].as_u64().expect("Missing register in test") as u16
+}
+
+/// Returns the (address, value) pairs of memory in a state.
+fn get_ram(state: &Value) -> Vec<(u16, u8)> {
+    state["ram"]
+        .as_array()
+        .map(|entries| {
+            entries
+                .iter()
+                .map(|entry| (entry[0].as_u64().unwrap() as u16, entry[1].as_u64().unwrap() as u8))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn load_state(cpu: &mut CPU<FlatRam>, state: &Value) {
+    let regs = &mut cpu.regs;
+    regs.a = get_u16(state, "a") as u8;
+    regs.b = get_u16(state, "b") as u8;
+    regs.c = get_u16(state, "c") as u8;
+    regs.d = get_u16(state, "d") as u8;
+    regs.e = get_u16(state, "e") as u8;
+    regs.f = get_u16(state, "f") as u8;
+    regs.h = get_u16(state, "h") as u8;
+    regs.l = get_u16(state, "l") as u8;
+    regs.sp = get_u16(state, "sp");
+    regs.pc = get_u16(state, "pc");
+
+    cpu.interrupts_enabled = get_u16(state, "ime") != 0;
+    cpu.interrupts_countdown = -1;
+    cpu.halted = false;
+    cpu.stopped = false;
+
+    for (address, value) in get_ram(state) {
+        cpu.mem.write(address, value);
+    }
+}
+
+/// Compares the CPU against the expected final state, returning a description
+///  of the first difference found.
+fn check_state(cpu: &CPU<FlatRam>, state: &Value) -> Result<(), String> {
+    let regs = &cpu.regs;
+    let registers = [
+        ("a", regs.a as u16),
+        ("b", regs.b as u16),
+        ("c", regs.c as u16),
+        ("d", regs.d as u16),
+        ("e", regs.e as u16),
+        ("f", regs.f as u16),
+        ("h", regs.h as u16),
+        ("l", regs.l as u16),
+        ("sp", regs.sp),
+        ("pc", regs.pc),
+    ];
+
+    for &(name, actual) in registers.iter() {
+        let expected = get_u16(state, name);
+        if actual != expected {
+            return Err(format!("{} = {:04X}, expected {:04X}", name, actual, expected));
+        }
+    }
+
+    // EI only takes effect after the next instruction, so count it as enabled
+    let ime = cpu.interrupts_enabled || cpu.interrupts_countdown >= 0;
+    if ime != (get_u16(state, "ime") != 0) {
+        return Err(format!("ime = {}", ime));
+    }
+
+    for (address, expected) in get_ram(state) {
+        let actual = cpu.mem.read(address);
+        if actual != expected {
+            return Err(format!(
+                "({:04X}) = {:02X}, expected {:02X}",
+                address, actual, expected
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the reads and writes in a test's bus cycles, skipping the cycles
+///  where the bus is idle (as the CPU doesn't model when those happen).
+fn get_accesses(test: &Value) -> Vec<Access> {
+    test["cycles"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|cycle| {
+            let pins = cycle[2].as_str()?;
+            let write = pins.contains('w');
+            if !write && !pins.contains('r') {
+                return None;
+            }
+
+            Some(Access {
+                address: cycle[0].as_u64()? as u16,
+                value: cycle[1].as_u64()? as u8,
+                write,
+            })
+        })
+        .collect()
+}
+
+/// Compares the bus accesses made by the CPU against the test's, returning a
+///  description of the first difference found.
+fn check_accesses(cpu: &CPU<FlatRam>, test: &Value) -> Result<(), String> {
+    let actual = cpu.mem.accesses.borrow();
+    let expected = get_accesses(test);
+
+    for index in 0..actual.len().max(expected.len()) {
+        match (actual.get(index), expected.get(index)) {
+            (Some(actual), Some(expected)) if actual == expected => {}
+            (actual, expected) => {
+                let describe = |access: Option<&Access>| {
+                    access.map_or("nothing".to_string(), ToString::to_string)
+                };
+                return Err(format!(
+                    "bus access {} was {}, expected {}",
+                    index,
+                    describe(actual),
+                    describe(expected)
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Runs a single test, returning a description of the first difference from
+///  the expected results.
+fn run_test(cpu: &mut CPU<FlatRam>, test: &Value) -> Result<(), String> {
+    load_state(cpu, &test["initial"]);
+    cpu.mem.accesses.borrow_mut().clear();
+
+    let cycles = cpu.execute_next();
+
+    let result = check_accesses(cpu, test).and_then(|_| {
+        let expected_cycles = test["cycles"].as_array().map_or(0, Vec::len) * 4;
+        if cycles as usize == expected_cycles {
+            Ok(())
+        } else {
+            Err(format!("took {} cycles, expected {}", cycles, expected_cycles))
+        }
+    });
+    let result = result.and_then(|_| check_state(cpu, &test["final"]));
+
+    // Clear anything this test touched for the next one
+    let ram = &mut cpu.mem.memory;
+    for (address, _) in get_ram(&test["initial"]).into_iter().chain(get_ram(&test["final"])) {
+        ram[address as usize] = 0;
+    }
+    for cycle in test["cycles"].as_array().into_iter().flatten() {
+        if let Some(address) = cycle[0].as_u64() {
+            ram[address as usize & 0xFFFF] = 0;
+        }
+    }
+
+    result
+}
+
+/// Runs all the tests for a single opcode, returning the number of tests and
+///  the first failure (if any).
+fn run_file(cpu: &mut CPU<FlatRam>, path: &PathBuf) -> (usize, Option<String>) {
+    let data = fs::read_to_string(path).expect("Failed to read test file");
+    let tests: Value = serde_json::from_str(&data).expect("Failed to parse test file");
+    let tests = tests.as_array().expect("Test file is not a array");
+
+    let mut first_failure = None;
+
+    for test in tests {
+        if let Err(e) = run_test(cpu, test) {
+            if first_failure.is_none() {
+                first_failure = Some(format!("{}: {}", test["name"].as_str().unwrap_or("?"), e));
+            }
+        }
+    }
+
+    (tests.len(), first_failure)
+}
+
+/// Runs a instruction with the specified flags, returning the cycles it took.
+fn run_opcode(opcode: &[u8], flags: u8) -> u8 {
+    let mut cpu = build_cpu();
+    cpu.regs.pc = 0x100;
+    cpu.regs.sp = 0xD000;
+    cpu.regs.f = flags;
+    cpu.regs.set_hl(0xC000);
+    cpu.interrupts_countdown = -1;
+    cpu.mem.memory[0x100..0x100 + opcode.len()].copy_from_slice(opcode);
+
+    cpu.execute_next()
+}
+
+#[test]
+fn instruction_timings() {
+    let mut mismatches = Vec::new();
+
+    for opcode in 0..=0xFFu8 {
+        let expected = OPCODE_CYCLES[opcode as usize];
+        if expected.0 != 0 {
+            // Conditions are all false with no flags set, and true with all set
+            // (other than NZ and NC, which are the other way around)
+            let mut actual = [run_opcode(&[opcode], 0), run_opcode(&[opcode], 0xF0)];
+            actual.sort();
+            let mut expected = [expected.0, expected.1];
+            expected.sort();
+
+            if actual != expected {
+                mismatches.push(format!("{:02x}: {:?}, expected {:?}", opcode, actual, expected));
+            }
+        }
+
+        let expected = get_cb_cycles(opcode);
+        let actual = run_opcode(&[0xCB, opcode], 0);
+        if actual != expected {
+            mismatches.push(format!("cb {:02x}: {}, expected {}", opcode, actual, expected));
+        }
+    }
+
+    assert!(mismatches.is_empty(), "Wrong cycle counts: {:?}", mismatches);
+}
+
+/// Tests in the same format as the SM83 vectors: LD (HL+),A then INC BC,
+///  which has a idle cycle after the fetch.
+const SAMPLE_TESTS: &str = r#"[
+    {
+        "name": "22 0000",
+        "initial": {
+            "a": 66, "b": 0, "c": 0, "d": 0, "e": 0, "f": 0, "h": 192, "l": 0,
+            "pc": 256, "sp": 65534, "ime": 0, "ram": [[256, 34]]
+        },
+        "final": {
+            "a": 66, "b": 0, "c": 0, "d": 0, "e": 0, "f": 0, "h": 192, "l": 1,
+            "pc": 257, "sp": 65534, "ime": 0, "ram": [[256, 34], [49152, 66]]
+        },
+        "cycles": [[256, 34, "r-m"], [49152, 66, "-wm"]]
+    },
+    {
+        "name": "03 0000",
+        "initial": {
+            "a": 0, "b": 18, "c": 255, "d": 0, "e": 0, "f": 0, "h": 0, "l": 0,
+            "pc": 512, "sp": 65534, "ime": 0, "ram": [[512, 3]]
+        },
+        "final": {
+            "a": 0, "b": 19, "c": 0, "d": 0, "e": 0, "f": 0, "h": 0, "l": 0,
+            "pc": 513, "sp": 65534, "ime": 0, "ram": [[512, 3]]
+        },
+        "cycles": [[512, 3, "r-m"], null]
+    }
+]"#;
+
+#[test]
+fn checks_bus_accesses() {
+    let tests: Value = serde_json::from_str(SAMPLE_TESTS).unwrap();
+    let mut cpu = build_cpu();
+
+    for test in tests.as_array().unwrap() {
+        assert_eq!(run_test(&mut cpu, test), Ok(()));
+    }
+
+    // A different value on the bus fails, even with the right final state
+    let mut test = tests[0].clone();
+    test["cycles"][1][1] = Value::from(67);
+    assert_eq!(
+        run_test(&mut cpu, &test),
+        Err("bus access 1 was write 42 @ C000, expected write 43 @ C000".to_string())
+    );
+
+    // As does a read in place of a write
+    let mut test = tests[0].clone();
+    test["cycles"][1][2] = Value::from("r-m");
+    assert_eq!(
+        run_test(&mut cpu, &test),
+        Err("bus access 1 was write 42 @ C000, expected read 42 @ C000".to_string())
+    );
+
+    let mut test = tests[0].clone();
+    test["cycles"].as_array_mut().unwrap().pop();
+    assert_eq!(
+        run_test(&mut cpu, &test),
+        Err("bus access 1 was write 42 @ C000, expected nothing".to_string())
+    );
+}
+
+#[test]
+fn sm83_test_vectors() {
+    let dir = match env::var_os("OXIDGB_SM83_TESTS") {
+        Some(dir) => PathBuf::from(dir),
+        None => {
+            println!("OXIDGB_SM83_TESTS not set, skipping SM83 test vectors");
+            return;
+        }
+    };
+
+    let mut files: Vec<PathBuf> = fs::read_dir(&dir)
+        .expect("Failed to read test directory")
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    files.sort();
+
+    let mut cpu = build_cpu();
+    let mut unexpected_failures = Vec::new();
+    let mut passed = 0;
+
+    for path in &files {
+        let opcode = path.file_stem().unwrap().to_string_lossy().to_string();
+        let (count, failure) = run_file(&mut cpu, path);
+
+        match failure {
+            Some(failure) => {
+                println!("{:<6} FAIL ({} tests) - {}", opcode, count, failure);
+                if !KNOWN_FAILURES.contains(&opcode.as_str()) {
+                    unexpected_failures.push(opcode);
+                }
+            }
+            None => {
+                println!("{:<6} PASS ({} tests)", opcode, count);
+                passed += 1;
+            }
+        }
+    }
+
+    println!("{}/{} opcodes passed", passed, files.len());
+
+    assert!(
+        unexpected_failures.is_empty(),
+        "Opcodes failed: {:?}",
+        unexpected_failures
+    );
+}
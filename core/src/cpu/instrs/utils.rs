@@ -3,10 +3,11 @@
  *
  * Helper utilities for instructions.
 **/
+use cpu::bus::Bus;
 use cpu::CPU;
 
 #[inline]
-pub fn get_n(cpu: &mut CPU) -> u8 {
+pub fn get_n<B: Bus>(cpu: &mut CPU<B>) -> u8 {
     let value = cpu.mem.read(cpu.regs.pc);
     cpu.regs.pc = cpu.regs.pc.wrapping_add(1);
     value
@@ -1,3 +1,5 @@
+pub mod bus;
+pub mod disasm;
 pub mod interrupts;
 /**
  * cpu.rs
@@ -9,40 +11,60 @@ pub mod regs;
 mod instrs; // Private to the CPU implementation
 
 use mem::GBMemory;
-
+use model::Model;
+use rom::GameROM;
+#[cfg(feature = "heap_alloc")]
+use state::StateError;
+#[cfg(feature = "heap_alloc")]
+use state::StateReader;
+#[cfg(feature = "heap_alloc")]
+use state::StateWriter;
+
+use core::marker::PhantomData;
+
+use cpu::bus::Bus;
+use cpu::bus::GameboyBus;
 use cpu::instrs::execute_instruction;
 use cpu::interrupts::InterruptType;
 use cpu::regs::Registers;
 
+#[cfg(feature = "debugger")]
+use watchpoint::Access;
+#[cfg(feature = "debugger")]
+use watchpoint::WatchpointHit;
+
+/// The number of cycles in a single frame, at normal speed.
+pub const CYCLES_PER_FRAME: u32 = 70224;
+
+/// The number of cycles to idle for while the CPU isn't executing instructions.
+const IDLE_CYCLES: u8 = 64;
+
 #[cfg_attr(feature = "serialisation", derive(Serialize, Deserialize))]
-pub struct CPU<'a> {
+pub struct CPU<'a, B: Bus = GBMemory<'a>> {
     pub regs: Registers,
-    pub mem: GBMemory<'a>,
+    pub mem: B,
     pub interrupts_enabled: bool,
     pub interrupts_countdown: i8,
     pub stopped: bool,
     pub halted: bool,
-
-    /// If the timer was high
-    pub timer_armed: bool,
-    pub timer_counter: i32,
-    pub timer_enabled: bool,
+    /// Cycles remaining until a CGB speed switch has finished
+    pub speed_switch_countdown: u32,
 
     pub cycle_counter: u32,
-    pub timer_invoke_counter: u32,
+
+    /// The bus may borrow game data for the lifetime of the CPU.
+    lifetime: PhantomData<&'a ()>,
 }
 
-impl CPU<'_> {
+impl<'a, B: GameboyBus> CPU<'a, B> {
     /// Ticks the CPU + other components one instruction.
-    pub fn tick<#[cfg(feature = "debugger")] Debugger: GameboyDebugger>(
+    pub fn tick<#[cfg(feature = "debugger")] Debugger: GameboyDebugger<'a, B>>(
         &mut self,
-        #[cfg(feature = "debugger")] mut debugger: &mut Debugger,
+        #[cfg(feature = "debugger")] debugger: &mut Debugger,
     ) -> bool {
         // Before tick
-        if self.mem.dirty_interrupts {
-            //self.mem.dirty_interrupts = false;
-            let available_interrupts = self.mem.ioregs.iflag & self.mem.interrupt_reg;
-
+        let available_interrupts = self.mem.get_pending_interrupts();
+        if available_interrupts != 0 {
             for bit in 0..5 {
                 if (available_interrupts >> bit) & 0x1 == 1 {
                     let interrupt = InterruptType::get_by_bit(bit);
@@ -60,55 +82,56 @@ impl CPU<'_> {
             }
         }
 
-        let timer_active = (((self.mem.ioregs.tac >> 2) & 0x1) as u16) == 1;
-        if timer_active {
-            let tac = self.mem.ioregs.tac;
-            let speed = tac & 0b11;
-            let freq = match speed {
-                0b00 => 4096,
-                0b01 => 262144,
-                0b10 => 65536,
-                0b11 => 16384,
-                _ => panic!("Bad clock speed: {}", speed),
-            };
-
-            if self.timer_counter > 4194304 / freq {
-                self.timer_counter -= 4194304 / freq;
-
-                if self.mem.ioregs.tima == 0xFF {
-                    self.mem.ioregs.tima = self.mem.ioregs.tma;
-                    self.throw_interrupt(InterruptType::TIMER);
-                    self.timer_invoke_counter += 1;
-                } else {
-                    self.mem.ioregs.tima += 1;
-                }
+        if self.stopped {
+            // The system clock is halted - only a selected joypad line going low
+            //  will bring us back.
+            if self.mem.is_button_held() {
+                self.stopped = false;
+            } else {
+                self.cycle_counter += IDLE_CYCLES as u32;
+                return false;
             }
         }
 
         // Main tick
         #[cfg(feature = "debugger")]
-        debugger.debug(self);
-
-        let cycles = if !self.stopped && !self.halted {
-            // Read instruction
-            let current_instr = self.regs.pc;
+        let pc = self.regs.pc;
 
-            let mut raw_instruction = self.mem.read(current_instr) as u16;
-
-            //println!("{:02X} = {:02X}", current_instr, raw_instruction);
+        #[cfg(feature = "debugger")]
+        {
+            // Anything touched while dispatching a interrupt
+            self.report_watchpoint_hit(debugger, pc);
+
+            if self.is_executing() {
+                if let Some(watchpoints) = self.mem.get_watchpoints() {
+                    let opcode = self.mem.read(pc);
+                    watchpoints.take_hit();
+                    watchpoints.check(pc, Access::Execute, opcode);
+                }
+                self.report_watchpoint_hit(debugger, pc);
+            }
 
-            self.regs.pc = self.regs.pc.wrapping_add(1);
+            debugger.debug(self);
 
-            if raw_instruction == 0xCB {
-                raw_instruction |= (self.mem.read(current_instr + 1) as u16) << 8;
-                self.regs.pc = self.regs.pc.wrapping_add(1);
+            // The debugger's own reads shouldn't trigger anything
+            if let Some(watchpoints) = self.mem.get_watchpoints() {
+                watchpoints.take_hit();
             }
+        }
 
-            execute_instruction(self, raw_instruction, current_instr)
+        let cycles = if self.speed_switch_countdown > 0 {
+            let cycles = self.speed_switch_countdown.min(IDLE_CYCLES as u32);
+            self.speed_switch_countdown -= cycles;
+            cycles as u8
+        } else if !self.halted {
+            self.execute_next()
         } else {
-            64 // TODO: Is this really the best?
+            IDLE_CYCLES // TODO: Is this really the best?
         };
 
+        #[cfg(feature = "debugger")]
+        self.report_watchpoint_hit(debugger, pc);
+
         // After
         // Handle interrupt toggle
         if self.interrupts_countdown > -1 {
@@ -119,44 +142,128 @@ impl CPU<'_> {
             }
         }
 
-        // Handle timers
-        let cur_value = self.mem.ioregs.div;
-        self.mem.ioregs.div = cur_value.wrapping_add(cycles as u16);
         self.cycle_counter += cycles as u32;
 
-        if timer_active {
-            self.timer_counter += cycles as i32;
+        // Handle everything else on the bus
+        self.mem.tick(cycles)
+    }
+
+    /// Passes any watchpoint hit since this was last called to the debugger,
+    ///  attributing it to the instruction at the specified address.
+    #[cfg(feature = "debugger")]
+    fn report_watchpoint_hit<Debugger: GameboyDebugger<'a, B>>(
+        &mut self,
+        debugger: &mut Debugger,
+        pc: u16,
+    ) {
+        let hit = self
+            .mem
+            .get_watchpoints()
+            .and_then(|watchpoints| watchpoints.take_hit());
+
+        if let Some(mut hit) = hit {
+            hit.pc = pc;
+            debugger.watchpoint(self, hit);
         }
+    }
 
-        // Handle audio
-        self.mem.sound.step(&mut self.mem.ioregs, cycles);
+    /// Fetches and executes the instruction at PC, returning the number of
+    ///  cycles it took.
+    fn execute_next(&mut self) -> u8 {
+        let current_instr = self.regs.pc;
 
-        // Handle GPU
-        let gpu_result = self.mem.gpu.step(cycles as u32);
+        let mut raw_instruction = self.mem.read(current_instr) as u16;
 
-        if let Some(value) = gpu_result {
-            //println!("GPU throwing interrupt: {:?}", value);
-            self.throw_interrupt(value);
-            if value == InterruptType::VBLANK {
-                return true;
-            }
+        //println!("{:02X} = {:02X}", current_instr, raw_instruction);
+
+        self.regs.pc = self.regs.pc.wrapping_add(1);
+
+        if raw_instruction == 0xCB {
+            raw_instruction |= (self.mem.read(current_instr.wrapping_add(1)) as u16) << 8;
+            self.regs.pc = self.regs.pc.wrapping_add(1);
         }
 
-        false
+        execute_instruction(self, raw_instruction, current_instr)
     }
 
     /// Runs a iteration of the CPU
-    pub fn run<#[cfg(feature = "debugger")] Debugger: GameboyDebugger>(
+    pub fn run<#[cfg(feature = "debugger")] Debugger: GameboyDebugger<'a, B>>(
         &mut self,
-        #[cfg(feature = "debugger")] mut debugger: &mut Debugger,
+        #[cfg(feature = "debugger")] debugger: &mut Debugger,
     ) {
         self.cycle_counter = 0;
-        self.timer_invoke_counter = 0;
 
-        #[cfg(feature = "debugger")]
-        while !self.tick(&mut debugger) {}
-        #[cfg(not(feature = "debugger"))]
-        while !self.tick() {}
+        loop {
+            #[cfg(feature = "debugger")]
+            let frame_done = self.tick(debugger);
+            #[cfg(not(feature = "debugger"))]
+            let frame_done = self.tick();
+
+            if frame_done {
+                break;
+            }
+
+            if self.is_frame_timed_out() {
+                break;
+            }
+        }
+    }
+
+    /// Runs for (at least) the specified number of cycles, continuing across
+    ///  frame boundaries. This allows input to be changed mid-frame. Returns
+    ///  if a frame was completed.
+    pub fn run_cycles<#[cfg(feature = "debugger")] Debugger: GameboyDebugger<'a, B>>(
+        &mut self,
+        cycles: u32,
+        #[cfg(feature = "debugger")] debugger: &mut Debugger,
+    ) -> bool {
+        let mut elapsed = 0;
+        let mut frame_completed = false;
+
+        while elapsed < cycles {
+            let previous_counter = self.cycle_counter;
+
+            #[cfg(feature = "debugger")]
+            let frame_done = self.tick(debugger);
+            #[cfg(not(feature = "debugger"))]
+            let frame_done = self.tick();
+
+            elapsed += self.cycle_counter - previous_counter;
+
+            if frame_done || self.is_frame_timed_out() {
+                frame_completed = true;
+                self.cycle_counter = 0;
+            }
+        }
+
+        frame_completed
+    }
+
+    /// Returns if a frame's worth of time has passed without a VBlank to end
+    ///  it, which happens with the LCD off (or the clock stopped).
+    pub fn is_frame_timed_out(&self) -> bool {
+        (self.stopped || !self.mem.is_display_enabled())
+            && self.cycle_counter >= self.get_cycles_per_frame()
+    }
+
+    /// Returns if the instruction at PC is about to be executed, rather than
+    ///  the CPU idling while halted, stopped or switching speed.
+    pub fn is_executing(&self) -> bool {
+        !self.halted && !self.stopped && self.speed_switch_countdown == 0
+    }
+
+    /// Returns if the CPU is running in CGB double speed mode.
+    pub fn is_double_speed(&self) -> bool {
+        self.mem.is_double_speed()
+    }
+
+    /// Returns the number of CPU cycles that make up a frame at the current speed.
+    pub fn get_cycles_per_frame(&self) -> u32 {
+        if self.is_double_speed() {
+            CYCLES_PER_FRAME * 2
+        } else {
+            CYCLES_PER_FRAME
+        }
     }
 
     /// Registers that a interrupt should be thrown.
@@ -169,8 +276,7 @@ impl CPU<'_> {
         //panic!("Throwing: {:?}", interrupt);
 
         // Set the IF flag
-        self.mem.ioregs.iflag |= 1 << (interrupt as u8);
-        self.mem.dirty_interrupts = true;
+        self.mem.request_interrupt(interrupt);
 
         true
     }
@@ -189,7 +295,7 @@ impl CPU<'_> {
             return true;
         }
 
-        self.mem.ioregs.iflag &= !(1 << interrupt as u8);
+        self.mem.acknowledge_interrupt(interrupt);
 
         self.interrupts_enabled = false;
 
@@ -211,37 +317,154 @@ impl CPU<'_> {
         true
     }
 
-    /// Builds a CPU from the specified memory module.
-    pub fn build(mem: GBMemory) -> CPU {
+    /// Builds a CPU attached to the specified bus, in its power on state.
+    pub fn build_with_bus(mem: B) -> CPU<'a, B> {
         CPU {
-            regs: CPU::get_default_registers(),
+            regs: Self::get_power_on_registers(),
             mem,
-            interrupts_enabled: true,
+            interrupts_enabled: false,
             interrupts_countdown: -1,
             stopped: false,
             halted: false,
-            timer_counter: 0,
-            timer_enabled: false,
-            timer_armed: false,
+            speed_switch_countdown: 0,
             cycle_counter: 0,
-            timer_invoke_counter: 0,
+            lifetime: PhantomData,
+        }
+    }
+
+    /// Returns the state of the CPU registers on power on, ready to execute the
+    ///  boot ROM.
+    pub fn get_power_on_registers() -> Registers {
+        Registers {
+            a: 0,
+            f: 0,
+
+            b: 0,
+            c: 0,
+
+            d: 0,
+            e: 0,
+
+            h: 0,
+            l: 0,
+
+            sp: 0,
+            pc: 0,
+        }
+    }
+}
+
+impl<'a> CPU<'a, GBMemory<'a>> {
+    /// Writes the CPU's state to a save state.
+    #[cfg(feature = "heap_alloc")]
+    pub fn save_state(&self, writer: &mut StateWriter) {
+        let regs = &self.regs;
+        for &value in [regs.a, regs.b, regs.c, regs.d, regs.e, regs.f, regs.h, regs.l].iter() {
+            writer.write_u8(value);
         }
+        writer.write_u16(regs.sp);
+        writer.write_u16(regs.pc);
+
+        writer.write_bool(self.interrupts_enabled);
+        writer.write_u8(self.interrupts_countdown as u8);
+        writer.write_bool(self.stopped);
+        writer.write_bool(self.halted);
+        writer.write_u32(self.speed_switch_countdown);
+
+        writer.write_u32(self.cycle_counter);
+    }
+
+    /// Loads the CPU's state from a save state.
+    #[cfg(feature = "heap_alloc")]
+    pub fn load_state(&mut self, reader: &mut StateReader, version: u16) -> Result<(), StateError> {
+        let regs = &mut self.regs;
+        for value in [
+            &mut regs.a,
+            &mut regs.b,
+            &mut regs.c,
+            &mut regs.d,
+            &mut regs.e,
+            &mut regs.f,
+            &mut regs.h,
+            &mut regs.l,
+        ]
+        .iter_mut()
+        {
+            **value = reader.read_u8()?;
+        }
+        regs.sp = reader.read_u16()?;
+        regs.pc = reader.read_u16()?;
+
+        self.interrupts_enabled = reader.read_bool()?;
+        self.interrupts_countdown = reader.read_u8()? as i8;
+        self.stopped = reader.read_bool()?;
+        self.halted = reader.read_bool()?;
+        self.speed_switch_countdown = reader.read_u32()?;
+
+        if version < 2 {
+            // The timer used to be run by the CPU
+            reader.read_bool()?;
+            self.mem.timer_counter = reader.read_i32()?;
+            reader.read_bool()?;
+        }
+
+        self.cycle_counter = reader.read_u32()?;
+
+        if version < 2 {
+            // Timer interrupt count, which is no longer tracked
+            reader.read_u32()?;
+        }
+
+        Ok(())
+    }
+
+    /// Builds a CPU from the specified memory module.
+    pub fn build(mem: GBMemory<'a>) -> CPU<'a> {
+        let run_boot_rom = mem.boot_rom.is_some();
+        let regs = Self::get_default_registers(mem.model, &mem.rom);
+
+        let mut cpu = Self::build_with_bus(mem);
+        if !run_boot_rom {
+            cpu.regs = regs;
+        }
+
+        cpu
     }
 
-    /// Returns the default expected state for the CPU registers.
-    pub fn get_default_registers() -> Registers {
+    /// Returns the state of the CPU registers as the boot ROM for the specified
+    ///  model would leave them, ready to execute the cartridge.
+    pub fn get_default_registers(model: Model, rom: &GameROM) -> Registers {
+        // The DMG/MGB boot ROMs leave H and C set if the header checksum is non-zero
+        let checksum_flags = if rom.get_header_checksum() != 0 {
+            0xB0
+        } else {
+            0x80
+        };
+
+        let (a, f, b, c, d, e, h, l) = match model {
+            Model::Dmg0 => (0x01, 0x00, 0xFF, 0x13, 0x00, 0xC1, 0x84, 0x03),
+            Model::Dmg => (0x01, checksum_flags, 0x00, 0x13, 0x00, 0xD8, 0x01, 0x4D),
+            Model::Mgb => (0xFF, checksum_flags, 0x00, 0x13, 0x00, 0xD8, 0x01, 0x4D),
+            Model::Sgb => (0x01, 0x00, 0x00, 0x14, 0x00, 0x00, 0xC0, 0x60),
+            Model::Cgb if rom.is_cgb_game() => (0x11, 0x80, 0x00, 0x00, 0xFF, 0x56, 0x00, 0x0D),
+            Model::Cgb => (0x11, 0x80, 0x00, 0x00, 0x00, 0x08, 0x00, 0x7C),
+            // The AGB boot ROM increments B, which is how games detect it
+            Model::Agb if rom.is_cgb_game() => (0x11, 0x00, 0x01, 0x00, 0xFF, 0x56, 0x00, 0x0D),
+            Model::Agb => (0x11, 0x00, 0x01, 0x00, 0x00, 0x08, 0x00, 0x7C),
+        };
+
         Registers {
-            a: 0x01,
-            f: 0xB0,
+            a,
+            f,
 
-            b: 0x00,
-            c: 0x13,
+            b,
+            c,
 
-            d: 0x00,
-            e: 0xD8,
+            d,
+            e,
 
-            h: 0x01,
-            l: 0x4D,
+            h,
+            l,
 
             sp: 0xFFFE,
             pc: 0x0100,
@@ -249,6 +472,12 @@ impl CPU<'_> {
     }
 }
 
-pub trait GameboyDebugger {
-    fn debug(&mut self, cpu: &mut CPU);
+pub trait GameboyDebugger<'a, B: GameboyBus = GBMemory<'a>> {
+    /// Called at the start of each tick, including ticks spent idling (see
+    ///  `CPU::is_executing`).
+    fn debug(&mut self, cpu: &mut CPU<'a, B>);
+
+    /// Called once a instruction has accessed memory covered by a watchpoint.
+    #[cfg(feature = "debugger")]
+    fn watchpoint(&mut self, _cpu: &mut CPU<'a, B>, _hit: WatchpointHit) {}
 }
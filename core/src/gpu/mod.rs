@@ -4,10 +4,18 @@
  * Renders graphics into a framebuffer
 **/
 use cpu::interrupts::InterruptType;
+use model::Model;
 
 #[cfg(feature = "heap_alloc")]
 use alloc::vec::Vec;
 
+#[cfg(feature = "heap_alloc")]
+use state::StateError;
+#[cfg(feature = "heap_alloc")]
+use state::StateReader;
+#[cfg(feature = "heap_alloc")]
+use state::StateWriter;
+
 pub const PITCH: usize = 3;
 
 #[derive(Copy, Clone, PartialEq)]
@@ -127,6 +135,14 @@ impl GPU {
         None
     }
 
+    /// Clears the framebuffer to the lightest shade, as the LCD shows when it
+    ///  isn't being driven.
+    pub fn blank_screen(&mut self) {
+        for x in 0..160 * 144 {
+            self.draw_pixel(x, 0);
+        }
+    }
+
     /// Returns if the screen is currently enabled.
     pub fn is_enabled(&self) -> bool {
         self.lcdc >> 7 & 0x1 == 1
@@ -399,10 +415,70 @@ impl GPU {
         }
     }
 
-    /// Builds a new instance of the GPU
+    /// Writes the GPU's state (including the current frame) to a save state.
+    #[cfg(feature = "heap_alloc")]
+    pub fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_u8(self.mode as u8);
+        writer.write_bytes(&self.pixel_data);
+        writer.write_bytes(&self.vram);
+        writer.write_bytes(&self.oam);
+
+        for &value in [
+            self.lcdc, self.stat, self.lyc, self.scx, self.scy, self.wx, self.wy, self.bgp,
+            self.obp0, self.obp1,
+        ]
+        .iter()
+        {
+            writer.write_u8(value);
+        }
+
+        writer.write_u32(self.internal_clock);
+        writer.write_u8(self.current_line);
+    }
+
+    /// Loads the GPU's state from a save state.
+    #[cfg(feature = "heap_alloc")]
+    pub fn load_state(&mut self, reader: &mut StateReader, _version: u16) -> Result<(), StateError> {
+        self.mode = match reader.read_u8()? {
+            0 => GPUMode::Hblank,
+            1 => GPUMode::Vblank,
+            2 => GPUMode::OamScanline,
+            3 => GPUMode::VramScanline,
+            _ => return Err(reader.malformed()),
+        };
+        reader.read_bytes_into(&mut self.pixel_data)?;
+        reader.read_bytes_into(&mut self.vram)?;
+        reader.read_bytes_into(&mut self.oam)?;
+
+        for value in [
+            &mut self.lcdc,
+            &mut self.stat,
+            &mut self.lyc,
+            &mut self.scx,
+            &mut self.scy,
+            &mut self.wx,
+            &mut self.wy,
+            &mut self.bgp,
+            &mut self.obp0,
+            &mut self.obp1,
+        ]
+        .iter_mut()
+        {
+            **value = reader.read_u8()?;
+        }
+
+        self.internal_clock = reader.read_u32()?;
+        self.current_line = reader.read_u8()?;
+
+        Ok(())
+    }
+
+    /// Builds a new instance of the GPU. If the boot ROM is going to be run,
+    ///  the LCD starts off disabled, otherwise as the boot ROM for the specified
+    ///  model would leave it.
     #[inline(always)]
-    pub fn build() -> GPU {
-        GPU {
+    pub fn build(model: Model, run_boot_rom: bool) -> GPU {
+        let mut gpu = GPU {
             #[cfg(feature = "heap_alloc")]
             pixel_data: vec![0xFF; 160 * 144 * PITCH],
             #[cfg(not(feature = "heap_alloc"))]
@@ -420,7 +496,7 @@ impl GPU {
             oam: [0; 160],
 
             lcdc: 0x91,
-            stat: 0x81, // TODO: verify
+            stat: if model == Model::Dmg0 { 0x81 } else { 0x85 },
             scx: 0,
             scy: 0,
             lyc: 0,
@@ -432,6 +508,18 @@ impl GPU {
 
             internal_clock: 0,
             current_line: 0x94,
+        };
+
+        if run_boot_rom {
+            gpu.mode = GPUMode::Hblank;
+            gpu.lcdc = 0;
+            gpu.stat = 0;
+            gpu.bgp = 0;
+            gpu.obp0 = 0;
+            gpu.obp1 = 0;
+            gpu.current_line = 0;
         }
+
+        gpu
     }
 }
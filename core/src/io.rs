@@ -6,12 +6,12 @@
 use cpu::interrupts::InterruptType;
 use gpu::GPUMode;
 use mem::GBMemory;
+use model::Model;
 
 /// Storage for various I/O registers.
 #[cfg_attr(feature = "serialisation", derive(Serialize, Deserialize))]
 pub struct IORegisters {
     pub p1: u8,           // 0x00 - Joypad info and controller (R/W)
-    pub sb: u8,           // 0x01 - Serial transfer data (R/W)
     pub div: u16,         // 0x04 - Divider register (R/W)
     pub tima: u8,         // 0x05 - Timer Counter (R/W)
     pub tma: u8,          // 0x06 - Timer Modulo (R/W)
@@ -40,16 +40,31 @@ pub struct IORegisters {
     pub nr52: u8,         // 0x26 - Sound on/off (R/W)
     pub wave: [u8; 0x10], // Wave Pattern RAM
     pub dma: u8,          // 0x46 - DMA Transfer and Start Address (W)
+    pub key1: u8,         // 0x4D - CGB Prepare Speed Switch (R/W)
 }
 
 impl IORegisters {
+    /// Builds the I/O registers. If the boot ROM is going to be run, this is
+    ///  the blank power-on state, otherwise the state the boot ROM for the
+    ///  specified model leaves behind.
     #[inline(always)]
-    pub fn build() -> IORegisters {
-        // TODO: Validate these
+    pub fn build(model: Model, run_boot_rom: bool) -> IORegisters {
+        if run_boot_rom {
+            return IORegisters::build_power_on();
+        }
+
+        // The SGB and CGB boot ROMs take a variable amount of time (depending on
+        //  the cartridge header), so their DIV values are typical ones.
+        let div = match model {
+            Model::Dmg0 => 0x1830,
+            Model::Dmg | Model::Mgb => 0xABCC,
+            Model::Sgb => 0xD85C,
+            Model::Cgb | Model::Agb => 0x267C,
+        };
+
         IORegisters {
-            p1: 0,
-            sb: 0,
-            div: 0xABCC,
+            p1: 0xCF,
+            div,
             tima: 0,
             tma: 0,
             tac: 0xF8,
@@ -73,10 +88,48 @@ impl IORegisters {
             nr44: 0,
             nr50: 0x77,
             nr51: 0xF3,
-            nr52: 0xF1,
+            nr52: if model.is_sgb() { 0xF0 } else { 0xF1 },
+            wave: [0; 0x10],
+            iflag: 1 << (InterruptType::VBLANK as u8),
+            dma: if model.is_cgb() { 0x00 } else { 0xFF },
+            key1: 0,
+        }
+    }
+
+    /// Builds the I/O registers as they are when the system is first powered on.
+    #[inline(always)]
+    fn build_power_on() -> IORegisters {
+        IORegisters {
+            p1: 0,
+            div: 0,
+            tima: 0,
+            tma: 0,
+            tac: 0xF8,
+            nr10: 0,
+            nr11: 0,
+            nr12: 0,
+            nr13: 0,
+            nr14: 0,
+            nr21: 0,
+            nr22: 0,
+            nr23: 0,
+            nr24: 0,
+            nr30: 0,
+            nr31: 0,
+            nr32: 0,
+            nr33: 0,
+            nr34: 0,
+            nr41: 0,
+            nr42: 0,
+            nr43: 0,
+            nr44: 0,
+            nr50: 0,
+            nr51: 0,
+            nr52: 0,
             wave: [0; 0x10],
             iflag: 0,
             dma: 0,
+            key1: 0,
         }
     }
 }
@@ -86,26 +139,9 @@ impl IORegisters {
 /// Reads a I/O register.
 pub fn read(mem: &GBMemory, ptr: u8) -> u8 {
     match ptr {
-        0x00 => {
-            let p14 = (mem.ioregs.p1 >> 5) & 0x1 == 1;
-            let p15 = (mem.ioregs.p1 >> 4) & 0x1 == 1;
-
-            let mut output = 0;
-
-            if p14 {
-                output |= mem.buttons.p14;
-            }
-
-            if p15 {
-                output |= mem.buttons.p15;
-            }
-
-            output = (!output) & 0b1111;
-            output |= ((if p14 { 1 } else { 0 }) << 5) | ((if p15 { 1 } else { 0 }) << 4);
-
-            output
-        }
-        0x02 => mem.ioregs.sb,
+        0x00 => 0b11000000 | (mem.ioregs.p1 & 0b110000) | mem.get_joypad_lines(),
+        0x01 => mem.serial.sb,
+        0x02 => mem.serial.read_control(mem.model),
         0x04 => (mem.ioregs.div >> 8) as u8,
         0x05 => mem.ioregs.tima,
         0x06 => mem.ioregs.tma,
@@ -160,7 +196,9 @@ pub fn read(mem: &GBMemory, ptr: u8) -> u8 {
         0x49 => mem.gpu.obp1,
         0x4A => mem.gpu.wy,
         0x4B => mem.gpu.wx,
-        0x4C..=0xFF => {
+        0x4D if mem.model.is_cgb() => mem.ioregs.key1 | 0b1111110,
+        0x50 => 0xFF, // Write only
+        0x4C..=0x4F | 0x51..=0xFF => {
             #[cfg(feature = "logging")]
             warn!("Out of range I/O register: {:02x}", ptr);
             0xFF
@@ -176,12 +214,13 @@ pub fn read(mem: &GBMemory, ptr: u8) -> u8 {
 /// Writes to a I/O register.
 pub fn write(mem: &mut GBMemory, ptr: u8, val: u8) {
     match ptr {
-        0x00 => mem.ioregs.p1 = val,
-        0x01 => {
-            // Serial
-            //info!("{}", val as char);
+        0x00 => {
+            let previous_lines = mem.get_joypad_lines();
+            mem.ioregs.p1 = val & 0b110000;
+            mem.check_joypad_interrupt(previous_lines);
         }
-        0x02 => mem.ioregs.sb = val,
+        0x01 => mem.serial.sb = val,
+        0x02 => mem.serial.write_control(mem.model, val),
         0x04 => mem.ioregs.div = 0,
         0x05 => mem.ioregs.tima = val,
         0x06 => mem.ioregs.tma = val,
@@ -255,7 +294,15 @@ pub fn write(mem: &mut GBMemory, ptr: u8, val: u8) {
         0x49 => mem.gpu.obp1 = val,
         0x4A => mem.gpu.wy = val,
         0x4B => mem.gpu.wx = val,
-        0x4C..=0xFF => {
+        0x4D if mem.model.is_cgb() => {
+            // Only the armed bit is writable - the current speed is switched by STOP
+            mem.ioregs.key1 = (mem.ioregs.key1 & (1 << 7)) | (val & 0x1);
+        }
+        0x50 => {
+            // Any non-zero write unmaps the boot ROM until the next reset
+            mem.boot_rom_mapped = mem.boot_rom_mapped && val == 0;
+        }
+        0x4C..=0x4F | 0x51..=0xFF => {
             #[cfg(feature = "logging")]
             warn!("Out of range I/O register: {:02x} = {:02x}", ptr, val);
         }
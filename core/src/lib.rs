@@ -9,15 +9,38 @@ extern crate alloc;
 #[cfg(feature = "logging")]
 extern crate log;
 
+#[cfg(test)]
+extern crate serde_json;
+#[cfg(test)]
+extern crate std;
+
 #[macro_use]
 #[cfg(feature = "serialisation")]
 extern crate serde;
 
+pub mod cheats;
 pub mod cpu;
 pub mod gpu;
 pub mod input;
+#[cfg(feature = "heap_alloc")]
+pub mod link;
 pub mod mem;
+pub mod model;
+#[cfg(feature = "heap_alloc")]
+pub mod printer;
+#[cfg(feature = "heap_alloc")]
+pub mod rewind;
 pub mod rom;
+#[cfg(feature = "heap_alloc")]
+pub mod search;
+pub mod serial;
 pub mod sound;
+#[cfg(feature = "heap_alloc")]
+pub mod state;
+#[cfg(feature = "heap_alloc")]
+pub mod symbols;
+pub mod trace;
+#[cfg(feature = "debugger")]
+pub mod watchpoint;
 
 mod io;
@@ -0,0 +1,130 @@
+/**
+ * link.rs
+ *
+ * Emulates a link cable between two Gameboys running in the same process.
+ *  Both systems are stepped in lockstep, so that serial transfers happen at
+ *  the same cycle on both ends.
+**/
+use cpu::CPU;
+#[cfg(feature = "debugger")]
+use cpu::GameboyDebugger;
+use serial::SerialDevice;
+
+use alloc::boxed::Box;
+use alloc::rc::Rc;
+use core::cell::RefCell;
+
+/// What one end of the cable can see of the Gameboy plugged into it.
+#[derive(Copy, Clone)]
+struct CableEnd {
+    /// The byte waiting in the Gameboy's shift register.
+    sb: u8,
+    /// If the Gameboy is waiting for the other end to clock a transfer.
+    waiting: bool,
+    /// A byte clocked in by the other end, not yet picked up by this one.
+    incoming: Option<u8>,
+}
+
+/// One end of a link cable, to be plugged into a Gameboy's link port.
+pub struct LinkPort {
+    ends: Rc<RefCell<[CableEnd; 2]>>,
+    side: usize,
+}
+
+impl SerialDevice for LinkPort {
+    fn exchange(&mut self, out: u8) -> u8 {
+        let mut ends = self.ends.borrow_mut();
+        let other = &mut ends[1 - self.side];
+
+        // The other end only shifts if it has a transfer of its own running
+        if other.waiting && other.incoming.is_none() {
+            other.incoming = Some(out);
+            other.sb
+        } else {
+            0xFF
+        }
+    }
+
+    fn poll_external(&mut self, _out: u8) -> Option<u8> {
+        // Our byte has already been handed over when the other end clocked it
+        self.ends.borrow_mut()[self.side].incoming.take()
+    }
+}
+
+/// A link cable connecting two Gameboys.
+pub struct LinkCable {
+    ends: Rc<RefCell<[CableEnd; 2]>>,
+}
+
+impl LinkCable {
+    /// Plugs the cable into both Gameboys, replacing any existing serial
+    ///  devices.
+    pub fn connect(&self, first: &mut CPU, second: &mut CPU) {
+        first.mem.set_serial_device(Box::new(LinkPort {
+            ends: self.ends.clone(),
+            side: 0,
+        }));
+        second.mem.set_serial_device(Box::new(LinkPort {
+            ends: self.ends.clone(),
+            side: 1,
+        }));
+    }
+
+    /// Updates what each end of the cable sees of its Gameboy.
+    fn sync(&self, first: &CPU, second: &CPU) {
+        let mut ends = self.ends.borrow_mut();
+
+        for (end, cpu) in ends.iter_mut().zip([first, second].iter()) {
+            end.sb = cpu.mem.serial.sb;
+            end.waiting = cpu.mem.serial.is_transferring() && !cpu.mem.serial.is_internal_clock();
+        }
+    }
+
+    /// Runs a frame on both Gameboys. Whichever system is behind is always
+    ///  the one stepped, keeping both within a instruction of each other.
+    pub fn run<'a, #[cfg(feature = "debugger")] Debugger: GameboyDebugger<'a>>(
+        &self,
+        first: &mut CPU<'a>,
+        second: &mut CPU<'a>,
+        #[cfg(feature = "debugger")] debugger: &mut Debugger,
+    ) {
+        first.cycle_counter = 0;
+        second.cycle_counter = 0;
+
+        let mut first_done = false;
+        let mut second_done = false;
+
+        while !first_done || !second_done {
+            self.sync(first, second);
+
+            let step_first =
+                second_done || (!first_done && first.cycle_counter <= second.cycle_counter);
+            let cpu = if step_first { &mut *first } else { &mut *second };
+
+            #[cfg(feature = "debugger")]
+            let frame_done = cpu.tick(debugger);
+            #[cfg(not(feature = "debugger"))]
+            let frame_done = cpu.tick();
+
+            if frame_done || cpu.is_frame_timed_out() {
+                if step_first {
+                    first_done = true;
+                } else {
+                    second_done = true;
+                }
+            }
+        }
+    }
+
+    pub fn build() -> LinkCable {
+        let end = CableEnd {
+            sb: 0xFF,
+            waiting: false,
+            incoming: None,
+        };
+
+        LinkCable {
+            ends: Rc::new(RefCell::new([end; 2])),
+        }
+    }
+}
@@ -7,6 +7,13 @@ use input::GameboyButton;
 **/
 use input::GameboyInput;
 
+use cheats::Cheat;
+
+use cpu::bus::Bus;
+use cpu::bus::GameboyBus;
+use cpu::interrupts::InterruptType;
+
+use model::Model;
 use rom::GameROM;
 
 use gpu::GPUMode;
@@ -15,8 +22,27 @@ use gpu::GPU;
 use io;
 use io::IORegisters;
 
+use serial::Serial;
+use serial::SerialDevice;
+#[cfg(not(feature = "heap_alloc"))]
+use serial::Disconnected;
+
 use sound::Sound;
 
+#[cfg(feature = "debugger")]
+use watchpoint::Access;
+#[cfg(feature = "debugger")]
+use watchpoint::Watchpoints;
+
+#[cfg(feature = "heap_alloc")]
+use state::StateError;
+#[cfg(feature = "heap_alloc")]
+use state::StateReader;
+#[cfg(feature = "heap_alloc")]
+use state::StateWriter;
+
+#[cfg(feature = "heap_alloc")]
+use alloc::boxed::Box;
 #[cfg(feature = "heap_alloc")]
 use alloc::vec::Vec;
 
@@ -35,18 +61,43 @@ pub struct GBMemory<'a> {
 
     pub gpu: GPU,
     pub sound: Sound,
+    pub serial: Serial,
+
+    #[cfg(feature = "heap_alloc")]
+    #[cfg_attr(
+        feature = "serialisation",
+        serde(skip, default = "::serial::default_device")
+    )]
+    pub serial_device: Box<dyn SerialDevice>,
+    #[cfg(not(feature = "heap_alloc"))]
+    pub serial_device: Option<&'a mut dyn SerialDevice>,
 
     pub dirty_interrupts: bool, // If the CPU should handle interrupts
     pub interrupt_reg: u8,
     pub ioregs: IORegisters,
+    /// Cycles elapsed since TIMA was last incremented
+    pub timer_counter: i32,
 
     pub buttons: GameboyInput,
+
+    pub model: Model,
+
+    #[cfg(feature = "own_game_data")]
+    pub boot_rom: Option<Vec<u8>>,
+    #[cfg(not(feature = "own_game_data"))]
+    pub boot_rom: Option<&'a [u8]>,
+    /// If the boot ROM is still mapped over the start of the cartridge
+    pub boot_rom_mapped: bool,
+
+    #[cfg(feature = "debugger")]
+    #[cfg_attr(feature = "serialisation", serde(skip))]
+    pub watchpoints: Watchpoints,
 }
 
-impl GBMemory<'_> {
+impl<'a> GBMemory<'a> {
     /// Reads a value from memory. 0xFF if invalid.
     pub fn read(&self, ptr: u16) -> u8 {
-        match ptr {
+        let value = match ptr {
             0xFFFF => {
                 // Interrupt enable reg
                 self.interrupt_reg
@@ -102,8 +153,33 @@ impl GBMemory<'_> {
             }
             0x0000..=0x7FFF => {
                 // Cartridge / Switchable ROM
-                self.rom.read(ptr)
+                match self.read_boot_rom(ptr) {
+                    Some(value) => value,
+                    None => self.rom.read(ptr),
+                }
+            }
+        };
+
+        #[cfg(feature = "debugger")]
+        self.watchpoints.check(ptr, Access::Read, value);
+
+        value
+    }
+
+    /// Reads from the boot ROM, if it is currently mapped over this address.
+    fn read_boot_rom(&self, ptr: u16) -> Option<u8> {
+        if !self.boot_rom_mapped {
+            return None;
+        }
+
+        let boot_rom = self.boot_rom.as_ref()?;
+
+        // CGB boot ROMs are split in two around the cartridge header
+        match ptr {
+            0x0000..=0x00FF | 0x0200..=0x08FF if (ptr as usize) < boot_rom.len() => {
+                Some(boot_rom[ptr as usize])
             }
+            _ => None,
         }
     }
 
@@ -111,6 +187,9 @@ impl GBMemory<'_> {
     pub fn write(&mut self, ptr: u16, val: u8) {
         //println!("${:04X}: Write ${:02X}", ptr, val);
 
+        #[cfg(feature = "debugger")]
+        self.watchpoints.check(ptr, Access::Write, val);
+
         match ptr {
             0xFFFF => {
                 // Interrupt enable reg
@@ -181,14 +260,189 @@ impl GBMemory<'_> {
         self.write(ptr + 1, ((val >> 8) & 0xFF) as u8);
     }
 
-    /// Sets the input registers.
+    /// Connects a device to the link port, replacing what was there before.
+    #[cfg(feature = "heap_alloc")]
+    pub fn set_serial_device(&mut self, device: Box<dyn SerialDevice>) {
+        self.serial_device = device;
+    }
+
+    /// Connects a device to the link port, replacing what was there before.
+    #[cfg(not(feature = "heap_alloc"))]
+    pub fn set_serial_device(&mut self, device: &'a mut dyn SerialDevice) {
+        self.serial_device = Some(device);
+    }
+
+    /// Steps the serial port alongside the CPU. Returns a interrupt if a
+    ///  transfer has completed.
+    pub fn step_serial(&mut self, cycles: u8) -> Option<InterruptType> {
+        #[cfg(feature = "heap_alloc")]
+        let device = &mut *self.serial_device;
+        #[cfg(not(feature = "heap_alloc"))]
+        let device: &mut dyn SerialDevice = match self.serial_device {
+            Some(ref mut device) => *device,
+            None => &mut Disconnected,
+        };
+
+        self.serial.step(cycles, device)
+    }
+
+    /// Steps the timer alongside the CPU.
+    fn step_timer(&mut self, cycles: u8) {
+        if (self.ioregs.tac >> 2) & 0x1 == 0 {
+            return;
+        }
+
+        let freq = match self.ioregs.tac & 0b11 {
+            0b00 => 4096,
+            0b01 => 262144,
+            0b10 => 65536,
+            _ => 16384,
+        };
+
+        self.timer_counter += cycles as i32;
+
+        if self.timer_counter > 4194304 / freq {
+            self.timer_counter -= 4194304 / freq;
+
+            if self.ioregs.tima == 0xFF {
+                self.ioregs.tima = self.ioregs.tma;
+                self.request_interrupt(InterruptType::TIMER);
+            } else {
+                self.ioregs.tima += 1;
+            }
+        }
+    }
+
+    /// Sets the input registers. This can be called between calls to
+    ///  `CPU::run_cycles` to change input mid-frame.
     pub fn set_input(&mut self, input: &[GameboyButton]) {
+        let previous_lines = self.get_joypad_lines();
         self.buttons = build_input(input);
+        self.check_joypad_interrupt(previous_lines);
+    }
+
+    /// Requests a joypad interrupt if any of the P10-P13 lines have gone from
+    ///  high to low.
+    pub fn check_joypad_interrupt(&mut self, previous_lines: u8) {
+        if previous_lines & !self.get_joypad_lines() != 0 {
+            self.ioregs.iflag |= 1 << (InterruptType::KEYPAD as u8);
+            self.dirty_interrupts = true;
+        }
+    }
+
+    /// Returns the state of the P10-P13 input lines for the currently selected
+    ///  button groups. Lines are active low, so a pressed button reads as 0.
+    pub fn get_joypad_lines(&self) -> u8 {
+        let mut pressed = 0;
+
+        if (self.ioregs.p1 >> 4) & 0x1 == 0 {
+            pressed |= self.buttons.p14;
+        }
+
+        if (self.ioregs.p1 >> 5) & 0x1 == 0 {
+            pressed |= self.buttons.p15;
+        }
+
+        !pressed & 0b1111
+    }
+
+    /// Applies GameShark codes. These are written directly to RAM, rather than
+    ///  through the bus, so they don't trigger watchpoints.
+    fn apply_cheats(&mut self) {
+        if self.rom.cheats.is_empty() {
+            return;
+        }
+
+        let cheats = self.rom.cheats;
+        for cheat in cheats.iter() {
+            match *cheat {
+                Cheat::GameShark {
+                    address,
+                    value,
+                    cart_bank: Some(bank),
+                } => self.rom.write_ram_bank(bank, address - 0xA000, value),
+                Cheat::GameShark { address, value, .. } => match address {
+                    0xA000..=0xBFFF => self.rom.write_ram(address - 0xA000, value),
+                    0xC000..=0xDFFF => self.ram[(address - 0xC000) as usize] = value,
+                    0xFF80..=0xFFFE => self.high_ram[(address - 0xFF80) as usize] = value,
+                    // Rejected when the code is parsed
+                    _ => {}
+                },
+                Cheat::GameGenie { .. } => {}
+            }
+        }
+    }
+
+    /// Writes the state of memory and the general I/O registers to a save
+    ///  state. Components with their own chunks (e.g. the GPU) are excluded.
+    #[cfg(feature = "heap_alloc")]
+    pub fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_u8(self.model as u8);
+        writer.write_bytes(&self.ram);
+        writer.write_bytes(&self.high_ram);
+        writer.write_u8(self.interrupt_reg);
+        writer.write_bool(self.boot_rom_mapped);
+
+        writer.write_u8(self.ioregs.p1);
+        writer.write_u16(self.ioregs.div);
+        writer.write_u8(self.ioregs.tima);
+        writer.write_u8(self.ioregs.tma);
+        writer.write_u8(self.ioregs.tac);
+        writer.write_u8(self.ioregs.iflag);
+        writer.write_u8(self.ioregs.dma);
+        writer.write_u8(self.ioregs.key1);
+        writer.write_i32(self.timer_counter);
+
+        writer.write_u8(self.buttons.p14);
+        writer.write_u8(self.buttons.p15);
+    }
+
+    /// Loads the state of memory and the general I/O registers from a save
+    ///  state.
+    #[cfg(feature = "heap_alloc")]
+    pub fn load_state(&mut self, reader: &mut StateReader, version: u16) -> Result<(), StateError> {
+        self.model = Model::from_id(reader.read_u8()?).ok_or_else(|| reader.malformed())?;
+        reader.read_bytes_into(&mut self.ram)?;
+        reader.read_bytes_into(&mut self.high_ram)?;
+        self.interrupt_reg = reader.read_u8()?;
+        // The boot ROM can't be mapped if we weren't started with one
+        self.boot_rom_mapped = reader.read_bool()? && self.boot_rom.is_some();
+
+        self.ioregs.p1 = reader.read_u8()?;
+        self.ioregs.div = reader.read_u16()?;
+        self.ioregs.tima = reader.read_u8()?;
+        self.ioregs.tma = reader.read_u8()?;
+        self.ioregs.tac = reader.read_u8()?;
+        self.ioregs.iflag = reader.read_u8()?;
+        self.ioregs.dma = reader.read_u8()?;
+        self.ioregs.key1 = reader.read_u8()?;
+        // Older versions kept the timer in the CPU chunk
+        if version >= 2 {
+            self.timer_counter = reader.read_i32()?;
+        }
+
+        self.buttons.p14 = reader.read_u8()?;
+        self.buttons.p15 = reader.read_u8()?;
+
+        Ok(())
     }
 
     /// Builds a new memory manager.
+    ///
+    /// * `model` - The hardware revision to emulate.
+    /// * `rom` - The cartridge to run.
+    /// * `boot_rom` - A optional boot ROM image (256 bytes for DMG/MGB, 2304 for
+    ///   CGB), to be executed before the cartridge. Without one, emulation
+    ///   starts from the state the boot ROM would have left behind.
     #[inline(always)]
-    pub fn build(rom: GameROM) -> GBMemory {
+    pub fn build(
+        model: Model,
+        rom: GameROM<'a>,
+        #[cfg(feature = "own_game_data")] boot_rom: Option<Vec<u8>>,
+        #[cfg(not(feature = "own_game_data"))] boot_rom: Option<&'a [u8]>,
+    ) -> GBMemory<'a> {
+        let run_boot_rom = boot_rom.is_some();
+
         GBMemory {
             rom,
             #[cfg(feature = "heap_alloc")]
@@ -200,14 +454,139 @@ impl GBMemory<'_> {
             #[cfg(not(feature = "heap_alloc"))]
             high_ram: [0; 127],
 
-            gpu: GPU::build(),
+            gpu: GPU::build(model, run_boot_rom),
             sound: Sound::build(),
+            serial: Serial::build(model, run_boot_rom),
+
+            #[cfg(feature = "heap_alloc")]
+            serial_device: ::serial::default_device(),
+            #[cfg(not(feature = "heap_alloc"))]
+            serial_device: None,
 
             dirty_interrupts: false,
             interrupt_reg: 0,
-            ioregs: IORegisters::build(),
+            ioregs: IORegisters::build(model, run_boot_rom),
+            timer_counter: 0,
 
             buttons: GameboyInput { p14: 0, p15: 0 },
+
+            model,
+
+            boot_rom,
+            boot_rom_mapped: run_boot_rom,
+
+            #[cfg(feature = "debugger")]
+            watchpoints: Watchpoints::default(),
+        }
+    }
+}
+
+impl<'a> Bus for GBMemory<'a> {
+    fn read(&self, ptr: u16) -> u8 {
+        GBMemory::read(self, ptr)
+    }
+
+    fn write(&mut self, ptr: u16, val: u8) {
+        GBMemory::write(self, ptr, val)
+    }
+
+    fn tick(&mut self, cycles: u8) -> bool {
+        // The timer and serial port are clocked at CPU speed
+        self.ioregs.div = self.ioregs.div.wrapping_add(cycles as u16);
+        self.step_timer(cycles);
+
+        if let Some(value) = self.step_serial(cycles) {
+            self.request_interrupt(value);
+        }
+
+        // Everything else runs at the same rate regardless of CPU speed
+        let device_cycles = if self.is_double_speed() {
+            cycles / 2
+        } else {
+            cycles
+        };
+
+        self.sound.step(&mut self.ioregs, device_cycles);
+
+        if let Some(value) = self.gpu.step(device_cycles as u32) {
+            self.request_interrupt(value);
+
+            if value == InterruptType::VBLANK {
+                self.apply_cheats();
+                return true;
+            }
+        }
+
+        false
+    }
+
+    fn read_short(&self, ptr: u16) -> u16 {
+        GBMemory::read_short(self, ptr)
+    }
+
+    fn write_short(&mut self, ptr: u16, val: u16) {
+        GBMemory::write_short(self, ptr, val)
+    }
+}
+
+impl<'a> GameboyBus for GBMemory<'a> {
+    /// Interrupts are only checked once something has requested one.
+    fn get_pending_interrupts(&self) -> u8 {
+        if self.dirty_interrupts {
+            self.ioregs.iflag & self.interrupt_reg & 0b11111
+        } else {
+            0
+        }
+    }
+
+    fn request_interrupt(&mut self, interrupt: InterruptType) {
+        self.ioregs.iflag |= 1 << (interrupt as u8);
+        self.dirty_interrupts = true;
+    }
+
+    fn acknowledge_interrupt(&mut self, interrupt: InterruptType) {
+        self.ioregs.iflag &= !(1 << interrupt as u8);
+    }
+
+    fn is_button_held(&self) -> bool {
+        self.get_joypad_lines() != 0b1111
+    }
+
+    fn stop(&mut self) -> bool {
+        self.ioregs.div = 0;
+
+        if self.ioregs.key1 & 0x1 == 1 {
+            // A speed switch has been armed through KEY1 - perform that instead
+            self.ioregs.key1 = (self.ioregs.key1 ^ (1 << 7)) & !0x1;
+            return true;
+        }
+
+        self.gpu.blank_screen();
+        false
+    }
+
+    fn is_double_speed(&self) -> bool {
+        (self.ioregs.key1 >> 7) & 0x1 == 1
+    }
+
+    fn is_display_enabled(&self) -> bool {
+        self.gpu.is_enabled()
+    }
+
+    fn get_rom_bank(&self) -> u8 {
+        self.rom.get_current_bank()
+    }
+
+    fn get_ram_bank(&self) -> u8 {
+        self.rom.get_current_ram_bank()
+    }
+
+    #[cfg(feature = "debugger")]
+    fn get_watchpoints(&self) -> Option<&Watchpoints> {
+        if self.watchpoints.is_empty() {
+            None
+        } else {
+            Some(&self.watchpoints)
         }
     }
 }
@@ -0,0 +1,63 @@
+/**
+ * model.rs
+ *
+ * The different Gameboy hardware revisions which can be emulated. The model
+ *  decides the state the system is left in after the boot ROM, as well as any
+ *  model-specific hardware quirks.
+**/
+
+#[derive(Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serialisation", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "debug_structs", derive(Debug))]
+pub enum Model {
+    /// The original Gameboy, with the early revision 0 boot ROM.
+    Dmg0,
+    /// The original Gameboy.
+    Dmg,
+    /// The Gameboy Pocket.
+    Mgb,
+    /// The Super Gameboy.
+    Sgb,
+    /// The Gameboy Color.
+    Cgb,
+    /// The Gameboy Advance, running in Gameboy Color mode.
+    Agb,
+}
+
+impl Model {
+    /// Returns if this model has Gameboy Color hardware.
+    pub fn is_cgb(&self) -> bool {
+        *self == Model::Cgb || *self == Model::Agb
+    }
+
+    /// Returns if this model is a Super Gameboy.
+    pub fn is_sgb(&self) -> bool {
+        *self == Model::Sgb
+    }
+
+    /// Looks up a model by its numeric ID (as given by `model as u8`).
+    pub fn from_id(id: u8) -> Option<Model> {
+        match id {
+            0 => Some(Model::Dmg0),
+            1 => Some(Model::Dmg),
+            2 => Some(Model::Mgb),
+            3 => Some(Model::Sgb),
+            4 => Some(Model::Cgb),
+            5 => Some(Model::Agb),
+            _ => None,
+        }
+    }
+
+    /// Looks up a model by its short name (e.g. "dmg", "cgb").
+    pub fn from_name(name: &str) -> Option<Model> {
+        match name {
+            "dmg0" => Some(Model::Dmg0),
+            "dmg" => Some(Model::Dmg),
+            "mgb" => Some(Model::Mgb),
+            "sgb" => Some(Model::Sgb),
+            "cgb" => Some(Model::Cgb),
+            "agb" => Some(Model::Agb),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,403 @@
+/**
+ * printer.rs
+ *
+ * Emulates the Gameboy Printer, which is connected to the link port. Games
+ *  send it packets of tile data and print commands, which are assembled here
+ *  into images.
+**/
+use serial::SerialDevice;
+
+use alloc::vec::Vec;
+
+/// The width of printed images, in pixels.
+pub const PRINT_WIDTH: usize = 160;
+/// The size of a single row of 20 tiles in the printer's buffer.
+const TILE_ROW_SIZE: usize = (PRINT_WIDTH / 8) * 16;
+/// The size of the printer's image buffer.
+const BUFFER_SIZE: usize = 0x2000;
+/// The number of status requests that report the printer as busy after
+///  printing.
+const PRINT_BUSY_POLLS: u8 = 4;
+
+const COMMAND_INIT: u8 = 0x1;
+const COMMAND_PRINT: u8 = 0x2;
+const COMMAND_DATA: u8 = 0x4;
+const COMMAND_STATUS: u8 = 0xF;
+
+const STATUS_CHECKSUM_ERROR: u8 = 1;
+const STATUS_PRINTING: u8 = 1 << 1;
+const STATUS_IMAGE_FULL: u8 = 1 << 2;
+const STATUS_UNPROCESSED: u8 = 1 << 3;
+
+/// A printed image. Each pixel is a shade from 0 (white) to 3 (black).
+pub struct PrintedImage {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+}
+
+/// Where the printer is up to in receiving a packet.
+#[derive(Copy, Clone, PartialEq)]
+enum PacketStage {
+    MagicFirst,
+    MagicSecond,
+    Command,
+    Compression,
+    LengthLow,
+    LengthHigh,
+    Data,
+    ChecksumLow,
+    ChecksumHigh,
+    Alive,
+    Status,
+}
+
+pub struct Printer {
+    stage: PacketStage,
+    command: u8,
+    compressed: bool,
+    length: u16,
+    data: Vec<u8>,
+    checksum: u16,
+    received_checksum: u16,
+
+    status: u8,
+    busy_polls: u8,
+    buffer: Vec<u8>,
+    /// Rows of a image which hasn't yet been fed out of the printer.
+    pending: Vec<u8>,
+    /// Images which have been fed out of the printer.
+    pub images: Vec<PrintedImage>,
+}
+
+impl Printer {
+    /// Takes all completed images from the printer.
+    pub fn take_images(&mut self) -> Vec<PrintedImage> {
+        core::mem::take(&mut self.images)
+    }
+
+    /// Handles a complete packet.
+    fn process_packet(&mut self) {
+        if self.checksum != self.received_checksum {
+            self.status |= STATUS_CHECKSUM_ERROR;
+            return;
+        }
+
+        self.status &= !STATUS_CHECKSUM_ERROR;
+
+        match self.command {
+            COMMAND_INIT => {
+                self.buffer.clear();
+                self.status = 0;
+                self.busy_polls = 0;
+            }
+            COMMAND_DATA => {
+                let data = core::mem::take(&mut self.data);
+                if self.compressed {
+                    self.decompress(&data);
+                } else {
+                    self.buffer.extend_from_slice(&data);
+                }
+                self.data = data;
+
+                self.buffer.truncate(BUFFER_SIZE);
+                if self.buffer.len() == BUFFER_SIZE {
+                    self.status |= STATUS_IMAGE_FULL;
+                }
+                if !self.buffer.is_empty() {
+                    self.status |= STATUS_UNPROCESSED;
+                }
+            }
+            COMMAND_PRINT if self.data.len() >= 4 => {
+                let margins = self.data[1];
+                let palette = self.data[2];
+                self.print(palette);
+
+                // Without a margin after this print, the next one follows on
+                //  as part of the same image
+                if margins & 0xF != 0 {
+                    self.feed();
+                }
+
+                self.status = (self.status & !(STATUS_UNPROCESSED | STATUS_IMAGE_FULL))
+                    | STATUS_PRINTING;
+                self.busy_polls = PRINT_BUSY_POLLS;
+            }
+            COMMAND_PRINT => {
+                // Missing the sheet count, margins, palette or exposure
+                #[cfg(feature = "logging")]
+                warn!("Malformed print packet ({} bytes)", self.data.len());
+            }
+            COMMAND_STATUS => {
+                self.busy_polls = self.busy_polls.saturating_sub(1);
+                if self.busy_polls == 0 {
+                    self.status &= !STATUS_PRINTING;
+                }
+            }
+            _ => {
+                #[cfg(feature = "logging")]
+                warn!("Unknown printer command: {:02X}", self.command);
+            }
+        }
+    }
+
+    /// Decompresses run-length encoded data into the image buffer.
+    fn decompress(&mut self, data: &[u8]) {
+        let mut pos = 0;
+        while pos < data.len() {
+            let control = data[pos];
+            pos += 1;
+
+            if control & 0x80 != 0 {
+                // A single byte, repeated
+                let length = (control & 0x7F) as usize + 2;
+                if let Some(&value) = data.get(pos) {
+                    let end = self.buffer.len() + length;
+                    self.buffer.resize(end, value);
+                }
+                pos += 1;
+            } else {
+                // A run of bytes, copied as is
+                let length = control as usize + 1;
+                let end = (pos + length).min(data.len());
+                self.buffer.extend_from_slice(&data[pos..end]);
+                pos = end;
+            }
+        }
+    }
+
+    /// Converts the tile data in the buffer into pixels, emptying it.
+    fn print(&mut self, palette: u8) {
+        // A empty palette is treated as the standard one
+        let palette = if palette == 0 { 0b11100100 } else { palette };
+
+        for row in self.buffer.chunks_exact(TILE_ROW_SIZE) {
+            for y in 0..8 {
+                for tile in row.chunks_exact(16) {
+                    let low = tile[y * 2];
+                    let high = tile[y * 2 + 1];
+
+                    for bit in (0..8).rev() {
+                        let color = (((high >> bit) & 0x1) << 1) | ((low >> bit) & 0x1);
+                        self.pending.push((palette >> (color * 2)) & 0b11);
+                    }
+                }
+            }
+        }
+
+        self.buffer.clear();
+    }
+
+    /// Feeds the current image out of the printer.
+    fn feed(&mut self) {
+        if self.pending.is_empty() {
+            return;
+        }
+
+        let pixels = core::mem::take(&mut self.pending);
+        self.images.push(PrintedImage {
+            width: PRINT_WIDTH,
+            height: pixels.len() / PRINT_WIDTH,
+            pixels,
+        });
+    }
+
+    pub fn build() -> Printer {
+        Printer {
+            stage: PacketStage::MagicFirst,
+            command: 0,
+            compressed: false,
+            length: 0,
+            data: Vec::new(),
+            checksum: 0,
+            received_checksum: 0,
+
+            status: 0,
+            busy_polls: 0,
+            buffer: Vec::new(),
+            pending: Vec::new(),
+            images: Vec::new(),
+        }
+    }
+}
+
+impl SerialDevice for Printer {
+    fn exchange(&mut self, out: u8) -> u8 {
+        let mut response = 0;
+
+        self.stage = match self.stage {
+            PacketStage::MagicFirst if out == 0x88 => PacketStage::MagicSecond,
+            PacketStage::MagicFirst => PacketStage::MagicFirst,
+            PacketStage::MagicSecond if out == 0x33 => PacketStage::Command,
+            PacketStage::MagicSecond if out == 0x88 => PacketStage::MagicSecond,
+            PacketStage::MagicSecond => PacketStage::MagicFirst,
+            PacketStage::Command => {
+                self.command = out;
+                self.checksum = out as u16;
+                PacketStage::Compression
+            }
+            PacketStage::Compression => {
+                self.compressed = out & 0x1 == 1;
+                self.checksum = self.checksum.wrapping_add(out as u16);
+                PacketStage::LengthLow
+            }
+            PacketStage::LengthLow => {
+                self.length = out as u16;
+                self.checksum = self.checksum.wrapping_add(out as u16);
+                PacketStage::LengthHigh
+            }
+            PacketStage::LengthHigh => {
+                self.length |= (out as u16) << 8;
+                self.checksum = self.checksum.wrapping_add(out as u16);
+                self.data.clear();
+
+                if self.length == 0 {
+                    PacketStage::ChecksumLow
+                } else {
+                    PacketStage::Data
+                }
+            }
+            PacketStage::Data => {
+                self.data.push(out);
+                self.checksum = self.checksum.wrapping_add(out as u16);
+
+                if self.data.len() == self.length as usize {
+                    PacketStage::ChecksumLow
+                } else {
+                    PacketStage::Data
+                }
+            }
+            PacketStage::ChecksumLow => {
+                self.received_checksum = out as u16;
+                PacketStage::ChecksumHigh
+            }
+            PacketStage::ChecksumHigh => {
+                self.received_checksum |= (out as u16) << 8;
+                self.process_packet();
+                PacketStage::Alive
+            }
+            PacketStage::Alive => {
+                response = 0x81;
+                PacketStage::Status
+            }
+            PacketStage::Status => {
+                response = self.status;
+                PacketStage::MagicFirst
+            }
+        };
+
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Sends a whole packet, returning the alive and status bytes.
+    fn send_packet(printer: &mut Printer, command: u8, compressed: bool, data: &[u8]) -> (u8, u8) {
+        let mut packet = vec![0x88, 0x33, command, compressed as u8];
+        packet.push(data.len() as u8);
+        packet.push((data.len() >> 8) as u8);
+        packet.extend_from_slice(data);
+
+        let checksum = packet[2..].iter().fold(0u16, |sum, &b| sum.wrapping_add(b as u16));
+        packet.push(checksum as u8);
+        packet.push((checksum >> 8) as u8);
+
+        for byte in packet {
+            assert_eq!(printer.exchange(byte), 0);
+        }
+
+        (printer.exchange(0), printer.exchange(0))
+    }
+
+    /// Returns a initialised printer with two rows of black tiles buffered.
+    fn build_loaded() -> Printer {
+        let mut printer = Printer::build();
+        send_packet(&mut printer, COMMAND_INIT, false, &[]);
+        send_packet(&mut printer, COMMAND_DATA, false, &[0xFF; TILE_ROW_SIZE * 2]);
+        printer
+    }
+
+    #[test]
+    fn init_responds_alive() {
+        let mut printer = Printer::build();
+        assert_eq!(send_packet(&mut printer, COMMAND_INIT, false, &[]), (0x81, 0));
+    }
+
+    #[test]
+    fn resyncs_on_garbage() {
+        let mut printer = Printer::build();
+        for &byte in &[0x00, 0x88, 0x12, 0x88, 0x88] {
+            printer.exchange(byte);
+        }
+        // The trailing 0x88 is taken as the start of this packet
+        for &byte in &[0x33, COMMAND_INIT, 0, 0, 0, COMMAND_INIT, 0] {
+            printer.exchange(byte);
+        }
+        assert_eq!(printer.exchange(0), 0x81);
+    }
+
+    #[test]
+    fn bad_checksum() {
+        let mut printer = Printer::build();
+        for &byte in &[0x88, 0x33, COMMAND_STATUS, 0, 0, 0, 0xFF, 0xFF] {
+            printer.exchange(byte);
+        }
+        printer.exchange(0);
+        assert_eq!(printer.exchange(0), STATUS_CHECKSUM_ERROR);
+    }
+
+    #[test]
+    fn prints_image() {
+        let mut printer = build_loaded();
+        assert_eq!(printer.status, STATUS_UNPROCESSED);
+
+        let (_, status) = send_packet(&mut printer, COMMAND_PRINT, false, &[1, 0x13, 0xE4, 0x40]);
+        assert_eq!(status, STATUS_PRINTING);
+
+        let images = printer.take_images();
+        assert_eq!(images.len(), 1);
+        assert_eq!((images[0].width, images[0].height), (PRINT_WIDTH, 16));
+        assert!(images[0].pixels.iter().all(|&shade| shade == 3));
+
+        // Busy until the game has polled the status a few times
+        for _ in 1..PRINT_BUSY_POLLS {
+            assert_eq!(send_packet(&mut printer, COMMAND_STATUS, false, &[]).1, STATUS_PRINTING);
+        }
+        assert_eq!(send_packet(&mut printer, COMMAND_STATUS, false, &[]).1, 0);
+    }
+
+    #[test]
+    fn joins_prints_without_margin() {
+        let mut printer = build_loaded();
+        send_packet(&mut printer, COMMAND_PRINT, false, &[1, 0x00, 0xE4, 0x40]);
+        assert!(printer.images.is_empty());
+
+        send_packet(&mut printer, COMMAND_DATA, false, &[0x00; TILE_ROW_SIZE]);
+        send_packet(&mut printer, COMMAND_PRINT, false, &[1, 0x03, 0xE4, 0x40]);
+
+        let images = printer.take_images();
+        assert_eq!(images.len(), 1);
+        assert_eq!(images[0].height, 24);
+    }
+
+    #[test]
+    fn decompresses_data() {
+        let mut printer = Printer::build();
+        // A run of 3 literal bytes, then a byte repeated 5 times
+        send_packet(&mut printer, COMMAND_DATA, true, &[0x02, 1, 2, 3, 0x83, 9]);
+        assert_eq!(printer.buffer, [1, 2, 3, 9, 9, 9, 9, 9]);
+    }
+
+    #[test]
+    fn ignores_short_print() {
+        let mut printer = build_loaded();
+        let (_, status) = send_packet(&mut printer, COMMAND_PRINT, false, &[1, 0x13]);
+
+        assert_eq!(status, STATUS_UNPROCESSED);
+        assert!(printer.images.is_empty());
+        assert_eq!(printer.buffer.len(), TILE_ROW_SIZE * 2);
+    }
+}
@@ -0,0 +1,255 @@
+/**
+ * rewind.rs
+ *
+ * Keeps a history of recent save states so that emulation can be stepped
+ *  backwards. Only the newest snapshot is kept in full - older ones are
+ *  stored as the run-length encoded XOR against the snapshot after them,
+ *  which is mostly zeros as little changes between snapshots.
+**/
+use cpu::CPU;
+use state::load_state;
+use state::save_state;
+
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+
+/// Runs of fewer zeros than this are kept inside a literal run, as ending the
+///  literal would cost more than it saves.
+const MIN_ZERO_RUN: usize = 4;
+
+pub struct Rewind {
+    /// The number of frames between snapshots.
+    interval: u32,
+    /// The maximum number of older snapshots to keep.
+    capacity: usize,
+    frames_since_snapshot: u32,
+
+    latest: Vec<u8>,
+    /// Deltas taking each snapshot back to the one before it, oldest first.
+    deltas: VecDeque<Vec<u8>>,
+}
+
+impl Rewind {
+    /// Called once per frame, taking a snapshot every `interval` frames.
+    pub fn capture(&mut self, cpu: &CPU) {
+        self.frames_since_snapshot += 1;
+        if !self.latest.is_empty() && self.frames_since_snapshot < self.interval {
+            return;
+        }
+        self.frames_since_snapshot = 0;
+
+        let snapshot = save_state(cpu);
+
+        if self.latest.len() == snapshot.len() {
+            self.deltas.push_back(encode_delta(&snapshot, &self.latest));
+            if self.deltas.len() > self.capacity {
+                self.deltas.pop_front();
+            }
+        } else {
+            // The state's layout has changed (e.g. a different ROM), so older
+            //  snapshots can't be reconstructed from this one
+            self.deltas.clear();
+        }
+
+        self.latest = snapshot;
+    }
+
+    /// Steps back by up to the specified number of frames, stopping at the
+    ///  furthest snapshot within that range. Returns false if there is no
+    ///  history left to rewind to.
+    pub fn rewind(&mut self, cpu: &mut CPU, frames: u32) -> bool {
+        if self.latest.is_empty() {
+            return false;
+        }
+
+        // The latest snapshot is up to a interval old already
+        let steps = frames.saturating_sub(self.frames_since_snapshot) / self.interval;
+
+        let mut rewound = self.frames_since_snapshot > 0;
+        for _ in 0..steps {
+            match self.deltas.pop_back() {
+                Some(delta) => {
+                    apply_delta(&mut self.latest, &delta);
+                    rewound = true;
+                }
+                None => break,
+            }
+        }
+
+        self.frames_since_snapshot = 0;
+
+        rewound && load_state(cpu, &self.latest).is_ok()
+    }
+
+    /// Returns the number of frames which can currently be rewound.
+    pub fn get_available_frames(&self) -> u32 {
+        self.deltas.len() as u32 * self.interval + self.frames_since_snapshot
+    }
+
+    /// Discards all history, e.g. after loading a save state.
+    pub fn clear(&mut self) {
+        self.latest.clear();
+        self.deltas.clear();
+        self.frames_since_snapshot = 0;
+    }
+
+    /// Builds a new rewind buffer.
+    ///
+    /// * `interval` - The number of frames between snapshots.
+    /// * `capacity` - The number of snapshots to keep.
+    pub fn build(interval: u32, capacity: usize) -> Rewind {
+        Rewind {
+            interval: interval.max(1),
+            capacity,
+            frames_since_snapshot: 0,
+
+            latest: Vec::new(),
+            deltas: VecDeque::new(),
+        }
+    }
+}
+
+fn write_length(output: &mut Vec<u8>, mut length: usize) {
+    loop {
+        let byte = (length & 0x7F) as u8;
+        length >>= 7;
+
+        if length == 0 {
+            output.push(byte);
+            return;
+        }
+
+        output.push(byte | 0x80);
+    }
+}
+
+fn read_length(input: &[u8], pos: &mut usize) -> usize {
+    let mut length = 0;
+    let mut shift = 0;
+
+    while let Some(&byte) = input.get(*pos) {
+        *pos += 1;
+        length |= ((byte & 0x7F) as usize) << shift;
+        shift += 7;
+
+        if byte & 0x80 == 0 {
+            break;
+        }
+    }
+
+    length
+}
+
+/// Encodes the XOR of two equally sized snapshots as a series of
+///  (zero run length, literal length, literal bytes) records.
+fn encode_delta(current: &[u8], previous: &[u8]) -> Vec<u8> {
+    let mut output = Vec::new();
+    let mut pos = 0;
+
+    while pos < current.len() {
+        let zeros_start = pos;
+        while pos < current.len() && current[pos] == previous[pos] {
+            pos += 1;
+        }
+
+        let literal_start = pos;
+        let mut zero_run = 0;
+        while pos < current.len() && zero_run < MIN_ZERO_RUN {
+            if current[pos] == previous[pos] {
+                zero_run += 1;
+            } else {
+                zero_run = 0;
+            }
+            pos += 1;
+        }
+        // Leave a trailing run of zeros for the next record
+        if zero_run == MIN_ZERO_RUN {
+            pos -= zero_run;
+        }
+
+        write_length(&mut output, literal_start - zeros_start);
+        write_length(&mut output, pos - literal_start);
+        output.extend(
+            current[literal_start..pos]
+                .iter()
+                .zip(&previous[literal_start..pos])
+                .map(|(a, b)| a ^ b),
+        );
+    }
+
+    output
+}
+
+/// Applies a delta from `encode_delta` to a snapshot.
+fn apply_delta(snapshot: &mut [u8], delta: &[u8]) {
+    let mut delta_pos = 0;
+    let mut pos = 0;
+
+    while delta_pos < delta.len() {
+        pos += read_length(delta, &mut delta_pos);
+        let literal_length = read_length(delta, &mut delta_pos);
+
+        for value in &delta[delta_pos..delta_pos + literal_length] {
+            snapshot[pos] ^= value;
+            pos += 1;
+        }
+        delta_pos += literal_length;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roundtrip(current: &[u8], previous: &[u8]) -> Vec<u8> {
+        let delta = encode_delta(current, previous);
+
+        let mut snapshot = current.to_vec();
+        apply_delta(&mut snapshot, &delta);
+        assert_eq!(snapshot, previous);
+
+        delta
+    }
+
+    #[test]
+    fn identical_snapshots() {
+        let snapshot: Vec<u8> = (0..1000).map(|i| i as u8).collect();
+        let delta = roundtrip(&snapshot, &snapshot);
+
+        // A single record of 1000 zeros, with no literal
+        assert_eq!(delta, [0xE8, 0x07, 0x00]);
+    }
+
+    #[test]
+    fn long_runs() {
+        // Runs long enough to need multi-byte lengths
+        let previous = vec![0x55; 40000];
+        let mut current = previous.clone();
+        for value in &mut current[200..20000] {
+            *value = 0xAA;
+        }
+        current[39999] = 0;
+
+        roundtrip(&current, &previous);
+    }
+
+    #[test]
+    fn short_zero_runs() {
+        // Gaps shorter than MIN_ZERO_RUN are kept inside literals
+        let previous = vec![0; 64];
+        let current: Vec<u8> = (0..64).map(|i| if i % 3 == 0 { 0 } else { i as u8 }).collect();
+
+        roundtrip(&current, &previous);
+    }
+
+    #[test]
+    fn differing_lengths() {
+        for &length in &[0, 1, 2, MIN_ZERO_RUN, 127, 128, 129, 16384] {
+            let previous: Vec<u8> = (0..length).map(|i| (i / 7) as u8).collect();
+            let current: Vec<u8> = (0..length).map(|i| (i / 5) as u8).collect();
+
+            roundtrip(&current, &previous);
+            roundtrip(&previous, &current);
+        }
+    }
+}
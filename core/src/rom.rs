@@ -10,6 +10,14 @@ use alloc::vec::Vec;
 #[cfg(feature = "heap_alloc")]
 use core::marker::PhantomData;
 
+use cheats::Cheats;
+#[cfg(feature = "heap_alloc")]
+use state::StateError;
+#[cfg(feature = "heap_alloc")]
+use state::StateReader;
+#[cfg(feature = "heap_alloc")]
+use state::StateWriter;
+
 /// The different kinds of cartridges that can be handled. Each has a
 ///  specific way of managing memory/providing additional capabilities.
 #[derive(PartialEq, Debug)]
@@ -44,6 +52,28 @@ pub enum CartridgeType {
     HudsonHuC1 = 0xFF,
 }
 
+impl CartridgeType {
+    /// Returns if the cartridge has a battery keeping its RAM (and clock, if
+    ///  any) powered when the system is off.
+    pub fn has_battery(&self) -> bool {
+        matches!(
+            self,
+            CartridgeType::RomMbc1RamBatt
+                | CartridgeType::RomMbc2Batt
+                | CartridgeType::RomRamBatt
+                | CartridgeType::RomMMMD1SramBatt
+                | CartridgeType::RomMbc3TimerBatt
+                | CartridgeType::RomMbc3TimerRamBatt
+                | CartridgeType::RomMbc3RamBatt
+                | CartridgeType::RomMbc5RamBatt
+                | CartridgeType::RomMbc5RumbleSramBatt
+                | CartridgeType::PocketCamera
+                | CartridgeType::HudsonHuC3
+                | CartridgeType::HudsonHuC1
+        )
+    }
+}
+
 /// Holds a game's ROM, and exposes interfaces to read information from
 ///  it intelligently.
 #[cfg_attr(feature = "serialisation", derive(Serialize, Deserialize))]
@@ -53,15 +83,30 @@ pub struct GameROM<'a> {
     #[cfg(not(feature = "own_game_data"))]
     backing_data: &'a [u8],
     current_bank: u8,
+    /// The bank of cart RAM selected through 0x4000-0x5FFF. On MBC1, this
+    ///  is only mapped in once RAM banking mode is selected.
+    ram_bank: u8,
+    /// MBC1's banking mode, where 0x4000-0x5FFF selects the bank of cart RAM
+    ///  rather than the upper bits of the ROM bank.
+    ram_banking_mode: bool,
 
     #[cfg(feature = "heap_alloc")]
     pub cart_ram: Vec<u8>,
     #[cfg(not(feature = "heap_alloc"))]
     pub cart_ram: [u8; 128 * 1024], // 128 Kbyte,
     ram_size: usize,
+    /// Set when cart RAM is written to, so frontends know when battery backed
+    ///  RAM needs to be saved. Frontends should clear this once saved.
+    #[cfg_attr(feature = "serialisation", serde(skip))]
+    pub ram_dirty: bool,
 
     pub cart_type: CartridgeType,
 
+    /// Game Genie codes are applied to reads, and GameShark codes are applied
+    ///  by memory at VBlank
+    #[cfg_attr(feature = "serialisation", serde(skip))]
+    pub cheats: Cheats,
+
     // Used to facilitate a lifetime in non-heap modes
     #[cfg(feature = "heap_alloc")]
     pub alloc_marker: PhantomData<&'a ()>,
@@ -69,7 +114,7 @@ pub struct GameROM<'a> {
 
 impl<'a> GameROM<'a> {
     pub fn read(&self, ptr: u16) -> u8 {
-        match self.cart_type {
+        let value = match self.cart_type {
             CartridgeType::RomOnly => self.backing_data[ptr as usize],
             CartridgeType::RomMbc1
             | CartridgeType::RomMbc1Ram
@@ -97,7 +142,9 @@ impl<'a> GameROM<'a> {
                 #[cfg(not(feature = "debug_structs"))]
                 panic!("Unimplemented cart type");
             }
-        }
+        };
+
+        self.cheats.patch(ptr, value)
     }
 
     pub fn read_ram(&self, ptr: u16) -> u8 {
@@ -107,7 +154,21 @@ impl<'a> GameROM<'a> {
             return 0xFF;
         }
 
-        self.cart_ram[ptr as usize]
+        match self.get_ram_offset(self.get_current_ram_bank(), ptr) {
+            Some(offset) => self.cart_ram[offset],
+            None => 0xFF,
+        }
+    }
+
+    /// Returns where a address in a bank of the cart RAM area is in cart RAM.
+    ///  Returns None if the cartridge has less RAM than that.
+    fn get_ram_offset(&self, bank: u8, ptr: u16) -> Option<usize> {
+        let offset = bank as usize * 0x2000 + ptr as usize;
+        if offset < self.ram_size {
+            Some(offset)
+        } else {
+            None
+        }
     }
 
     pub fn write(&mut self, ptr: u16, val: u8) {
@@ -129,10 +190,17 @@ impl<'a> GameROM<'a> {
                             self.current_bank = 1;
                         }
                     }
+                    0x4000..=0x5FFF => {
+                        // RAM bank switching (or the upper bits of the ROM bank)
+                        self.ram_bank = val & 0b11;
+                        if !self.ram_banking_mode && self.ram_bank != 0 {
+                            #[cfg(feature = "logging")]
+                            debug!("STUB: MBC1 upper ROM bank bits: {:02x}", val);
+                        }
+                    }
                     0x6000..=0x7FFF => {
-                        // Memory models
-                        #[cfg(feature = "logging")]
-                        warn!("MBC1 memory models are not supported!");
+                        // Banking mode select
+                        self.ram_banking_mode = val & 0x1 == 1;
                     }
                     _ => {
                         #[cfg(feature = "logging")]
@@ -190,6 +258,15 @@ impl<'a> GameROM<'a> {
                             self.current_bank = 1;
                         }
                     }
+                    0x4000..=0x5FFF => {
+                        // RAM bank switching (or mapping in a clock register)
+                        if val <= 0x3 {
+                            self.ram_bank = val;
+                        } else {
+                            #[cfg(feature = "logging")]
+                            debug!("STUB: MBC3 clock register select: {:02x}", val);
+                        }
+                    }
                     0x6000..=0x7FFF => {
                         // Memory models
                         #[cfg(feature = "logging")]
@@ -220,7 +297,30 @@ impl<'a> GameROM<'a> {
             return;
         }
 
-        self.cart_ram[ptr as usize] = val;
+        let bank = self.get_current_ram_bank();
+        self.write_ram_bank(bank, ptr, val);
+    }
+
+    /// Writes to a bank of cart RAM, whether or not it is mapped in. Writes
+    ///  past the end of the cartridge's RAM are ignored.
+    pub fn write_ram_bank(&mut self, bank: u8, ptr: u16, val: u8) {
+        if let Some(offset) = self.get_ram_offset(bank, ptr) {
+            self.cart_ram[offset] = val;
+            self.ram_dirty = true;
+        }
+    }
+
+    /// Returns the cartridge's RAM, as it should be written to a save file.
+    pub fn get_ram(&self) -> &[u8] {
+        &self.cart_ram[..self.ram_size]
+    }
+
+    /// Loads the cartridge's RAM from a save file. Saves of the wrong size are
+    ///  truncated or padded as needed.
+    pub fn load_ram(&mut self, data: &[u8]) {
+        let length = data.len().min(self.ram_size);
+        self.cart_ram[..length].copy_from_slice(&data[..length]);
+        self.ram_dirty = false;
     }
 
     pub fn get_cart_name(&self) -> &str {
@@ -237,6 +337,78 @@ impl<'a> GameROM<'a> {
         core::str::from_utf8(range).expect("Failed to read cart name")
     }
 
+    /// Returns the header checksum stored in the cartridge header.
+    pub fn get_header_checksum(&self) -> u8 {
+        self.backing_data[0x14D]
+    }
+
+    /// Returns the checksum of the entire ROM stored in the cartridge header.
+    pub fn get_global_checksum(&self) -> u16 {
+        ((self.backing_data[0x14E] as u16) << 8) | self.backing_data[0x14F] as u16
+    }
+
+    /// Returns the ROM bank currently mapped in at 0x4000-0x7FFF.
+    pub fn get_current_bank(&self) -> u8 {
+        self.current_bank
+    }
+
+    /// Returns the bank of cart RAM currently mapped in at 0xA000-0xBFFF.
+    pub fn get_current_ram_bank(&self) -> u8 {
+        match self.cart_type {
+            CartridgeType::RomMbc1 | CartridgeType::RomMbc1Ram | CartridgeType::RomMbc1RamBatt
+                if !self.ram_banking_mode =>
+            {
+                0
+            }
+            _ => self.ram_bank,
+        }
+    }
+
+    /// Returns if the cartridge declares support for Gameboy Color features.
+    pub fn is_cgb_game(&self) -> bool {
+        (self.backing_data[0x143] >> 7) & 0x1 == 1
+    }
+
+    /// Writes the mapper's state and cart RAM to a save state, along with the
+    ///  checksums identifying the cartridge.
+    #[cfg(feature = "heap_alloc")]
+    pub fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_u16(self.get_global_checksum());
+        writer.write_u8(self.get_header_checksum());
+        writer.write_u8(self.current_bank);
+        writer.write_u8(self.ram_bank);
+        writer.write_bool(self.ram_banking_mode);
+        writer.write_bytes(self.get_ram());
+    }
+
+    /// Loads the mapper's state from a save state, if it was made for this
+    ///  cartridge.
+    #[cfg(feature = "heap_alloc")]
+    pub fn load_state(&mut self, reader: &mut StateReader, version: u16) -> Result<(), StateError> {
+        if reader.read_u16()? != self.get_global_checksum()
+            || reader.read_u8()? != self.get_header_checksum()
+        {
+            return Err(StateError::DifferentRom);
+        }
+
+        self.current_bank = reader.read_u8()?;
+        // Cart RAM banks weren't switched before version 2
+        if version >= 2 {
+            self.ram_bank = reader.read_u8()?;
+            self.ram_banking_mode = reader.read_bool()?;
+        } else {
+            self.ram_bank = 0;
+            self.ram_banking_mode = false;
+        }
+
+        let ram_size = self.ram_size;
+        reader.read_bytes_into(&mut self.cart_ram[..ram_size])?;
+        // Cart RAM no longer matches what is saved on disk
+        self.ram_dirty = true;
+
+        Ok(())
+    }
+
     /// Builds a new ROM from the specified file. Expects
     ///  a correctly formatted file.
     ///
@@ -296,9 +468,14 @@ impl<'a> GameROM<'a> {
             backing_data: data,
             cart_type,
             current_bank: 1,
+            ram_bank: 0,
+            ram_banking_mode: false,
 
             cart_ram: ram,
             ram_size,
+            ram_dirty: false,
+
+            cheats: Cheats::default(),
 
             #[cfg(feature = "heap_alloc")]
             alloc_marker: PhantomData,
@@ -334,3 +511,132 @@ pub fn get_ram_size(id: u8) -> usize {
         _ => panic!("Unknown RAM size: {}", id),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "heap_alloc")]
+    use state::CHUNK_MAPPER;
+    #[cfg(not(feature = "own_game_data"))]
+    use std::boxed::Box;
+    use std::vec::Vec;
+
+    /// Builds a 64 Kbyte cartridge of the specified type and RAM size id.
+    fn build_rom(cart_type: u8, ram_size: u8) -> GameROM<'static> {
+        let mut data = ::std::vec![0; 64 * 1024];
+        data[0x147] = cart_type;
+        data[0x148] = 1;
+        data[0x149] = ram_size;
+        data[0x14D] = 0x12;
+        data[0x14E] = 0x34;
+        data[0x14F] = 0x56;
+
+        build_from(data)
+    }
+
+    #[cfg(feature = "own_game_data")]
+    fn build_from(data: Vec<u8>) -> GameROM<'static> {
+        GameROM::build(data)
+    }
+
+    #[cfg(not(feature = "own_game_data"))]
+    fn build_from(data: Vec<u8>) -> GameROM<'static> {
+        GameROM::build(Box::leak(data.into_boxed_slice()))
+    }
+
+    #[test]
+    fn mbc3_switches_ram_banks() {
+        let mut rom = build_rom(0x13, 3);
+        rom.write_ram(0x10, 0x11);
+
+        rom.write(0x4000, 2);
+        assert_eq!(rom.get_current_ram_bank(), 2);
+        assert_eq!(rom.read_ram(0x10), 0xFF);
+        rom.write_ram(0x10, 0x22);
+        assert_eq!(rom.cart_ram[2 * 0x2000 + 0x10], 0x22);
+
+        rom.write(0x4000, 0);
+        assert_eq!(rom.read_ram(0x10), 0x11);
+    }
+
+    #[test]
+    fn mbc3_clock_registers_keep_ram_bank() {
+        let mut rom = build_rom(0x10, 3);
+        rom.write(0x4000, 1);
+        rom.write(0x4000, 0x08);
+
+        assert_eq!(rom.get_current_ram_bank(), 1);
+    }
+
+    #[test]
+    fn mbc1_banks_ram_in_ram_banking_mode() {
+        let mut rom = build_rom(0x03, 3);
+
+        // ROM banking mode, where only the first bank is mapped in
+        rom.write(0x4000, 2);
+        assert_eq!(rom.get_current_ram_bank(), 0);
+
+        rom.write(0x6000, 1);
+        assert_eq!(rom.get_current_ram_bank(), 2);
+        rom.write_ram(0x10, 0xAB);
+        assert_eq!(rom.cart_ram[2 * 0x2000 + 0x10], 0xAB);
+
+        rom.write(0x6000, 0);
+        assert_eq!(rom.get_current_ram_bank(), 0);
+        assert_eq!(rom.read_ram(0x10), 0xFF);
+    }
+
+    #[test]
+    fn ignores_banks_past_end_of_ram() {
+        let mut rom = build_rom(0x13, 2);
+        rom.write(0x4000, 1);
+
+        assert_eq!(rom.read_ram(0), 0xFF);
+        rom.write_ram(0, 0);
+        assert!(!rom.ram_dirty);
+        assert!(rom.get_ram().iter().all(|&value| value == 0xFF));
+    }
+
+    #[test]
+    #[cfg(feature = "heap_alloc")]
+    fn saves_ram_bank() {
+        let mut rom = build_rom(0x03, 3);
+        rom.write(0x4000, 3);
+        rom.write(0x6000, 1);
+
+        let mut writer = StateWriter::build();
+        rom.save_state(&mut writer);
+        let payload = writer.into_payload();
+
+        let mut loaded = build_rom(0x03, 3);
+        let mut reader = StateReader::build(CHUNK_MAPPER, &payload);
+        assert!(loaded.load_state(&mut reader, 2).is_ok());
+        assert!(reader.is_empty());
+        assert_eq!(loaded.get_current_ram_bank(), 3);
+    }
+
+    #[test]
+    #[cfg(feature = "heap_alloc")]
+    fn loads_version_1_mapper_chunk() {
+        let mut ram = ::std::vec![0; 32 * 1024];
+        ram[0x10] = 0x42;
+
+        // Version 1 chunks have no RAM bank or banking mode
+        let mut writer = StateWriter::build();
+        writer.write_u16(0x3456);
+        writer.write_u8(0x12);
+        writer.write_u8(2);
+        writer.write_bytes(&ram);
+        let payload = writer.into_payload();
+
+        let mut rom = build_rom(0x13, 3);
+        rom.write(0x4000, 3);
+
+        let mut reader = StateReader::build(CHUNK_MAPPER, &payload);
+        assert!(rom.load_state(&mut reader, 1).is_ok());
+        assert!(reader.is_empty());
+        assert_eq!(rom.get_current_bank(), 2);
+        assert_eq!(rom.get_current_ram_bank(), 0);
+        assert_eq!(rom.read_ram(0x10), 0x42);
+    }
+}
@@ -0,0 +1,360 @@
+/**
+ * search.rs
+ *
+ * RAM search, for finding where a game keeps values such as lives or health.
+ *  A search starts with every address in WRAM, HRAM and cartridge RAM, and
+ *  each filter narrows this down by comparing the current values against a
+ *  number or against the values seen by the previous filter.
+**/
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use core::fmt;
+
+use mem::GBMemory;
+
+/// Where each searchable region starts in a snapshot of RAM.
+const WRAM_START: usize = 0;
+const HRAM_START: usize = WRAM_START + 8192;
+const CART_RAM_START: usize = HRAM_START + 127;
+
+#[derive(Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "debug_structs", derive(Debug))]
+pub enum SearchSize {
+    Byte,
+    /// Two bytes, little endian
+    Short,
+}
+
+impl SearchSize {
+    fn get_length(&self) -> usize {
+        match *self {
+            SearchSize::Byte => 1,
+            SearchSize::Short => 2,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "debug_structs", derive(Debug))]
+pub enum Filter {
+    Equal(u16),
+    Greater(u16),
+    Less(u16),
+    /// Different to the previous value
+    Changed,
+    Unchanged,
+    /// Greater than the previous value
+    Increased,
+    Decreased,
+}
+
+impl Filter {
+    fn matches(&self, value: u16, previous: u16) -> bool {
+        match *self {
+            Filter::Equal(target) => value == target,
+            Filter::Greater(target) => value > target,
+            Filter::Less(target) => value < target,
+            Filter::Changed => value != previous,
+            Filter::Unchanged => value == previous,
+            Filter::Increased => value > previous,
+            Filter::Decreased => value < previous,
+        }
+    }
+}
+
+/// A address as the game sees it, along with the bank it is in.
+#[derive(Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "debug_structs", derive(Debug))]
+pub struct RamLocation {
+    pub bank: u8,
+    pub address: u16,
+    /// If this is in cartridge RAM, rather than the Gameboy's own
+    pub cart_ram: bool,
+}
+
+impl RamLocation {
+    /// Returns the location of a offset into a snapshot of RAM.
+    fn from_offset(offset: usize) -> RamLocation {
+        if offset >= CART_RAM_START {
+            let offset = offset - CART_RAM_START;
+            RamLocation {
+                bank: (offset / 0x2000) as u8,
+                address: 0xA000 + (offset % 0x2000) as u16,
+                cart_ram: true,
+            }
+        } else if offset >= HRAM_START {
+            RamLocation {
+                bank: 0,
+                address: 0xFF80 + (offset - HRAM_START) as u16,
+                cart_ram: false,
+            }
+        } else {
+            let address = 0xC000 + (offset - WRAM_START) as u16;
+            RamLocation {
+                bank: if address >= 0xD000 { 1 } else { 0 },
+                address,
+                cart_ram: false,
+            }
+        }
+    }
+
+    /// Returns a GameShark code which keeps this location set to `value`.
+    pub fn to_gameshark(&self, value: u8) -> String {
+        // Cartridge RAM codes select the bank to write to
+        let code_type = if self.cart_ram { 0x80 | self.bank } else { 0x01 };
+
+        format!(
+            "{:02X}{:02X}{:02X}{:02X}",
+            code_type,
+            value,
+            self.address & 0xFF,
+            self.address >> 8
+        )
+    }
+}
+
+impl fmt::Display for RamLocation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:02X}:{:04X}", self.bank, self.address)
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "debug_structs", derive(Debug))]
+pub struct SearchResult {
+    pub location: RamLocation,
+    pub value: u16,
+    /// The value when the search was last filtered
+    pub previous: u16,
+    pub size: SearchSize,
+}
+
+impl SearchResult {
+    /// Returns GameShark codes which keep this location set to `value`.
+    pub fn to_gameshark(&self, value: u16) -> Vec<String> {
+        let mut codes = vec![self.location.to_gameshark(value as u8)];
+
+        if self.size == SearchSize::Short {
+            let high = RamLocation {
+                address: self.location.address + 1,
+                ..self.location
+            };
+            codes.push(high.to_gameshark((value >> 8) as u8));
+        }
+
+        codes
+    }
+}
+
+pub struct RamSearch {
+    size: SearchSize,
+    /// The contents of RAM when the search was last filtered
+    snapshot: Vec<u8>,
+    /// Offsets into the snapshot of the values which are still candidates
+    candidates: Vec<usize>,
+}
+
+impl RamSearch {
+    /// Copies all searchable RAM, in the order laid out by the `_START`
+    ///  constants.
+    fn take_snapshot(mem: &GBMemory) -> Vec<u8> {
+        let mut snapshot = Vec::with_capacity(CART_RAM_START + mem.rom.get_ram().len());
+        snapshot.extend_from_slice(&mem.ram[..]);
+        snapshot.extend_from_slice(&mem.high_ram[..]);
+        snapshot.extend_from_slice(mem.rom.get_ram());
+        snapshot
+    }
+
+    fn get_value(&self, snapshot: &[u8], offset: usize) -> u16 {
+        match self.size {
+            SearchSize::Byte => snapshot[offset] as u16,
+            SearchSize::Short => (snapshot[offset + 1] as u16) << 8 | snapshot[offset] as u16,
+        }
+    }
+
+    /// Narrows the search down to the values which match a filter, and
+    ///  returns how many are left.
+    pub fn filter(&mut self, mem: &GBMemory, filter: Filter) -> usize {
+        let snapshot = RamSearch::take_snapshot(mem);
+
+        let candidates = self
+            .candidates
+            .iter()
+            .cloned()
+            .filter(|&offset| {
+                filter.matches(
+                    self.get_value(&snapshot, offset),
+                    self.get_value(&self.snapshot, offset),
+                )
+            })
+            .collect();
+
+        self.candidates = candidates;
+        self.snapshot = snapshot;
+        self.candidates.len()
+    }
+
+    /// Returns the values which are still candidates, as they are now.
+    pub fn get_results<'a>(
+        &'a self,
+        mem: &GBMemory,
+    ) -> impl Iterator<Item = SearchResult> + 'a {
+        let snapshot = RamSearch::take_snapshot(mem);
+
+        self.candidates.iter().map(move |&offset| SearchResult {
+            location: RamLocation::from_offset(offset),
+            value: self.get_value(&snapshot, offset),
+            previous: self.get_value(&self.snapshot, offset),
+            size: self.size,
+        })
+    }
+
+    pub fn get_size(&self) -> SearchSize {
+        self.size
+    }
+
+    pub fn len(&self) -> usize {
+        self.candidates.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.candidates.is_empty()
+    }
+
+    /// Starts a new search, with every value in RAM as a candidate.
+    pub fn build(mem: &GBMemory, size: SearchSize) -> RamSearch {
+        let snapshot = RamSearch::take_snapshot(mem);
+
+        // Values can't span two regions, or two banks of cartridge RAM
+        let mut regions = vec![WRAM_START..HRAM_START, HRAM_START..CART_RAM_START];
+        regions.extend(
+            (CART_RAM_START..snapshot.len())
+                .step_by(0x2000)
+                .map(|start| start..(start + 0x2000).min(snapshot.len())),
+        );
+        let candidates = regions
+            .iter()
+            .flat_map(|region| {
+                let end = region.end.saturating_sub(size.get_length() - 1);
+                region.start..end.max(region.start)
+            })
+            .collect();
+
+        RamSearch {
+            size,
+            snapshot,
+            candidates,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use model::Model;
+    use rom::GameROM;
+    #[cfg(not(feature = "own_game_data"))]
+    use std::boxed::Box;
+
+    /// Builds memory for a cartridge with 32 Kbyte (four banks) of RAM.
+    fn build_memory() -> GBMemory<'static> {
+        let mut data = vec![0; 32 * 1024];
+        data[0x147] = 0x13; // MBC3+RAM+BATTERY
+        data[0x149] = 3;
+
+        #[cfg(feature = "own_game_data")]
+        let rom = GameROM::build(data);
+        #[cfg(not(feature = "own_game_data"))]
+        let rom = GameROM::build(Box::leak(data.into_boxed_slice()));
+
+        GBMemory::build(Model::Dmg, rom, None)
+    }
+
+    fn get_locations(search: &RamSearch, mem: &GBMemory) -> Vec<String> {
+        search
+            .get_results(mem)
+            .map(|result| format!("{}", result.location))
+            .collect()
+    }
+
+    #[test]
+    fn splits_shorts_at_regions_and_banks() {
+        let mem = build_memory();
+
+        let bytes = RamSearch::build(&mem, SearchSize::Byte);
+        assert_eq!(bytes.len(), 8192 + 127 + 4 * 0x2000);
+
+        // A short can't start on the last byte of a region or bank
+        let shorts = RamSearch::build(&mem, SearchSize::Short);
+        assert_eq!(shorts.len(), 8191 + 126 + 4 * 0x1FFF);
+
+        let locations = get_locations(&shorts, &mem);
+        for location in &["00:C000", "01:DFFE", "00:FF80", "00:FFFD", "00:A000", "03:BFFE"] {
+            assert!(locations.iter().any(|found| found == location), "{}", location);
+        }
+        for location in &["01:DFFF", "00:FFFE", "00:BFFF", "01:BFFF", "03:BFFF"] {
+            assert!(!locations.iter().any(|found| found == location), "{}", location);
+        }
+    }
+
+    #[test]
+    fn maps_offsets_to_banks() {
+        let cases = [
+            (WRAM_START, "00:C000", false),
+            (WRAM_START + 0xFFF, "00:CFFF", false),
+            (WRAM_START + 0x1000, "01:D000", false),
+            (HRAM_START, "00:FF80", false),
+            (HRAM_START + 126, "00:FFFE", false),
+            (CART_RAM_START, "00:A000", true),
+            (CART_RAM_START + 0x2005, "01:A005", true),
+            (CART_RAM_START + 0x7FFF, "03:BFFF", true),
+        ];
+
+        for &(offset, expected, cart_ram) in cases.iter() {
+            let location = RamLocation::from_offset(offset);
+            assert_eq!(format!("{}", location), expected);
+            assert_eq!(location.cart_ram, cart_ram, "{}", expected);
+        }
+    }
+
+    #[test]
+    fn filters_little_endian_shorts() {
+        let mut mem = build_memory();
+        mem.ram[0x10] = 0x34;
+        mem.ram[0x11] = 0x12;
+
+        let mut search = RamSearch::build(&mem, SearchSize::Short);
+        assert_eq!(search.filter(&mem, Filter::Equal(0x1234)), 1);
+        assert_eq!(get_locations(&search, &mem), ["00:C010"]);
+
+        mem.ram[0x11] = 0x13;
+        assert_eq!(search.filter(&mem, Filter::Increased), 1);
+        assert_eq!(search.filter(&mem, Filter::Changed), 0);
+    }
+
+    #[test]
+    fn generates_gameshark_codes() {
+        let wram = RamLocation::from_offset(WRAM_START + 0x1123);
+        assert_eq!(wram.to_gameshark(0x34), "013423D1");
+
+        // Cartridge RAM codes carry the bank
+        let cart_ram = RamLocation::from_offset(CART_RAM_START + 0x4005);
+        assert_eq!(cart_ram.to_gameshark(0x34), "823405A0");
+
+        let short = SearchResult {
+            location: RamLocation::from_offset(CART_RAM_START + 0x20FF),
+            value: 0,
+            previous: 0,
+            size: SearchSize::Short,
+        };
+        assert_eq!(short.to_gameshark(0x1234), ["8134FFA0", "811200A1"]);
+
+        let byte = SearchResult {
+            size: SearchSize::Byte,
+            ..short
+        };
+        assert_eq!(byte.to_gameshark(0x1234), ["8134FFA0"]);
+    }
+}
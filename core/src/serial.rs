@@ -0,0 +1,223 @@
+/**
+ * serial.rs
+ *
+ * Emulates the serial (link) port, and provides a interface for devices
+ *  connected to the other end of it.
+**/
+use cpu::interrupts::InterruptType;
+use model::Model;
+
+#[cfg(feature = "heap_alloc")]
+use alloc::boxed::Box;
+#[cfg(feature = "heap_alloc")]
+use alloc::rc::Rc;
+#[cfg(feature = "heap_alloc")]
+use core::cell::RefCell;
+
+#[cfg(feature = "heap_alloc")]
+use state::StateError;
+#[cfg(feature = "heap_alloc")]
+use state::StateReader;
+#[cfg(feature = "heap_alloc")]
+use state::StateWriter;
+#[cfg(feature = "heap_alloc")]
+use alloc::vec::Vec;
+
+/// Cycles taken to shift a single bit with the internal clock (8192Hz).
+const CYCLES_PER_BIT: u32 = 512;
+/// Cycles taken to shift a single bit with the CGB's fast internal clock (262144Hz).
+const FAST_CYCLES_PER_BIT: u32 = 16;
+
+/// A device connected to the Gameboy's link port. Implement this to connect
+///  the emulated Gameboy to something else.
+pub trait SerialDevice {
+    /// Called when the Gameboy has finished clocking out a byte using its
+    ///  internal clock. Returns the byte the device shifted back in return.
+    fn exchange(&mut self, out: u8) -> u8;
+
+    /// Polled while the Gameboy is waiting on the device to drive the clock.
+    ///  If the device has clocked in a full byte, this returns the byte sent,
+    ///  with `out` being the byte the Gameboy sent back in return.
+    fn poll_external(&mut self, _out: u8) -> Option<u8> {
+        None
+    }
+}
+
+/// Allows a device to be shared, so that it can still be inspected while
+///  connected (e.g. to collect printed images).
+#[cfg(feature = "heap_alloc")]
+impl<T: SerialDevice> SerialDevice for Rc<RefCell<T>> {
+    fn exchange(&mut self, out: u8) -> u8 {
+        self.borrow_mut().exchange(out)
+    }
+
+    fn poll_external(&mut self, out: u8) -> Option<u8> {
+        self.borrow_mut().poll_external(out)
+    }
+}
+
+/// Nothing is plugged into the link port.
+pub struct Disconnected;
+
+impl SerialDevice for Disconnected {
+    fn exchange(&mut self, _out: u8) -> u8 {
+        // The input line is pulled high with nothing connected
+        0xFF
+    }
+}
+
+/// Records every byte sent over the link port, logging each line as it is
+///  completed. Test ROMs (such as Blargg's) report their results this way.
+#[cfg(feature = "heap_alloc")]
+pub struct SerialLogger {
+    pub output: Vec<u8>,
+    line_start: usize,
+}
+
+#[cfg(feature = "heap_alloc")]
+impl SerialLogger {
+    pub fn build() -> SerialLogger {
+        SerialLogger {
+            output: Vec::new(),
+            line_start: 0,
+        }
+    }
+}
+
+#[cfg(feature = "heap_alloc")]
+impl SerialDevice for SerialLogger {
+    fn exchange(&mut self, out: u8) -> u8 {
+        self.output.push(out);
+
+        if out == b'\n' {
+            #[cfg(feature = "logging")]
+            info!(
+                "Serial: {}",
+                core::str::from_utf8(&self.output[self.line_start..self.output.len() - 1])
+                    .unwrap_or("<invalid UTF-8>")
+            );
+            self.line_start = self.output.len();
+        }
+
+        0xFF
+    }
+}
+
+#[cfg(feature = "heap_alloc")]
+pub fn default_device() -> Box<dyn SerialDevice> {
+    Box::new(Disconnected)
+}
+
+/// The state of the serial controller.
+#[cfg_attr(feature = "serialisation", derive(Serialize, Deserialize))]
+pub struct Serial {
+    pub sb: u8, // 0xFF01 - Serial transfer data (R/W)
+    pub sc: u8, // 0xFF02 - Serial transfer control (R/W)
+
+    bits_remaining: u8,
+    clock: u32,
+}
+
+impl Serial {
+    /// Handles a write to SC, starting a transfer if requested.
+    pub fn write_control(&mut self, model: Model, val: u8) {
+        // The fast clock select only exists on the CGB
+        self.sc = val & if model.is_cgb() { 0b10000011 } else { 0b10000001 };
+
+        if self.is_transferring() {
+            self.bits_remaining = 8;
+            self.clock = 0;
+        }
+    }
+
+    /// Reads SC, with unused bits set.
+    pub fn read_control(&self, model: Model) -> u8 {
+        self.sc | if model.is_cgb() { 0b1111100 } else { 0b1111110 }
+    }
+
+    /// Returns if a transfer is currently in progress.
+    pub fn is_transferring(&self) -> bool {
+        (self.sc >> 7) & 0x1 == 1
+    }
+
+    /// Returns if the transfer is being clocked by this Gameboy.
+    pub fn is_internal_clock(&self) -> bool {
+        self.sc & 0x1 == 1
+    }
+
+    /// Steps the serial controller, exchanging data with the connected device
+    ///  where needed. Returns a interrupt if a transfer has completed.
+    pub fn step(&mut self, cycles: u8, device: &mut dyn SerialDevice) -> Option<InterruptType> {
+        if !self.is_transferring() {
+            return None;
+        }
+
+        if self.is_internal_clock() {
+            let bit_length = if (self.sc >> 1) & 0x1 == 1 {
+                FAST_CYCLES_PER_BIT
+            } else {
+                CYCLES_PER_BIT
+            };
+
+            self.clock += cycles as u32;
+
+            while self.clock >= bit_length && self.bits_remaining > 0 {
+                self.clock -= bit_length;
+                self.bits_remaining -= 1;
+            }
+
+            if self.bits_remaining > 0 {
+                return None;
+            }
+
+            self.sb = device.exchange(self.sb);
+        } else {
+            match device.poll_external(self.sb) {
+                Some(value) => self.sb = value,
+                None => return None,
+            }
+        }
+
+        self.sc &= !(1 << 7);
+        self.bits_remaining = 0;
+
+        Some(InterruptType::SERIAL)
+    }
+
+    /// Writes the serial controller's state to a save state.
+    #[cfg(feature = "heap_alloc")]
+    pub fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_u8(self.sb);
+        writer.write_u8(self.sc);
+        writer.write_u8(self.bits_remaining);
+        writer.write_u32(self.clock);
+    }
+
+    /// Loads the serial controller's state from a save state.
+    #[cfg(feature = "heap_alloc")]
+    pub fn load_state(&mut self, reader: &mut StateReader, _version: u16) -> Result<(), StateError> {
+        self.sb = reader.read_u8()?;
+        self.sc = reader.read_u8()?;
+        self.bits_remaining = reader.read_u8()?;
+        self.clock = reader.read_u32()?;
+
+        Ok(())
+    }
+
+    /// Builds the serial controller. If the boot ROM is going to be run, this
+    ///  is the power-on state, otherwise the state the boot ROM leaves behind.
+    #[inline(always)]
+    pub fn build(model: Model, run_boot_rom: bool) -> Serial {
+        Serial {
+            sb: 0,
+            sc: if model.is_cgb() && !run_boot_rom {
+                0b11
+            } else {
+                0
+            },
+
+            bits_remaining: 0,
+            clock: 0,
+        }
+    }
+}
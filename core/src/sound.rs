@@ -3,6 +3,13 @@
 /// I/O register sound emulation.
 use io::IORegisters;
 
+#[cfg(feature = "heap_alloc")]
+use state::StateError;
+#[cfg(feature = "heap_alloc")]
+use state::StateReader;
+#[cfg(feature = "heap_alloc")]
+use state::StateWriter;
+
 #[cfg(feature = "heap_alloc")]
 #[cfg(feature = "enable_sound")]
 use alloc::vec::Vec;
@@ -319,6 +326,75 @@ impl Sound {
         (samples, old_pointer)
     }
 
+    /// Writes the sound registers and channel state to a save state.
+    #[cfg(feature = "heap_alloc")]
+    pub fn save_state(&self, writer: &mut StateWriter, registers: &IORegisters) {
+        for &value in [
+            registers.nr10, registers.nr11, registers.nr12, registers.nr13, registers.nr14,
+            registers.nr21, registers.nr22, registers.nr23, registers.nr24, registers.nr30,
+            registers.nr31, registers.nr32, registers.nr33, registers.nr34, registers.nr41,
+            registers.nr42, registers.nr43, registers.nr44, registers.nr50, registers.nr51,
+            registers.nr52,
+        ]
+        .iter()
+        {
+            writer.write_u8(value);
+        }
+        writer.write_bytes(&registers.wave);
+
+        writer.write_bool(self.channel_1_running);
+        writer.write_u64(self.channel_1_step);
+        writer.write_bool(self.channel_2_running);
+        writer.write_u64(self.channel_2_step);
+        writer.write_u64(self.last_cycle);
+    }
+
+    /// Loads the sound registers and channel state from a save state.
+    #[cfg(feature = "heap_alloc")]
+    pub fn load_state(
+        &mut self,
+        reader: &mut StateReader,
+        _version: u16,
+        registers: &mut IORegisters,
+    ) -> Result<(), StateError> {
+        for value in [
+            &mut registers.nr10,
+            &mut registers.nr11,
+            &mut registers.nr12,
+            &mut registers.nr13,
+            &mut registers.nr14,
+            &mut registers.nr21,
+            &mut registers.nr22,
+            &mut registers.nr23,
+            &mut registers.nr24,
+            &mut registers.nr30,
+            &mut registers.nr31,
+            &mut registers.nr32,
+            &mut registers.nr33,
+            &mut registers.nr34,
+            &mut registers.nr41,
+            &mut registers.nr42,
+            &mut registers.nr43,
+            &mut registers.nr44,
+            &mut registers.nr50,
+            &mut registers.nr51,
+            &mut registers.nr52,
+        ]
+        .iter_mut()
+        {
+            **value = reader.read_u8()?;
+        }
+        reader.read_bytes_into(&mut registers.wave)?;
+
+        self.channel_1_running = reader.read_bool()?;
+        self.channel_1_step = reader.read_u64()?;
+        self.channel_2_running = reader.read_bool()?;
+        self.channel_2_step = reader.read_u64()?;
+        self.last_cycle = reader.read_u64()?;
+
+        Ok(())
+    }
+
     #[inline(always)]
     pub fn build() -> Sound {
         Sound {
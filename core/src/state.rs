@@ -0,0 +1,313 @@
+/**
+ * state.rs
+ *
+ * A explicit, versioned save state format. States are made up of a header
+ *  followed by chunks, one per component, each with their own version:
+ *
+ *  Header: "OXGS", format version (u16)
+ *  Chunk:  tag (4 bytes), chunk version (u16), payload length (u32), payload
+ *
+ * All values are little endian. ROM data is not included, so a state can
+ *  only be loaded into a emulator running the same cartridge. When a
+ *  component's state changes, bump its chunk version below and have its
+ *  `load_state` handle the older versions.
+**/
+use cpu::CPU;
+
+use alloc::vec::Vec;
+use core::fmt;
+
+const MAGIC: &[u8; 4] = b"OXGS";
+const FORMAT_VERSION: u16 = 1;
+
+pub const CHUNK_CPU: [u8; 4] = *b"CPU ";
+pub const CHUNK_MEMORY: [u8; 4] = *b"MEM ";
+pub const CHUNK_PPU: [u8; 4] = *b"PPU ";
+pub const CHUNK_APU: [u8; 4] = *b"APU ";
+pub const CHUNK_MAPPER: [u8; 4] = *b"MAPR";
+/// Reserved for cartridge real time clocks, which are not yet emulated.
+pub const CHUNK_RTC: [u8; 4] = *b"RTC ";
+pub const CHUNK_SERIAL: [u8; 4] = *b"SERL";
+
+/// The current version of each chunk, in the order they are loaded. The
+///  mapper is loaded first, so that states for other ROMs are rejected before
+///  anything is changed.
+const CHUNK_VERSIONS: [([u8; 4], u16); 6] = [
+    (CHUNK_MAPPER, 2),
+    (CHUNK_CPU, 2),
+    (CHUNK_MEMORY, 2),
+    (CHUNK_PPU, 1),
+    (CHUNK_APU, 1),
+    (CHUNK_SERIAL, 1),
+];
+
+#[derive(Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "debug_structs", derive(Debug))]
+pub enum StateError {
+    /// The data isn't a save state.
+    InvalidHeader,
+    /// The state was made by a newer version of the emulator.
+    UnsupportedVersion(u16),
+    /// A required chunk is missing.
+    MissingChunk([u8; 4]),
+    /// A chunk was written by a newer version of the emulator.
+    UnsupportedChunkVersion([u8; 4], u16),
+    /// The state ends part way through a chunk.
+    Truncated,
+    /// A chunk's contents don't match what the component expects.
+    Malformed([u8; 4]),
+    /// The state was made for a different cartridge.
+    DifferentRom,
+}
+
+/// Returns a chunk tag as a printable name.
+fn tag_name(tag: &[u8; 4]) -> &str {
+    core::str::from_utf8(tag).unwrap_or("????")
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StateError::InvalidHeader => write!(f, "not a save state"),
+            StateError::UnsupportedVersion(version) => {
+                write!(f, "unsupported save state version {}", version)
+            }
+            StateError::MissingChunk(tag) => write!(f, "missing {} chunk", tag_name(tag)),
+            StateError::UnsupportedChunkVersion(tag, version) => write!(
+                f,
+                "unsupported {} chunk version {}",
+                tag_name(tag),
+                version
+            ),
+            StateError::Truncated => write!(f, "save state is truncated"),
+            StateError::Malformed(tag) => write!(f, "malformed {} chunk", tag_name(tag)),
+            StateError::DifferentRom => write!(f, "save state was made for a different ROM"),
+        }
+    }
+}
+
+/// Builds up the payload of a chunk.
+pub struct StateWriter {
+    data: Vec<u8>,
+}
+
+impl StateWriter {
+    /// Builds a writer for a single chunk's payload, for testing a component
+    ///  on its own.
+    #[cfg(test)]
+    pub fn build() -> StateWriter {
+        StateWriter { data: Vec::new() }
+    }
+
+    /// Returns the payload written so far.
+    #[cfg(test)]
+    pub fn into_payload(self) -> Vec<u8> {
+        self.data
+    }
+
+    pub fn write_u8(&mut self, value: u8) {
+        self.data.push(value);
+    }
+
+    pub fn write_bool(&mut self, value: bool) {
+        self.data.push(value as u8);
+    }
+
+    pub fn write_u16(&mut self, value: u16) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_u32(&mut self, value: u32) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_i32(&mut self, value: i32) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_u64(&mut self, value: u64) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    /// Writes a block of memory, prefixed with its length.
+    pub fn write_bytes(&mut self, value: &[u8]) {
+        self.write_u32(value.len() as u32);
+        self.data.extend_from_slice(value);
+    }
+
+    /// Writes a complete chunk, with the payload written by `contents`.
+    fn write_chunk<F: FnOnce(&mut StateWriter)>(&mut self, tag: [u8; 4], version: u16, contents: F) {
+        let mut chunk = StateWriter { data: Vec::new() };
+        contents(&mut chunk);
+
+        self.data.extend_from_slice(&tag);
+        self.write_u16(version);
+        self.write_bytes(&chunk.data);
+    }
+}
+
+/// Reads values back out of a chunk's payload.
+pub struct StateReader<'a> {
+    tag: [u8; 4],
+    data: &'a [u8],
+}
+
+impl<'a> StateReader<'a> {
+    /// Builds a reader over a single chunk's payload, for testing a component
+    ///  on its own.
+    #[cfg(test)]
+    pub fn build(tag: [u8; 4], data: &'a [u8]) -> StateReader<'a> {
+        StateReader { tag, data }
+    }
+
+    /// Returns if the whole payload has been read.
+    #[cfg(test)]
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    fn take(&mut self, length: usize) -> Result<&'a [u8], StateError> {
+        if self.data.len() < length {
+            return Err(StateError::Truncated);
+        }
+
+        let (value, rest) = self.data.split_at(length);
+        self.data = rest;
+        Ok(value)
+    }
+
+    pub fn read_u8(&mut self) -> Result<u8, StateError> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn read_bool(&mut self) -> Result<bool, StateError> {
+        Ok(self.read_u8()? != 0)
+    }
+
+    pub fn read_u16(&mut self) -> Result<u16, StateError> {
+        let value = self.take(2)?;
+        Ok(u16::from_le_bytes([value[0], value[1]]))
+    }
+
+    pub fn read_u32(&mut self) -> Result<u32, StateError> {
+        let value = self.take(4)?;
+        Ok(u32::from_le_bytes([value[0], value[1], value[2], value[3]]))
+    }
+
+    pub fn read_i32(&mut self) -> Result<i32, StateError> {
+        Ok(self.read_u32()? as i32)
+    }
+
+    pub fn read_u64(&mut self) -> Result<u64, StateError> {
+        let mut value = [0; 8];
+        value.copy_from_slice(self.take(8)?);
+        Ok(u64::from_le_bytes(value))
+    }
+
+    /// Reads a block of memory, which has to exactly fill `target`.
+    pub fn read_bytes_into(&mut self, target: &mut [u8]) -> Result<(), StateError> {
+        let length = self.read_u32()? as usize;
+        if length != target.len() {
+            return Err(self.malformed());
+        }
+
+        target.copy_from_slice(self.take(length)?);
+        Ok(())
+    }
+
+    /// Returns a error for a chunk which doesn't contain what was expected.
+    pub fn malformed(&self) -> StateError {
+        StateError::Malformed(self.tag)
+    }
+}
+
+/// Saves the state of the emulator.
+pub fn save_state(cpu: &CPU) -> Vec<u8> {
+    let mut writer = StateWriter { data: Vec::new() };
+    writer.data.extend_from_slice(MAGIC);
+    writer.write_u16(FORMAT_VERSION);
+
+    for &(tag, version) in CHUNK_VERSIONS.iter() {
+        writer.write_chunk(tag, version, |chunk| match tag {
+            CHUNK_MAPPER => cpu.mem.rom.save_state(chunk),
+            CHUNK_CPU => cpu.save_state(chunk),
+            CHUNK_MEMORY => cpu.mem.save_state(chunk),
+            CHUNK_PPU => cpu.mem.gpu.save_state(chunk),
+            CHUNK_APU => cpu.mem.sound.save_state(chunk, &cpu.mem.ioregs),
+            CHUNK_SERIAL => cpu.mem.serial.save_state(chunk),
+            _ => unreachable!(),
+        });
+    }
+
+    writer.data
+}
+
+/// Loads a save state made for the running cartridge. The structure of the
+///  state is checked before anything is loaded, but a corrupt chunk may still
+///  leave the emulator partially loaded.
+pub fn load_state(cpu: &mut CPU, data: &[u8]) -> Result<(), StateError> {
+    let mut reader = StateReader {
+        tag: [0; 4],
+        data,
+    };
+
+    if reader.take(4).map_err(|_| StateError::InvalidHeader)? != MAGIC {
+        return Err(StateError::InvalidHeader);
+    }
+
+    let format_version = reader.read_u16()?;
+    if format_version > FORMAT_VERSION {
+        return Err(StateError::UnsupportedVersion(format_version));
+    }
+
+    // Split out each chunk, ignoring any we don't know about
+    let mut chunks = Vec::new();
+    while !reader.data.is_empty() {
+        let mut tag = [0; 4];
+        tag.copy_from_slice(reader.take(4)?);
+        let version = reader.read_u16()?;
+        let length = reader.read_u32()? as usize;
+        let payload = reader.take(length)?;
+
+        chunks.push((tag, version, payload));
+    }
+
+    let mut found = Vec::new();
+    for &(tag, current_version) in CHUNK_VERSIONS.iter() {
+        let &(_, version, payload) = chunks
+            .iter()
+            .find(|chunk| chunk.0 == tag)
+            .ok_or(StateError::MissingChunk(tag))?;
+
+        if version == 0 || version > current_version {
+            return Err(StateError::UnsupportedChunkVersion(tag, version));
+        }
+
+        found.push((tag, version, payload));
+    }
+
+    for (tag, version, payload) in found {
+        let mut chunk = StateReader { tag, data: payload };
+
+        match tag {
+            CHUNK_MAPPER => cpu.mem.rom.load_state(&mut chunk, version)?,
+            CHUNK_CPU => cpu.load_state(&mut chunk, version)?,
+            CHUNK_MEMORY => cpu.mem.load_state(&mut chunk, version)?,
+            CHUNK_PPU => cpu.mem.gpu.load_state(&mut chunk, version)?,
+            CHUNK_APU => cpu
+                .mem
+                .sound
+                .load_state(&mut chunk, version, &mut cpu.mem.ioregs)?,
+            CHUNK_SERIAL => cpu.mem.serial.load_state(&mut chunk, version)?,
+            _ => unreachable!(),
+        }
+
+        if !chunk.data.is_empty() {
+            return Err(StateError::Malformed(tag));
+        }
+    }
+
+    cpu.mem.dirty_interrupts = true;
+
+    Ok(())
+}
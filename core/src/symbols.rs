@@ -0,0 +1,231 @@
+/**
+ * symbols.rs
+ *
+ * Symbol files map labels to locations in a game, so that debuggers can show
+ *  names instead of raw addresses. Reads the `BANK:ADDR Label` format written
+ *  by RGBDS (and used by no$gmb and BGB).
+**/
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+
+use core::fmt;
+
+use cpu::bus::GameboyBus;
+use cpu::disasm::Instruction;
+use cpu::disasm::Operand;
+
+#[derive(Clone, PartialEq)]
+#[cfg_attr(feature = "debug_structs", derive(Debug))]
+pub struct Symbol {
+    pub bank: u8,
+    pub address: u16,
+    pub name: String,
+}
+
+#[derive(Default)]
+pub struct SymbolTable {
+    /// Sorted by location, with labels at the same location in file order
+    symbols: Vec<Symbol>,
+}
+
+impl SymbolTable {
+    /// Parses a symbol file. Comments (after a `;`) and lines which aren't
+    ///  symbols are ignored.
+    pub fn parse(text: &str) -> SymbolTable {
+        let mut symbols = text
+            .lines()
+            .filter_map(|line| {
+                let line = line.split(';').next().unwrap_or("").trim();
+                let mut parts = line.split_whitespace();
+                let location = parts.next()?;
+                let name = parts.next()?;
+
+                let mut location = location.splitn(2, ':');
+                let bank = u8::from_str_radix(location.next()?, 16).ok()?;
+                let address = u16::from_str_radix(location.next()?, 16).ok()?;
+
+                Some(Symbol {
+                    bank,
+                    address,
+                    name: name.to_string(),
+                })
+            })
+            .collect::<Vec<_>>();
+
+        symbols.sort_by_key(|symbol| (symbol.bank, symbol.address));
+
+        SymbolTable { symbols }
+    }
+
+    /// Returns the label for a location, if there is one.
+    pub fn get_label(&self, bank: u8, address: u16) -> Option<&str> {
+        let index = self
+            .symbols
+            .partition_point(|symbol| (symbol.bank, symbol.address) < (bank, address));
+
+        self.symbols
+            .get(index)
+            .filter(|symbol| symbol.bank == bank && symbol.address == address)
+            .map(|symbol| symbol.name.as_str())
+    }
+
+    /// Returns the label for a address in whichever bank is currently mapped
+    ///  in.
+    pub fn get_mapped_label<B: GameboyBus>(&self, bus: &B, address: u16) -> Option<&str> {
+        self.get_label(bus.get_bank(address), address)
+    }
+
+    /// Finds a symbol by name.
+    pub fn find(&self, name: &str) -> Option<&Symbol> {
+        self.symbols.iter().find(|symbol| symbol.name == name)
+    }
+
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    /// Wraps a instruction so that it is displayed with labels in place of
+    ///  any addresses that have them.
+    pub fn label_instruction<'a, B: GameboyBus>(
+        &'a self,
+        bus: &'a B,
+        instruction: &'a Instruction,
+    ) -> LabelledInstruction<'a, B> {
+        LabelledInstruction {
+            symbols: self,
+            bus,
+            instruction,
+        }
+    }
+}
+
+pub struct LabelledInstruction<'a, B: GameboyBus> {
+    symbols: &'a SymbolTable,
+    bus: &'a B,
+    instruction: &'a Instruction,
+}
+
+impl<'a, B: GameboyBus> fmt::Display for LabelledInstruction<'a, B> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.instruction.mnemonic)?;
+
+        for (i, operand) in self.instruction.get_operands().enumerate() {
+            write!(f, "{}", if i == 0 { " " } else { "," })?;
+
+            let label = match *operand {
+                Operand::Target(address) | Operand::Address(address) => {
+                    self.symbols.get_mapped_label(self.bus, address)
+                }
+                _ => None,
+            };
+
+            match (label, operand) {
+                (Some(label), Operand::Address(_)) => write!(f, "({})", label)?,
+                (Some(label), _) => write!(f, "{}", label)?,
+                (None, operand) => write!(f, "{}", operand)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cpu::bus::Bus;
+
+    const SYMBOLS: &str = "; File generated by rgblink
+01:4000 Bank1Start
+00:0150 Main
+00:0100 Entry ; the header jumps here
+02:A000 SaveData
+00:0150 Main.loop
+not a symbol
+00:0000
+XX:1234 BadBank
+";
+
+    /// A bus with a fixed set of banks mapped in.
+    struct Banks {
+        rom_bank: u8,
+        ram_bank: u8,
+    }
+
+    impl Bus for Banks {
+        fn read(&self, _: u16) -> u8 {
+            0xFF
+        }
+
+        fn write(&mut self, _: u16, _: u8) {}
+
+        fn tick(&mut self, _: u8) -> bool {
+            false
+        }
+    }
+
+    impl GameboyBus for Banks {
+        fn get_rom_bank(&self) -> u8 {
+            self.rom_bank
+        }
+
+        fn get_ram_bank(&self) -> u8 {
+            self.ram_bank
+        }
+    }
+
+    #[test]
+    fn parses_symbols() {
+        let symbols = SymbolTable::parse(SYMBOLS);
+
+        assert_eq!(symbols.len(), 5);
+        assert_eq!(
+            symbols
+                .symbols
+                .iter()
+                .map(|symbol| symbol.name.as_str())
+                .collect::<Vec<_>>(),
+            ["Entry", "Main", "Main.loop", "Bank1Start", "SaveData"]
+        );
+    }
+
+    #[test]
+    fn looks_up_labels() {
+        let symbols = SymbolTable::parse(SYMBOLS);
+
+        assert_eq!(symbols.get_label(0, 0x0100), Some("Entry"));
+        assert_eq!(symbols.get_label(0, 0x0150), Some("Main"));
+        assert_eq!(symbols.get_label(1, 0x4000), Some("Bank1Start"));
+        assert_eq!(symbols.get_label(2, 0x4000), None);
+        assert_eq!(symbols.get_label(0, 0x0101), None);
+
+        let symbol = symbols.find("SaveData").unwrap();
+        assert_eq!((symbol.bank, symbol.address), (2, 0xA000));
+        assert!(symbols.find("BadBank").is_none());
+    }
+
+    #[test]
+    fn looks_up_mapped_labels() {
+        let symbols = SymbolTable::parse(SYMBOLS);
+        let bus = Banks {
+            rom_bank: 1,
+            ram_bank: 2,
+        };
+
+        assert_eq!(symbols.get_mapped_label(&bus, 0x4000), Some("Bank1Start"));
+        assert_eq!(symbols.get_mapped_label(&bus, 0xA000), Some("SaveData"));
+
+        let bus = Banks {
+            rom_bank: 2,
+            ram_bank: 0,
+        };
+
+        assert_eq!(symbols.get_mapped_label(&bus, 0x4000), None);
+        assert_eq!(symbols.get_mapped_label(&bus, 0xA000), None);
+    }
+}
@@ -0,0 +1,138 @@
+/**
+ * trace.rs
+ *
+ * Formats the CPU state as a single line before each instruction, in formats
+ *  used by other emulators and tools so that traces can be diffed against
+ *  them to find where execution diverges.
+**/
+use cpu::bus::GameboyBus;
+use cpu::disasm::disassemble;
+use cpu::CPU;
+
+use core::fmt;
+use core::fmt::Write;
+
+/// The address of the LCD Y coordinate (LY) register.
+const LY: u16 = 0xFF44;
+
+#[derive(Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "debug_structs", derive(Debug))]
+pub enum TraceFormat {
+    /// The format compared by Gameboy Doctor, e.g.
+    ///  `A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:00,C3,13,02`.
+    ///  Lines have to match exactly, so nothing else is included.
+    Doctor,
+    /// The format of BGB's trace log, with the memory region and bank of PC,
+    ///  the instruction and the LY register and cycle counter, e.g.
+    ///  `ROM0:0100 00       nop                 A:01 F:Z-HC BC:0013 DE:00D8 HL:014D SP:FFFE LY:00 CY:0`.
+    Bgb,
+}
+
+impl TraceFormat {
+    pub fn from_name(name: &str) -> Option<TraceFormat> {
+        match name {
+            "doctor" => Some(TraceFormat::Doctor),
+            "bgb" => Some(TraceFormat::Bgb),
+            _ => None,
+        }
+    }
+}
+
+/// Returns BGB's name for the region of memory containing the address.
+fn get_region(address: u16) -> &'static str {
+    match address {
+        0x0000..=0x7FFF => "ROM",
+        0x8000..=0x9FFF => "VRA",
+        0xA000..=0xBFFF => "SRA",
+        0xC000..=0xDFFF => "WRA",
+        0xE000..=0xFDFF => "ECH",
+        0xFE00..=0xFE9F => "OAM",
+        0xFF80..=0xFFFE => "HRA",
+        _ => "I/O",
+    }
+}
+
+/// Writes a line describing the current state of the CPU, before it executes
+///  the instruction at PC.
+pub fn write_trace<W: fmt::Write, B: GameboyBus>(
+    out: &mut W,
+    cpu: &CPU<B>,
+    format: TraceFormat,
+) -> fmt::Result {
+    let regs = &cpu.regs;
+
+    match format {
+        TraceFormat::Doctor => writeln!(
+            out,
+            "A:{:02X} F:{:02X} B:{:02X} C:{:02X} D:{:02X} E:{:02X} H:{:02X} L:{:02X} \
+             SP:{:04X} PC:{:04X} PCMEM:{:02X},{:02X},{:02X},{:02X}",
+            regs.a,
+            regs.f,
+            regs.b,
+            regs.c,
+            regs.d,
+            regs.e,
+            regs.h,
+            regs.l,
+            regs.sp,
+            regs.pc,
+            cpu.mem.read(regs.pc),
+            cpu.mem.read(regs.pc.wrapping_add(1)),
+            cpu.mem.read(regs.pc.wrapping_add(2)),
+            cpu.mem.read(regs.pc.wrapping_add(3)),
+        ),
+        TraceFormat::Bgb => {
+            let region = get_region(regs.pc);
+            let bank = cpu.mem.get_bank(regs.pc);
+            write!(out, "{}{:X}:{:04X} ", region, bank, regs.pc)?;
+
+            let instruction = disassemble(&cpu.mem, regs.pc);
+            for offset in 0..4 {
+                if offset < instruction.length {
+                    write!(out, "{:02X}", cpu.mem.read(regs.pc.wrapping_add(offset)))?;
+                } else {
+                    write!(out, "  ")?;
+                }
+            }
+
+            // Instructions don't implement width, so pad them manually
+            let mut counter = Counter { out, written: 0 };
+            write!(counter, " {}", instruction)?;
+            let written = counter.written;
+            for _ in written..20 {
+                write!(out, " ")?;
+            }
+
+            let flag = |set: bool, name: char| if set { name } else { '-' };
+
+            writeln!(
+                out,
+                " A:{:02X} F:{}{}{}{} BC:{:04X} DE:{:04X} HL:{:04X} SP:{:04X} LY:{:02X} CY:{}",
+                regs.a,
+                flag(regs.get_flag_z(), 'Z'),
+                flag(regs.get_flag_n(), 'N'),
+                flag(regs.get_flag_h(), 'H'),
+                flag(regs.get_flag_c(), 'C'),
+                regs.get_bc(),
+                regs.get_de(),
+                regs.get_hl(),
+                regs.sp,
+                cpu.mem.read(LY),
+                cpu.cycle_counter,
+            )
+        }
+    }
+}
+
+/// Passes writes through, counting the number of characters written.
+struct Counter<'a, W: fmt::Write> {
+    out: &'a mut W,
+    written: usize,
+}
+
+impl<'a, W: fmt::Write> fmt::Write for Counter<'a, W> {
+    fn write_str(&mut self, text: &str) -> fmt::Result {
+        self.written += text.chars().count();
+        self.out.write_str(text)
+    }
+}
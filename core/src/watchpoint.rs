@@ -0,0 +1,147 @@
+/**
+ * watchpoint.rs
+ *
+ * Watchpoints trigger the debugger when a range of memory is read, written or
+ *  executed. Memory accesses are checked as they happen, and the first hit
+ *  is reported to the debugger once the instruction has finished.
+**/
+use core::cell::Cell;
+use core::fmt;
+
+/// The number of watchpoints which can be set at once.
+pub const MAX_WATCHPOINTS: usize = 16;
+
+#[derive(Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "debug_structs", derive(Debug))]
+pub enum Access {
+    Read,
+    Write,
+    Execute,
+}
+
+impl fmt::Display for Access {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
+            Access::Read => "read",
+            Access::Write => "write",
+            Access::Execute => "execute",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "debug_structs", derive(Debug))]
+pub struct Watchpoint {
+    pub start: u16,
+    /// The last address watched (inclusive)
+    pub end: u16,
+    pub read: bool,
+    pub write: bool,
+    pub execute: bool,
+}
+
+impl Watchpoint {
+    fn matches(&self, address: u16, access: Access) -> bool {
+        let watched = match access {
+            Access::Read => self.read,
+            Access::Write => self.write,
+            Access::Execute => self.execute,
+        };
+
+        watched && address >= self.start && address <= self.end
+    }
+}
+
+impl fmt::Display for Watchpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:04X}", self.start)?;
+        if self.end != self.start {
+            write!(f, "-{:04X}", self.end)?;
+        }
+
+        write!(f, " ")?;
+        for (enabled, flag) in [(self.read, "r"), (self.write, "w"), (self.execute, "x")] {
+            if enabled {
+                write!(f, "{}", flag)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "debug_structs", derive(Debug))]
+pub struct WatchpointHit {
+    pub watchpoint: Watchpoint,
+    pub address: u16,
+    /// The value read or written (or the opcode, for execution)
+    pub value: u8,
+    pub access: Access,
+    /// The instruction which made the access
+    pub pc: u16,
+}
+
+#[derive(Default)]
+pub struct Watchpoints {
+    slots: [Option<Watchpoint>; MAX_WATCHPOINTS],
+    /// The first hit since this was last checked
+    hit: Cell<Option<WatchpointHit>>,
+}
+
+impl Watchpoints {
+    /// Adds a watchpoint. Returns false if there isn't any space left.
+    pub fn add(&mut self, watchpoint: Watchpoint) -> bool {
+        match self.slots.iter_mut().find(|slot| slot.is_none()) {
+            Some(slot) => {
+                *slot = Some(watchpoint);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Removes any watchpoints covering the specified address.
+    pub fn remove(&mut self, address: u16) {
+        for slot in &mut self.slots {
+            if slot.is_some_and(|watchpoint| address >= watchpoint.start && address <= watchpoint.end) {
+                *slot = None;
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.slots = [None; MAX_WATCHPOINTS];
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Watchpoint> {
+        self.slots.iter().flatten()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.iter().all(Option::is_none)
+    }
+
+    /// Checks a memory access against the watchpoints, recording it if it is
+    ///  the first hit.
+    pub fn check(&self, address: u16, access: Access, value: u8) {
+        if self.hit.get().is_some() {
+            return;
+        }
+
+        if let Some(watchpoint) = self.iter().find(|watchpoint| watchpoint.matches(address, access)) {
+            self.hit.set(Some(WatchpointHit {
+                watchpoint: *watchpoint,
+                address,
+                value,
+                access,
+                pc: 0,
+            }));
+        }
+    }
+
+    /// Returns (and clears) the first hit since this was last called.
+    pub fn take_hit(&self) -> Option<WatchpointHit> {
+        self.hit.take()
+    }
+}
@@ -0,0 +1,85 @@
+/**
+ * link.rs
+ *
+ * Connects two Gameboys with a link cable, and checks that a byte is
+ *  exchanged in each direction.
+**/
+extern crate oxidgb_core;
+
+#[cfg(feature = "debugger")]
+use oxidgb_core::cpu::GameboyDebugger;
+use oxidgb_core::cpu::CPU;
+use oxidgb_core::link::LinkCable;
+use oxidgb_core::mem::GBMemory;
+use oxidgb_core::model::Model;
+use oxidgb_core::rom::GameROM;
+
+#[cfg(feature = "debugger")]
+struct NoDebugger;
+
+#[cfg(feature = "debugger")]
+impl<'a> GameboyDebugger<'a> for NoDebugger {
+    fn debug(&mut self, _cpu: &mut CPU<'a>) {}
+}
+
+/// Builds a Gameboy running a program which sends `out`, using `control` as
+///  SC, and stores the byte received at 0xC000.
+fn build_cpu(out: u8, control: u8) -> CPU<'static> {
+    let program = [
+        0x3E, out, // ld a, out
+        0xE0, 0x01, // ldh (SB), a
+        0x3E, control, // ld a, control
+        0xE0, 0x02, // ldh (SC), a
+        0xF0, 0x02, // wait: ldh a, (SC)
+        0xCB, 0x7F, // bit 7, a
+        0x20, 0xFA, // jr nz, wait
+        0xF0, 0x01, // ldh a, (SB)
+        0xEA, 0x00, 0xC0, // ld ($C000), a
+        0x18, 0xFE, // jr @
+    ];
+
+    let mut data = vec![0; 32 * 1024];
+    data[0x100..0x100 + program.len()].copy_from_slice(&program);
+
+    CPU::build(GBMemory::build(Model::Dmg, GameROM::build(data), None))
+}
+
+fn run_frame(cable: &LinkCable, first: &mut CPU<'static>, second: &mut CPU<'static>) {
+    #[cfg(feature = "debugger")]
+    cable.run(first, second, &mut NoDebugger);
+    #[cfg(not(feature = "debugger"))]
+    cable.run(first, second);
+}
+
+#[test]
+fn exchanges_bytes() {
+    // The first Gameboy drives the clock, and the second waits on it
+    let mut first = build_cpu(0x42, 0x81);
+    let mut second = build_cpu(0x99, 0x80);
+
+    let cable = LinkCable::build();
+    cable.connect(&mut first, &mut second);
+
+    for _ in 0..2 {
+        run_frame(&cable, &mut first, &mut second);
+    }
+
+    assert_eq!(first.mem.read(0xC000), 0x99);
+    assert_eq!(second.mem.read(0xC000), 0x42);
+}
+
+#[test]
+fn disconnected_end_reads_high() {
+    let mut first = build_cpu(0x42, 0x81);
+    let mut second = build_cpu(0x99, 0x01);
+
+    let cable = LinkCable::build();
+    cable.connect(&mut first, &mut second);
+
+    for _ in 0..2 {
+        run_frame(&cable, &mut first, &mut second);
+    }
+
+    // The second Gameboy never starts a transfer, so doesn't shift
+    assert_eq!(first.mem.read(0xC000), 0xFF);
+}
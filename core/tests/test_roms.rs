@@ -0,0 +1,374 @@
+/**
+ * test_roms.rs
+ *
+ * Runs community test ROMs and checks for their pass signatures:
+ *  - Blargg's tests report over serial, or (for the later ones, e.g.
+ *    mem_timing-2 and dmg_sound) by writing a result code and text to cart
+ *    RAM after the signature DE B0 61 at 0xA001
+ *  - Mooneye-gb's tests load the Fibonacci numbers into B/C/D/E/H/L (and
+ *    send them over serial) before executing `LD B, B`
+ *  - Any other ROM with a .png next to it (e.g. dmg-acid2) is compared
+ *    against that reference screenshot
+ *
+ * ROMs are found by searching the directory in OXIDGB_TEST_ROMS, which is
+ *  expected to hold blargg's ROMs (as laid out in the gb-test-roms repo)
+ *  under `blargg/`. A pass/fail matrix is printed, and the test fails if any
+ *  ROM listed in test_roms_passing.txt doesn't pass (or isn't there to run).
+ *  If the variable isn't set, the test is skipped, saying so on stderr. Run
+ *  with:
+ *
+ *  OXIDGB_TEST_ROMS=path/to/roms cargo test --release \
+ *      --features heap_alloc,own_game_data --test test_roms -- --nocapture
+**/
+extern crate oxidgb_core;
+extern crate png;
+
+use std::cell::RefCell;
+use std::fs::File;
+use std::io::Write;
+use std::panic;
+use std::path::Path;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+#[cfg(feature = "debugger")]
+use oxidgb_core::cpu::GameboyDebugger;
+use oxidgb_core::cpu::CPU;
+use oxidgb_core::mem::GBMemory;
+use oxidgb_core::model::Model;
+use oxidgb_core::rom::GameROM;
+use oxidgb_core::serial::SerialLogger;
+
+const BLARGG_FRAMES: u32 = 4000;
+const MOONEYE_FRAMES: u32 = 1200;
+const SCREENSHOT_FRAMES: u32 = 300;
+
+/// Written to 0xA001-0xA003 by blargg's tests which report through cart RAM.
+const BLARGG_SIGNATURE: [u8; 3] = [0xDE, 0xB0, 0x61];
+/// The result code blargg's tests write to 0xA000 while they are running.
+const BLARGG_RUNNING: u8 = 0x80;
+
+const FIBONACCI: [u8; 6] = [3, 5, 8, 13, 21, 34];
+/// `LD B, B`, used by Mooneye's tests (and dmg-acid2) to signal completion.
+const DEBUG_BREAKPOINT: u8 = 0x40;
+
+#[derive(PartialEq)]
+enum Suite {
+    Blargg,
+    Mooneye,
+    Screenshot(PathBuf),
+}
+
+#[derive(PartialEq)]
+enum Outcome {
+    Pass,
+    Fail(String),
+}
+
+#[cfg(feature = "debugger")]
+struct NoDebugger;
+
+#[cfg(feature = "debugger")]
+impl<'a> GameboyDebugger<'a> for NoDebugger {
+    fn debug(&mut self, _cpu: &mut CPU<'a>) {}
+}
+
+/// Ticks the CPU, returning true once a frame has finished.
+fn tick(cpu: &mut CPU) -> bool {
+    #[cfg(feature = "debugger")]
+    let frame_done = cpu.tick(&mut NoDebugger);
+    #[cfg(not(feature = "debugger"))]
+    let frame_done = cpu.tick();
+
+    if frame_done || cpu.is_frame_timed_out() {
+        cpu.cycle_counter = 0;
+        return true;
+    }
+
+    false
+}
+
+/// Works out which suite a ROM belongs to from its path.
+fn get_suite(path: &Path) -> Option<Suite> {
+    let reference = path.with_extension("png");
+    if reference.exists() {
+        return Some(Suite::Screenshot(reference));
+    }
+
+    let components: Vec<String> = path
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().to_lowercase())
+        .collect();
+    let in_dir = |name: &str| components.iter().any(|c| c.contains(name));
+
+    let blargg = [
+        "cpu_instrs",
+        "instr_timing",
+        "mem_timing",
+        "dmg_sound",
+        "cgb_sound",
+        "oam_bug",
+        "halt_bug",
+        "interrupt_time",
+    ];
+
+    if blargg.iter().any(|name| in_dir(name)) {
+        Some(Suite::Blargg)
+    } else if in_dir("mooneye") || in_dir("acceptance") {
+        Some(Suite::Mooneye)
+    } else {
+        None
+    }
+}
+
+fn find_roms(dir: &Path, roms: &mut Vec<PathBuf>) {
+    let mut entries: Vec<PathBuf> = match std::fs::read_dir(dir) {
+        Ok(entries) => entries.filter_map(|e| e.ok()).map(|e| e.path()).collect(),
+        Err(_) => return,
+    };
+    entries.sort();
+
+    for path in entries {
+        if path.is_dir() {
+            find_roms(&path, roms);
+        } else if path.extension().is_some_and(|ext| ext == "gb" || ext == "gbc") {
+            roms.push(path);
+        }
+    }
+}
+
+fn run_blargg(cpu: &mut CPU, serial: &Rc<RefCell<SerialLogger>>) -> Outcome {
+    for _ in 0..BLARGG_FRAMES {
+        while !tick(cpu) {}
+
+        let output = String::from_utf8_lossy(&serial.borrow().output).into_owned();
+        if output.contains("Passed") {
+            return Outcome::Pass;
+        }
+        if output.contains("Failed") {
+            return Outcome::Fail(output.trim().lines().last().unwrap_or("").to_string());
+        }
+
+        if let Some(outcome) = read_blargg_ram(cpu) {
+            return outcome;
+        }
+    }
+
+    Outcome::Fail("timed out".to_string())
+}
+
+/// Reads the result that blargg's later tests leave in cart RAM, if they have
+///  finished.
+fn read_blargg_ram(cpu: &CPU) -> Option<Outcome> {
+    let signature = [
+        cpu.mem.read(0xA001),
+        cpu.mem.read(0xA002),
+        cpu.mem.read(0xA003),
+    ];
+    if signature != BLARGG_SIGNATURE {
+        return None;
+    }
+
+    match cpu.mem.read(0xA000) {
+        BLARGG_RUNNING => None,
+        0 => Some(Outcome::Pass),
+        code => {
+            let text: Vec<u8> = (0xA004..0xC000)
+                .map(|ptr| cpu.mem.read(ptr))
+                .take_while(|&c| c != 0)
+                .collect();
+            let text = String::from_utf8_lossy(&text);
+            let reason = text.trim().lines().last().unwrap_or("").to_string();
+
+            Some(Outcome::Fail(format!("code {}: {}", code, reason)))
+        }
+    }
+}
+
+fn run_mooneye(cpu: &mut CPU, serial: &Rc<RefCell<SerialLogger>>) -> Outcome {
+    let mut frames = 0;
+
+    while frames < MOONEYE_FRAMES {
+        if cpu.mem.read(cpu.regs.pc) == DEBUG_BREAKPOINT {
+            let regs = &cpu.regs;
+            let registers = [regs.b, regs.c, regs.d, regs.e, regs.h, regs.l];
+
+            return if registers == FIBONACCI || serial.borrow().output.ends_with(&FIBONACCI) {
+                Outcome::Pass
+            } else {
+                Outcome::Fail(format!("registers {:02X?}", registers))
+            };
+        }
+
+        if tick(cpu) {
+            frames += 1;
+        }
+    }
+
+    Outcome::Fail("timed out".to_string())
+}
+
+/// Converts a reference image into shades from 0 (white) to 3 (black).
+fn load_reference(path: &Path) -> Result<Vec<u8>, String> {
+    let file = File::open(path).map_err(|e| e.to_string())?;
+    let mut decoder = png::Decoder::new(file);
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder.read_info().map_err(|e| e.to_string())?;
+
+    let mut data = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut data).map_err(|e| e.to_string())?;
+
+    if info.width != 160 || info.height != 144 {
+        return Err(format!("reference is {}x{}", info.width, info.height));
+    }
+
+    // Ignore any alpha channel, and average colour channels
+    let channels = info.color_type.samples();
+    let colour_channels = if channels >= 3 { 3 } else { 1 };
+
+    Ok(data[..info.buffer_size()]
+        .chunks(channels)
+        .map(|pixel| {
+            pixel[..colour_channels].iter().map(|&c| c as u32).sum::<u32>() / colour_channels as u32
+        })
+        .map(|brightness| match brightness {
+            0xC0..=0xFF => 0,
+            0x80..=0xBF => 1,
+            0x40..=0x7F => 2,
+            _ => 3,
+        })
+        .collect())
+}
+
+fn run_screenshot(cpu: &mut CPU, reference: &Path) -> Outcome {
+    let expected = match load_reference(reference) {
+        Ok(expected) => expected,
+        Err(e) => return Outcome::Fail(format!("bad reference: {}", e)),
+    };
+
+    let mut frames = 0;
+    while frames < SCREENSHOT_FRAMES && cpu.mem.read(cpu.regs.pc) != DEBUG_BREAKPOINT {
+        if tick(cpu) {
+            frames += 1;
+        }
+    }
+    // Let the final frame finish drawing
+    while !tick(cpu) {}
+
+    let gpu = &cpu.mem.gpu;
+    let actual = gpu.pixel_data.chunks(3).map(|pixel| {
+        gpu.palette
+            .chunks(3)
+            .position(|colour| colour == pixel)
+            .unwrap_or(4) as u8
+    });
+
+    let mismatches = actual.zip(expected).filter(|(a, b)| a != b).count();
+    if mismatches == 0 {
+        Outcome::Pass
+    } else {
+        Outcome::Fail(format!("{} pixels differ", mismatches))
+    }
+}
+
+fn run_rom(path: &Path, suite: &Suite) -> Outcome {
+    let data = match std::fs::read(path) {
+        Ok(data) => data,
+        Err(e) => return Outcome::Fail(e.to_string()),
+    };
+
+    // Unsupported cartridges (and other emulation errors) panic
+    let result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+        let mut memory = GBMemory::build(Model::Dmg, GameROM::build(data), None);
+        let serial = Rc::new(RefCell::new(SerialLogger::build()));
+        memory.set_serial_device(Box::new(serial.clone()));
+        let mut cpu = CPU::build(memory);
+
+        match suite {
+            Suite::Blargg => run_blargg(&mut cpu, &serial),
+            Suite::Mooneye => run_mooneye(&mut cpu, &serial),
+            Suite::Screenshot(reference) => run_screenshot(&mut cpu, reference),
+        }
+    }));
+
+    result.unwrap_or_else(|_| Outcome::Fail("crashed".to_string()))
+}
+
+#[test]
+fn test_roms() {
+    let expected_passes: Vec<String> = include_str!("test_roms_passing.txt")
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(String::from)
+        .collect();
+
+    let root = match std::env::var_os("OXIDGB_TEST_ROMS") {
+        Some(root) => PathBuf::from(root),
+        None => {
+            // Written directly, so this isn't hidden by the test harness
+            let _ = writeln!(
+                std::io::stderr(),
+                "test_roms: OXIDGB_TEST_ROMS not set, skipping {} test ROMs",
+                expected_passes.len()
+            );
+            return;
+        }
+    };
+
+    let mut roms = Vec::new();
+    find_roms(&root, &mut roms);
+
+    let mut passed = 0;
+    let mut regressions = Vec::new();
+    // Listed ROMs which weren't found, and so can't have passed
+    let mut missing = expected_passes.clone();
+
+    println!("{:<60} {:<10} Result", "ROM", "Suite");
+    for path in roms {
+        let suite = match get_suite(&path) {
+            Some(suite) => suite,
+            None => continue,
+        };
+
+        let name = path
+            .strip_prefix(&root)
+            .unwrap_or(&path)
+            .to_string_lossy()
+            .replace('\\', "/");
+        let suite_name = match suite {
+            Suite::Blargg => "blargg",
+            Suite::Mooneye => "mooneye",
+            Suite::Screenshot(_) => "screenshot",
+        };
+
+        missing.retain(|expected| *expected != name);
+
+        let outcome = run_rom(&path, &suite);
+        match outcome {
+            Outcome::Pass => {
+                passed += 1;
+                println!("{:<60} {:<10} PASS", name, suite_name);
+            }
+            Outcome::Fail(ref reason) => {
+                println!("{:<60} {:<10} FAIL ({})", name, suite_name, reason);
+                if expected_passes.contains(&name) {
+                    regressions.push(name);
+                }
+            }
+        }
+    }
+
+    println!("{} passed", passed);
+
+    assert!(
+        missing.is_empty(),
+        "ROMs which are expected to pass weren't found: {:?}",
+        missing
+    );
+    assert!(
+        regressions.is_empty(),
+        "ROMs which previously passed now fail: {:?}",
+        regressions
+    );
+}
@@ -0,0 +1,12 @@
+/**
+ * lib.rs
+ *
+ * Code shared between the frontends, which don't depend on each other.
+**/
+#[cfg_attr(feature = "debugger", macro_use)]
+extern crate log;
+
+extern crate oxidgb_core;
+
+pub mod logging;
+pub mod trace;
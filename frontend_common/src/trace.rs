@@ -0,0 +1,110 @@
+/**
+ * trace.rs
+ *
+ * Logs every executed instruction, for comparing against traces from other
+ *  emulators.
+**/
+use std::io;
+use std::io::Write;
+
+use oxidgb_core::cpu::bus::GameboyBus;
+#[cfg(feature = "debugger")]
+use oxidgb_core::cpu::GameboyDebugger;
+use oxidgb_core::cpu::CPU;
+use oxidgb_core::trace::write_trace;
+use oxidgb_core::trace::TraceFormat;
+
+pub struct Tracer<W: Write> {
+    output: W,
+    format: TraceFormat,
+    /// Reused between instructions to avoid allocating each line
+    line: String,
+}
+
+impl<W: Write> Tracer<W> {
+    /// Logs the instruction about to be executed. Nothing is logged while the
+    ///  CPU is idling (e.g. halted), so each instruction is logged once.
+    pub fn trace<B: GameboyBus>(&mut self, cpu: &CPU<B>) -> io::Result<()> {
+        if !cpu.is_executing() {
+            return Ok(());
+        }
+
+        self.line.clear();
+        // Writing into a String can't fail
+        let _ = write_trace(&mut self.line, cpu, self.format);
+        self.output.write_all(self.line.as_bytes())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.output.flush()
+    }
+
+    pub fn build(output: W, format: TraceFormat) -> Tracer<W> {
+        Tracer {
+            output,
+            format,
+            line: String::new(),
+        }
+    }
+}
+
+/// A debugger which only traces each instruction, stopping if the log can't
+///  be written to.
+#[cfg(feature = "debugger")]
+pub struct TraceLog<W: Write>(pub Option<Tracer<W>>);
+
+#[cfg(feature = "debugger")]
+impl<'a, W: Write> GameboyDebugger<'a> for TraceLog<W> {
+    fn debug(&mut self, cpu: &mut CPU<'a>) {
+        if let Some(ref mut tracer) = self.0 {
+            if let Err(e) = tracer.trace(cpu) {
+                error!("Failed to write trace, stopping: {}", e);
+                self.0 = None;
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "debugger"))]
+mod tests {
+    use super::*;
+
+    use oxidgb_core::mem::GBMemory;
+    use oxidgb_core::model::Model;
+    use oxidgb_core::rom::GameROM;
+    use oxidgb_core::trace::TraceFormat;
+
+    /// Runs a program for two frames, returning the PC of each traced line.
+    fn trace_program(program: &[u8]) -> Vec<String> {
+        let mut data = vec![0; 32 * 1024];
+        data[0x100..0x100 + program.len()].copy_from_slice(program);
+        let mut cpu = CPU::build(GBMemory::build(Model::Dmg, GameROM::build(data), None));
+
+        let mut trace_log = TraceLog(Some(Tracer::build(Vec::new(), TraceFormat::Doctor)));
+        for _ in 0..2 {
+            while !cpu.tick(&mut trace_log) {}
+        }
+
+        let output = trace_log.0.unwrap().output;
+        String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| line.split("PC:").nth(1).unwrap()[..4].to_string())
+            .collect()
+    }
+
+    #[test]
+    fn traces_halt_once() {
+        let pcs = trace_program(&[
+            0x3E, 0x01, // ld a, VBLANK
+            0xE0, 0xFF, // ldh (IE), a
+            0x76, // halt, until VBlank
+            0x00, // nop
+            0x18, 0xFE, // jr @
+        ]);
+
+        assert_eq!(pcs[..5], ["0100", "0102", "0104", "0105", "0106"]);
+        assert_eq!(pcs.iter().filter(|&pc| pc == "0104").count(), 1);
+        assert!(pcs[4..].iter().all(|pc| pc == "0106"));
+    }
+}
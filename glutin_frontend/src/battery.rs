@@ -0,0 +1,72 @@
+/**
+ * battery.rs
+ *
+ * Persists battery backed cartridge RAM to a .sav file next to the ROM.
+**/
+use std::path::Path;
+use std::path::PathBuf;
+
+use oxidgb_core::rom::GameROM;
+
+/// How often (in frames) to check if RAM needs to be saved.
+const FLUSH_INTERVAL: u32 = 60;
+
+pub struct BatterySave {
+    path: PathBuf,
+    frames_since_flush: u32,
+}
+
+impl BatterySave {
+    /// Loads the existing save file (if any) into the cartridge.
+    pub fn load(&self, rom: &mut GameROM) {
+        if !self.path.exists() {
+            return;
+        }
+
+        match std::fs::read(&self.path) {
+            Ok(data) => {
+                info!("Loaded save from {}", self.path.display());
+                rom.load_ram(&data);
+            }
+            Err(e) => error!("Failed to read save file: {}", e),
+        }
+    }
+
+    /// Writes cartridge RAM to the save file if it has changed.
+    pub fn flush(&mut self, rom: &mut GameROM) {
+        self.frames_since_flush = 0;
+
+        if !rom.ram_dirty {
+            return;
+        }
+
+        match std::fs::write(&self.path, rom.get_ram()) {
+            Ok(()) => {
+                debug!("Wrote save to {}", self.path.display());
+                rom.ram_dirty = false;
+            }
+            Err(e) => error!("Failed to write save file: {}", e),
+        }
+    }
+
+    /// Called once per frame, periodically flushing RAM to disk.
+    pub fn on_frame(&mut self, rom: &mut GameROM) {
+        self.frames_since_flush += 1;
+
+        if self.frames_since_flush >= FLUSH_INTERVAL {
+            self.flush(rom);
+        }
+    }
+
+    /// Creates a save for the cartridge if it has a battery.
+    pub fn build(rom_path: &Path, rom: &GameROM) -> Option<BatterySave> {
+        if !rom.cart_type.has_battery() {
+            return None;
+        }
+
+        Some(BatterySave {
+            path: rom_path.with_extension("sav"),
+            frames_since_flush: 0,
+        })
+    }
+}
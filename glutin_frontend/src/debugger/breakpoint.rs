@@ -0,0 +1,154 @@
+/**
+ * breakpoint.rs
+ *
+ * Breakpoints, optionally only breaking when a condition (e.g. `a==3` or
+ *  `(C000)!=0 && hl>=9800`) holds.
+**/
+use std::fmt;
+
+use oxidgb_core::cpu::bus::GameboyBus;
+use oxidgb_core::cpu::CPU;
+
+use debugger::get_register;
+use debugger::parse_number;
+use debugger::REGISTER_NAMES;
+
+/// Comparison operators, with two character operators first so that they
+///  are matched before their single character prefixes.
+const OPERATORS: [(&str, Comparison); 6] = [
+    ("==", Comparison::Equal),
+    ("!=", Comparison::NotEqual),
+    ("<=", Comparison::LessOrEqual),
+    (">=", Comparison::GreaterOrEqual),
+    ("<", Comparison::Less),
+    (">", Comparison::Greater),
+];
+
+#[derive(Copy, Clone)]
+enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+/// What a condition examines.
+enum Operand {
+    Register(String),
+    Memory(u16),
+}
+
+/// A single `operand op value` comparison.
+struct Term {
+    operand: Operand,
+    comparison: Comparison,
+    value: u16,
+}
+
+impl Term {
+    fn parse(text: &str) -> Result<Term, String> {
+        let (position, operator, comparison) = OPERATORS
+            .iter()
+            .filter_map(|&(operator, comparison)| {
+                text.find(operator)
+                    .map(|position| (position, operator, comparison))
+            })
+            .min_by_key(|&(position, _, _)| position)
+            .ok_or_else(|| format!("No comparison in condition: {}", text))?;
+
+        let name = text[..position].trim().to_lowercase();
+        let value = parse_number(text[position + operator.len()..].trim())?;
+
+        let operand = if name.starts_with('(') && name.ends_with(')') {
+            Operand::Memory(parse_number(&name[1..name.len() - 1])?)
+        } else if REGISTER_NAMES.contains(&name.as_str()) {
+            Operand::Register(name)
+        } else {
+            return Err(format!("Unknown register: {}", name));
+        };
+
+        Ok(Term {
+            operand,
+            comparison,
+            value,
+        })
+    }
+
+    fn is_met(&self, cpu: &CPU) -> bool {
+        let actual = match self.operand {
+            Operand::Register(ref name) => get_register(&cpu.regs, name).unwrap_or(0),
+            Operand::Memory(address) => cpu.mem.read(address) as u16,
+        };
+
+        match self.comparison {
+            Comparison::Equal => actual == self.value,
+            Comparison::NotEqual => actual != self.value,
+            Comparison::Less => actual < self.value,
+            Comparison::LessOrEqual => actual <= self.value,
+            Comparison::Greater => actual > self.value,
+            Comparison::GreaterOrEqual => actual >= self.value,
+        }
+    }
+}
+
+pub struct Breakpoint {
+    pub address: u16,
+    /// Only breaks when this bank is mapped in, if set
+    pub bank: Option<u8>,
+    /// The condition as entered, for display
+    condition_text: Option<String>,
+    /// All of these have to be met for the breakpoint to trigger
+    condition: Vec<Term>,
+}
+
+impl Breakpoint {
+    /// Builds a breakpoint, with a optional condition made up of comparisons
+    ///  joined by `&&`.
+    pub fn build(
+        address: u16,
+        bank: Option<u8>,
+        condition: Option<&str>,
+    ) -> Result<Breakpoint, String> {
+        let terms = match condition {
+            Some(text) => text
+                .split("&&")
+                .map(Term::parse)
+                .collect::<Result<Vec<_>, _>>()?,
+            None => Vec::new(),
+        };
+
+        Ok(Breakpoint {
+            address,
+            bank,
+            condition_text: condition.map(|text| text.trim().to_string()),
+            condition: terms,
+        })
+    }
+
+    /// Returns if the CPU should stop here.
+    pub fn is_hit(&self, cpu: &CPU) -> bool {
+        let bank_matches = match self.bank {
+            Some(bank) => cpu.mem.get_bank(self.address) == bank,
+            None => true,
+        };
+
+        cpu.regs.pc == self.address
+            && bank_matches
+            && self.condition.iter().all(|term| term.is_met(cpu))
+    }
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(bank) = self.bank {
+            write!(f, "{:02X}:", bank)?;
+        }
+        write!(f, "{:04X}", self.address)?;
+        if let Some(ref condition) = self.condition_text {
+            write!(f, " if {}", condition)?;
+        }
+        Ok(())
+    }
+}
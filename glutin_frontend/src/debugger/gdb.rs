@@ -0,0 +1,725 @@
+/**
+ * gdb.rs
+ *
+ * A GDB remote serial protocol stub, so that games can be debugged with GDB
+ *  (or anything else which speaks the protocol) over a TCP socket. GDB has no
+ *  SM83 support of its own, so the registers are described to it with a
+ *  target description instead.
+**/
+use std::io;
+use std::io::ErrorKind;
+use std::io::Read;
+use std::io::Write;
+use std::net::TcpListener;
+use std::net::TcpStream;
+
+use oxidgb_core::cpu::CPU;
+
+/// Describes the registers sent in `g` packets, in order.
+const TARGET_XML: &str = "<?xml version=\"1.0\"?>\
+<!DOCTYPE target SYSTEM \"gdb-target.dtd\">\
+<target version=\"1.0\">\
+<feature name=\"org.oxidgb.sm83\">\
+<reg name=\"af\" bitsize=\"16\" type=\"uint16\"/>\
+<reg name=\"bc\" bitsize=\"16\" type=\"uint16\"/>\
+<reg name=\"de\" bitsize=\"16\" type=\"uint16\"/>\
+<reg name=\"hl\" bitsize=\"16\" type=\"uint16\"/>\
+<reg name=\"sp\" bitsize=\"16\" type=\"data_ptr\"/>\
+<reg name=\"pc\" bitsize=\"16\" type=\"code_ptr\"/>\
+</feature>\
+</target>";
+
+/// The number of registers in the target description.
+const REGISTER_COUNT: usize = 6;
+
+/// The largest packet GDB may send us (in hex, for `qSupported`).
+const PACKET_SIZE: usize = 0x1000;
+
+/// How many instructions to run between checking for a interrupt (or a new
+///  connection) from GDB, as polling the socket is slow.
+const POLL_INTERVAL: u32 = 4096;
+
+/// Signals reported to GDB when stopping.
+const SIGINT: u8 = 2;
+const SIGTRAP: u8 = 5;
+
+/// What to do after handling a packet.
+enum Action {
+    Reply(String),
+    Step,
+    Continue,
+    Detach,
+    Kill,
+}
+
+pub struct GdbStub {
+    listener: TcpListener,
+    stream: Option<TcpStream>,
+    breakpoints: Vec<u16>,
+    /// Stops (with the signal) before the next instruction
+    stop_signal: Option<u8>,
+    /// Stops before the next instruction without reporting it, as GDB asks
+    ///  why the target is stopped once it connects
+    connected: bool,
+    /// Set until the first connection, which the emulator waits for
+    waiting: bool,
+    poll_counter: u32,
+    pub shutdown: bool,
+}
+
+/// Parses a hexadecimal number, as used throughout the protocol.
+fn parse_hex(text: &str) -> Result<u32, String> {
+    u32::from_str_radix(text, 16).map_err(|_| format!("Invalid number: {}", text))
+}
+
+/// Parses a `ADDR,LENGTH` pair.
+fn parse_range(text: &str) -> Result<(u16, usize), String> {
+    let mut parts = text.splitn(2, ',');
+    let address = parse_hex(parts.next().unwrap_or(""))?;
+    let length = parse_hex(parts.next().unwrap_or(""))?;
+    Ok((address as u16, length as usize))
+}
+
+/// Returns the checksum of a packet's data, which is sent after it.
+fn get_checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte))
+}
+
+/// Registers are sent as little endian hex.
+fn encode_register(value: u16) -> String {
+    format!("{:02x}{:02x}", value & 0xFF, value >> 8)
+}
+
+fn decode_register(text: &str) -> Result<u16, String> {
+    let value = parse_hex(text)? as u16;
+    Ok(value.swap_bytes())
+}
+
+fn get_register(cpu: &CPU, index: usize) -> u16 {
+    match index {
+        0 => cpu.regs.get_af(),
+        1 => cpu.regs.get_bc(),
+        2 => cpu.regs.get_de(),
+        3 => cpu.regs.get_hl(),
+        4 => cpu.regs.sp,
+        _ => cpu.regs.pc,
+    }
+}
+
+fn set_register(cpu: &mut CPU, index: usize, value: u16) {
+    match index {
+        0 => cpu.regs.set_af(value),
+        1 => cpu.regs.set_bc(value),
+        2 => cpu.regs.set_de(value),
+        3 => cpu.regs.set_hl(value),
+        4 => cpu.regs.sp = value,
+        _ => cpu.regs.pc = value,
+    }
+}
+
+/// Handles a single packet from GDB, which may set or clear breakpoints.
+fn handle_packet(
+    cpu: &mut CPU,
+    breakpoints: &mut Vec<u16>,
+    packet: &str,
+) -> Result<Action, String> {
+    // Invalid UTF-8 is replaced when packets are read, so the first
+    //  character may be more than a byte
+    let command_length = packet.chars().next().map_or(0, char::len_utf8);
+    let (command, args) = packet.split_at(command_length);
+
+    let reply = match command {
+        "?" => format!("S{:02x}", SIGTRAP),
+        "g" => (0..REGISTER_COUNT)
+            .map(|index| encode_register(get_register(cpu, index)))
+            .collect(),
+        "G" => {
+            if !args.is_ascii() {
+                return Err("Invalid register data".to_string());
+            }
+            if args.len() < REGISTER_COUNT * 4 {
+                return Err("Too few registers".to_string());
+            }
+            for index in 0..REGISTER_COUNT {
+                let value = decode_register(&args[index * 4..index * 4 + 4])?;
+                set_register(cpu, index, value);
+            }
+            "OK".to_string()
+        }
+        "p" => {
+            let index = parse_hex(args)? as usize;
+            if index >= REGISTER_COUNT {
+                return Err(format!("Unknown register: {}", index));
+            }
+            encode_register(get_register(cpu, index))
+        }
+        "P" => {
+            let mut parts = args.splitn(2, '=');
+            let index = parse_hex(parts.next().unwrap_or(""))? as usize;
+            if index >= REGISTER_COUNT {
+                return Err(format!("Unknown register: {}", index));
+            }
+            let value = decode_register(parts.next().unwrap_or(""))?;
+            set_register(cpu, index, value);
+            "OK".to_string()
+        }
+        "m" => {
+            let (address, length) = parse_range(args)?;
+            (0..length.min(PACKET_SIZE / 2))
+                .map(|offset| cpu.mem.read(address.wrapping_add(offset as u16)))
+                .map(|value| format!("{:02x}", value))
+                .collect()
+        }
+        "M" => {
+            let mut parts = args.splitn(2, ':');
+            let (address, length) = parse_range(parts.next().unwrap_or(""))?;
+            let data = parts.next().unwrap_or("");
+            if !data.is_ascii() {
+                return Err("Invalid memory data".to_string());
+            }
+            if data.len() < length * 2 {
+                return Err("Too little data".to_string());
+            }
+            for offset in 0..length {
+                let value = parse_hex(&data[offset * 2..offset * 2 + 2])? as u8;
+                cpu.mem.write(address.wrapping_add(offset as u16), value);
+            }
+            "OK".to_string()
+        }
+        // Software and hardware breakpoints are treated the same
+        "Z" | "z" if args.starts_with('0') || args.starts_with('1') => {
+            let range = args.get(2..).ok_or("Missing breakpoint address")?;
+            let (address, _) = parse_range(range)?;
+            if command == "Z" {
+                if !breakpoints.contains(&address) {
+                    breakpoints.push(address);
+                }
+            } else {
+                breakpoints.retain(|&breakpoint| breakpoint != address);
+            }
+            "OK".to_string()
+        }
+        "s" | "c" => {
+            if !args.is_empty() {
+                cpu.regs.pc = parse_hex(args)? as u16;
+            }
+            return Ok(if command == "s" {
+                Action::Step
+            } else {
+                Action::Continue
+            });
+        }
+        "D" => return Ok(Action::Detach),
+        "k" => return Ok(Action::Kill),
+        "H" | "T" => "OK".to_string(),
+        "q" => handle_query(args),
+        // Anything unsupported gets a empty reply
+        _ => String::new(),
+    };
+
+    Ok(Action::Reply(reply))
+}
+
+/// Handles a general query (`q` packet).
+fn handle_query(query: &str) -> String {
+    if query.starts_with("Supported") {
+        format!("PacketSize={:x};qXfer:features:read+", PACKET_SIZE)
+    } else if let Some(args) = query.strip_prefix("Xfer:features:read:target.xml:") {
+        // Sent in chunks, with `l` marking the last one
+        match parse_range(args) {
+            Ok((offset, length)) => {
+                let offset = (offset as usize).min(TARGET_XML.len());
+                let end = (offset + length).min(TARGET_XML.len());
+                let prefix = if end == TARGET_XML.len() { "l" } else { "m" };
+                format!("{}{}", prefix, &TARGET_XML[offset..end])
+            }
+            Err(_) => "E00".to_string(),
+        }
+    } else if query == "Attached" {
+        "1".to_string()
+    } else if query == "C" {
+        "QC1".to_string()
+    } else if query == "fThreadInfo" {
+        "m1".to_string()
+    } else if query == "sThreadInfo" {
+        "l".to_string()
+    } else {
+        String::new()
+    }
+}
+
+impl GdbStub {
+    /// Reads a single byte, returning None if the connection has closed.
+    fn read_byte<S: Read>(stream: &mut S) -> io::Result<Option<u8>> {
+        let mut byte = [0u8; 1];
+        match stream.read(&mut byte)? {
+            0 => Ok(None),
+            _ => Ok(Some(byte[0])),
+        }
+    }
+
+    /// Reads the next packet (acknowledging it), returning None if the
+    ///  connection has closed.
+    fn read_packet<S: Read + Write>(stream: &mut S) -> io::Result<Option<String>> {
+        loop {
+            // Skip acknowledgements and interrupts until the start of a packet
+            match GdbStub::read_byte(stream)? {
+                Some(b'$') => {}
+                Some(_) => continue,
+                None => return Ok(None),
+            }
+
+            let mut data = Vec::new();
+            loop {
+                match GdbStub::read_byte(stream)? {
+                    Some(b'#') => break,
+                    Some(byte) => data.push(byte),
+                    None => return Ok(None),
+                }
+            }
+
+            let mut expected = [0u8; 2];
+            stream.read_exact(&mut expected)?;
+            let expected = std::str::from_utf8(&expected)
+                .ok()
+                .and_then(|text| u8::from_str_radix(text, 16).ok());
+
+            if expected == Some(get_checksum(&data)) {
+                stream.write_all(b"+")?;
+                return Ok(Some(String::from_utf8_lossy(&data).into_owned()));
+            }
+
+            // Ask for it to be sent again
+            stream.write_all(b"-")?;
+        }
+    }
+
+    fn send_packet<W: Write>(stream: &mut W, data: &str) -> io::Result<()> {
+        write!(stream, "${}#{:02x}", data, get_checksum(data.as_bytes()))
+    }
+
+    /// Reports why the CPU stopped (if there is a reason), then handles
+    ///  packets until GDB resumes execution.
+    fn stop(&mut self, cpu: &mut CPU, signal: Option<u8>) {
+        let mut stream = match self.stream.take() {
+            Some(stream) => stream,
+            None => return,
+        };
+
+        if let Some(signal) = signal {
+            if let Err(e) = GdbStub::send_packet(&mut stream, &format!("S{:02x}", signal)) {
+                info!("GDB disconnected: {}", e);
+                self.disconnect();
+                return;
+            }
+        }
+
+        loop {
+            let packet = match GdbStub::read_packet(&mut stream) {
+                Ok(Some(packet)) => packet,
+                Ok(None) => {
+                    info!("GDB disconnected");
+                    self.disconnect();
+                    return;
+                }
+                Err(e) => {
+                    info!("GDB disconnected: {}", e);
+                    self.disconnect();
+                    return;
+                }
+            };
+
+            let reply = match handle_packet(cpu, &mut self.breakpoints, &packet) {
+                Ok(Action::Reply(reply)) => reply,
+                Ok(Action::Step) => {
+                    self.stop_signal = Some(SIGTRAP);
+                    break;
+                }
+                Ok(Action::Continue) => break,
+                Ok(Action::Detach) => {
+                    let _ = GdbStub::send_packet(&mut stream, "OK");
+                    info!("GDB detached");
+                    self.disconnect();
+                    return;
+                }
+                Ok(Action::Kill) => {
+                    self.shutdown = true;
+                    return;
+                }
+                Err(message) => {
+                    debug!("GDB packet {:?} failed: {}", packet, message);
+                    "E01".to_string()
+                }
+            };
+
+            if GdbStub::send_packet(&mut stream, &reply).is_err() {
+                self.disconnect();
+                return;
+            }
+        }
+
+        self.stream = Some(stream);
+    }
+
+    /// Forgets the current connection and lets the game run freely, until GDB
+    ///  connects again.
+    fn disconnect(&mut self) {
+        self.stream = None;
+        self.breakpoints.clear();
+        self.stop_signal = None;
+        self.connected = false;
+    }
+
+    /// Checks for a new connection, or for GDB interrupting execution.
+    fn poll(&mut self) -> io::Result<()> {
+        match self.stream {
+            Some(ref mut stream) => {
+                stream.set_nonblocking(true)?;
+                let mut byte = [0u8; 1];
+                let result = stream.read(&mut byte);
+                stream.set_nonblocking(false)?;
+
+                match result {
+                    Ok(0) => {
+                        info!("GDB disconnected");
+                        self.disconnect();
+                    }
+                    // Ctrl-C is sent as a bare 0x03
+                    Ok(_) if byte[0] == 0x03 => self.stop_signal = Some(SIGINT),
+                    Ok(_) => {}
+                    Err(ref e) if e.kind() == ErrorKind::WouldBlock => {}
+                    Err(e) => return Err(e),
+                }
+            }
+            None => {
+                let (stream, peer) = match self.listener.accept() {
+                    Ok(connection) => connection,
+                    Err(ref e) if e.kind() == ErrorKind::WouldBlock => return Ok(()),
+                    Err(e) => return Err(e),
+                };
+                stream.set_nonblocking(false)?;
+                stream.set_nodelay(true)?;
+                info!("GDB connected from {}", peer);
+                self.stream = Some(stream);
+                self.connected = true;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Stops the CPU the next time an instruction is about to be executed.
+    pub fn interrupt(&mut self) {
+        if self.stream.is_some() {
+            self.stop_signal = Some(SIGTRAP);
+        }
+    }
+
+    /// Called before each instruction is executed.
+    pub fn debug(&mut self, cpu: &mut CPU) {
+        if self.waiting {
+            self.waiting = false;
+            // The game waits for the first connection, so it can be debugged
+            //  from the start
+            if let Err(e) = self.listener.set_nonblocking(false).and_then(|_| self.poll()) {
+                error!("Failed to accept GDB connection: {}", e);
+            }
+            if let Err(e) = self.listener.set_nonblocking(true) {
+                error!("Failed to poll for GDB connections: {}", e);
+            }
+        }
+
+        self.poll_counter += 1;
+        if self.poll_counter >= POLL_INTERVAL {
+            self.poll_counter = 0;
+            if let Err(e) = self.poll() {
+                info!("GDB disconnected: {}", e);
+                self.disconnect();
+            }
+        }
+
+        if self.stream.is_none() {
+            return;
+        }
+
+        if self.connected {
+            self.connected = false;
+            self.stop(cpu, None);
+            return;
+        }
+
+        let signal = if self.breakpoints.contains(&cpu.regs.pc) {
+            Some(SIGTRAP)
+        } else {
+            self.stop_signal
+        };
+
+        if signal.is_some() {
+            self.stop_signal = None;
+            self.stop(cpu, signal);
+        }
+    }
+
+    /// Listens for GDB on the specified address (e.g. `127.0.0.1:2345`).
+    pub fn build(addr: &str) -> io::Result<GdbStub> {
+        let listener = TcpListener::bind(addr)?;
+        info!("Waiting for GDB connection on {}", addr);
+
+        Ok(GdbStub {
+            listener,
+            stream: None,
+            breakpoints: Vec::new(),
+            stop_signal: None,
+            connected: false,
+            waiting: true,
+            poll_counter: 0,
+            shutdown: false,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Cursor;
+
+    use oxidgb_core::mem::GBMemory;
+    use oxidgb_core::model::Model;
+    use oxidgb_core::rom::GameROM;
+
+    /// Stands in for a connection to GDB, recording what is sent back.
+    struct Loopback {
+        input: Cursor<Vec<u8>>,
+        output: Vec<u8>,
+    }
+
+    impl Read for Loopback {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.input.read(buf)
+        }
+    }
+
+    impl Write for Loopback {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.output.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Reads a packet from the bytes GDB sent, returning it along with the
+    ///  acknowledgements sent back.
+    fn read_packet(input: &[u8]) -> (io::Result<Option<String>>, String) {
+        let mut stream = Loopback {
+            input: Cursor::new(input.to_vec()),
+            output: Vec::new(),
+        };
+        let packet = GdbStub::read_packet(&mut stream);
+        (packet, String::from_utf8(stream.output).unwrap())
+    }
+
+    fn build_cpu() -> CPU<'static> {
+        let rom = GameROM::build(vec![0; 32 * 1024]);
+        CPU::build(GBMemory::build(Model::Dmg, rom, None))
+    }
+
+    /// Handles a packet which should be replied to, returning the reply.
+    fn reply(cpu: &mut CPU, breakpoints: &mut Vec<u16>, packet: &str) -> Result<String, String> {
+        match handle_packet(cpu, breakpoints, packet)? {
+            Action::Reply(reply) => Ok(reply),
+            _ => panic!("{:?} didn't get a reply", packet),
+        }
+    }
+
+    #[test]
+    fn reads_packets() {
+        let (packet, acks) = read_packet(b"$m0,2#fb");
+        assert_eq!(packet.unwrap(), Some("m0,2".to_string()));
+        assert_eq!(acks, "+");
+
+        // Acknowledgements and interrupts before the packet are skipped
+        let (packet, acks) = read_packet(b"+\x03$?#3f");
+        assert_eq!(packet.unwrap(), Some("?".to_string()));
+        assert_eq!(acks, "+");
+    }
+
+    #[test]
+    fn rejects_bad_checksums() {
+        // Bad packets are asked for again, until a good one arrives
+        let (packet, acks) = read_packet(b"$m0,2#00$m0,2#zz$?#3f");
+        assert_eq!(packet.unwrap(), Some("?".to_string()));
+        assert_eq!(acks, "--+");
+    }
+
+    #[test]
+    fn reads_truncated_packets() {
+        assert_eq!(read_packet(b"").0.unwrap(), None);
+        assert_eq!(read_packet(b"$m0,2").0.unwrap(), None);
+        assert!(read_packet(b"$m0,2#f").0.is_err());
+    }
+
+    #[test]
+    fn replaces_invalid_utf8() {
+        let (packet, acks) = read_packet(b"$\xff#ff");
+        assert_eq!(packet.unwrap(), Some("\u{FFFD}".to_string()));
+        assert_eq!(acks, "+");
+
+        let mut cpu = build_cpu();
+        assert_eq!(
+            reply(&mut cpu, &mut Vec::new(), "\u{FFFD}"),
+            Ok(String::new())
+        );
+    }
+
+    #[test]
+    fn sends_packets() {
+        let mut output = Vec::new();
+        GdbStub::send_packet(&mut output, "OK").unwrap();
+        assert_eq!(output, b"$OK#9a");
+    }
+
+    #[test]
+    fn rejects_malformed_packets() {
+        let mut cpu = build_cpu();
+        let mut breakpoints = Vec::new();
+
+        for packet in &[
+            "Z0",
+            "z1,",
+            "Z0,zz,1",
+            "G1234",
+            "G\u{e9}\u{e9}\u{e9}\u{e9}\u{e9}\u{e9}\u{e9}\u{e9}\u{e9}\u{e9}\u{e9}\u{e9}",
+            "M",
+            "Mc000,2:12",
+            "Mc000,1:\u{e9}",
+            "m",
+            "mc000",
+            "p6",
+            "pz",
+            "P0",
+            "P9=0000",
+        ] {
+            assert!(
+                handle_packet(&mut cpu, &mut breakpoints, packet).is_err(),
+                "{}",
+                packet
+            );
+        }
+
+        // Unsupported breakpoint types get a empty reply, like any other
+        //  unsupported packet
+        assert_eq!(reply(&mut cpu, &mut breakpoints, "Z"), Ok(String::new()));
+        assert_eq!(
+            reply(&mut cpu, &mut breakpoints, "Z2,c000,1"),
+            Ok(String::new())
+        );
+        assert!(breakpoints.is_empty());
+    }
+
+    #[test]
+    fn sets_breakpoints() {
+        let mut cpu = build_cpu();
+        let mut breakpoints = Vec::new();
+
+        assert_eq!(
+            reply(&mut cpu, &mut breakpoints, "Z0,150,1"),
+            Ok("OK".to_string())
+        );
+        assert_eq!(
+            reply(&mut cpu, &mut breakpoints, "Z1,150,1"),
+            Ok("OK".to_string())
+        );
+        assert_eq!(breakpoints, [0x150]);
+
+        assert_eq!(
+            reply(&mut cpu, &mut breakpoints, "z0,150,1"),
+            Ok("OK".to_string())
+        );
+        assert!(breakpoints.is_empty());
+    }
+
+    #[test]
+    fn reads_registers_in_order() {
+        let mut cpu = build_cpu();
+        cpu.regs.set_af(0x12B0);
+        cpu.regs.set_bc(0x3456);
+        cpu.regs.set_de(0x789A);
+        cpu.regs.set_hl(0xBCDE);
+        cpu.regs.sp = 0xFFFE;
+        cpu.regs.pc = 0x0150;
+
+        // AF, BC, DE, HL, SP then PC, each little endian
+        assert_eq!(
+            reply(&mut cpu, &mut Vec::new(), "g"),
+            Ok("b01256349a78debcfeff5001".to_string())
+        );
+        assert_eq!(
+            reply(&mut cpu, &mut Vec::new(), "p5"),
+            Ok("5001".to_string())
+        );
+    }
+
+    #[test]
+    fn writes_registers_in_order() {
+        let mut cpu = build_cpu();
+        assert_eq!(
+            reply(&mut cpu, &mut Vec::new(), "Gb01256349a78debcfeff5001"),
+            Ok("OK".to_string())
+        );
+
+        assert_eq!(cpu.regs.get_af(), 0x12B0);
+        assert_eq!(cpu.regs.get_bc(), 0x3456);
+        assert_eq!(cpu.regs.get_de(), 0x789A);
+        assert_eq!(cpu.regs.get_hl(), 0xBCDE);
+        assert_eq!(cpu.regs.sp, 0xFFFE);
+        assert_eq!(cpu.regs.pc, 0x0150);
+
+        assert_eq!(
+            reply(&mut cpu, &mut Vec::new(), "P4=3412"),
+            Ok("OK".to_string())
+        );
+        assert_eq!(cpu.regs.sp, 0x1234);
+    }
+
+    #[test]
+    fn reads_and_writes_memory() {
+        let mut cpu = build_cpu();
+        assert_eq!(
+            reply(&mut cpu, &mut Vec::new(), "Mc000,2:ab0c"),
+            Ok("OK".to_string())
+        );
+        assert_eq!(
+            reply(&mut cpu, &mut Vec::new(), "mc000,3"),
+            Ok("ab0c00".to_string())
+        );
+    }
+
+    #[test]
+    fn sends_target_description_in_chunks() {
+        let mut xml = String::new();
+        loop {
+            let query = format!("Xfer:features:read:target.xml:{:x},40", xml.len());
+            let chunk = handle_query(&query);
+            let (prefix, data) = chunk.split_at(1);
+            assert!(data.len() <= 0x40);
+            xml.push_str(data);
+
+            if prefix == "l" {
+                break;
+            }
+            assert_eq!(prefix, "m");
+        }
+        assert_eq!(xml, TARGET_XML);
+
+        // Reads past the end are empty
+        let query = format!(
+            "Xfer:features:read:target.xml:{:x},40",
+            TARGET_XML.len() + 5
+        );
+        assert_eq!(handle_query(&query), "l");
+        assert_eq!(handle_query("Xfer:features:read:target.xml:zz"), "E00");
+    }
+}
@@ -0,0 +1,736 @@
+/**
+ * debugger.rs
+ *
+ * The debugger provides a command line interface for debugging Oxidgb and Gameboy
+ *  games.
+**/
+mod breakpoint;
+pub mod gdb;
+
+use std::collections::VecDeque;
+use std::env;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::PathBuf;
+
+use rustyline::error::ReadlineError;
+use rustyline::Editor;
+
+use oxidgb_core::cpu::bus::GameboyBus;
+use oxidgb_core::cpu::disasm::disassemble;
+use oxidgb_core::cpu::regs::Registers;
+use oxidgb_core::cpu::GameboyDebugger;
+use oxidgb_core::cpu::CPU;
+use oxidgb_core::search::Filter;
+use oxidgb_core::search::RamSearch;
+use oxidgb_core::search::SearchSize;
+use oxidgb_core::symbols::SymbolTable;
+use oxidgb_core::watchpoint::Watchpoint;
+use oxidgb_core::watchpoint::WatchpointHit;
+
+use oxidgb_frontend_common::trace::Tracer;
+
+use debugger::breakpoint::Breakpoint;
+use debugger::gdb::GdbStub;
+
+/// Registers which can be read and written by name.
+pub const REGISTER_NAMES: [&str; 14] = [
+    "a", "f", "b", "c", "d", "e", "h", "l", "af", "bc", "de", "hl", "sp", "pc",
+];
+
+/// Names of the IO registers, for display.
+const IO_REGISTERS: [(u16, &str); 43] = [
+    (0xFF00, "P1"),
+    (0xFF01, "SB"),
+    (0xFF02, "SC"),
+    (0xFF04, "DIV"),
+    (0xFF05, "TIMA"),
+    (0xFF06, "TMA"),
+    (0xFF07, "TAC"),
+    (0xFF0F, "IF"),
+    (0xFF10, "NR10"),
+    (0xFF11, "NR11"),
+    (0xFF12, "NR12"),
+    (0xFF13, "NR13"),
+    (0xFF14, "NR14"),
+    (0xFF16, "NR21"),
+    (0xFF17, "NR22"),
+    (0xFF18, "NR23"),
+    (0xFF19, "NR24"),
+    (0xFF1A, "NR30"),
+    (0xFF1B, "NR31"),
+    (0xFF1C, "NR32"),
+    (0xFF1D, "NR33"),
+    (0xFF1E, "NR34"),
+    (0xFF20, "NR41"),
+    (0xFF21, "NR42"),
+    (0xFF22, "NR43"),
+    (0xFF23, "NR44"),
+    (0xFF24, "NR50"),
+    (0xFF25, "NR51"),
+    (0xFF26, "NR52"),
+    (0xFF40, "LCDC"),
+    (0xFF41, "STAT"),
+    (0xFF42, "SCY"),
+    (0xFF43, "SCX"),
+    (0xFF44, "LY"),
+    (0xFF45, "LYC"),
+    (0xFF46, "DMA"),
+    (0xFF47, "BGP"),
+    (0xFF48, "OBP0"),
+    (0xFF49, "OBP1"),
+    (0xFF4A, "WY"),
+    (0xFF4B, "WX"),
+    (0xFF4D, "KEY1"),
+    (0xFFFF, "IE"),
+];
+
+/// How many previously executed instructions are shown in disassembly.
+const HISTORY_LENGTH: usize = 4;
+
+/// How many RAM search results are listed before they are cut off.
+const SEARCH_RESULTS_SHOWN: usize = 32;
+
+const HELP: &str = "\
+Commands (numbers are in hex, and addresses can also be labels or BANK:ADDR):
+  <enter>, s, step          Executes a single instruction
+  n, next                   Steps over calls
+  finish, out               Runs until the current function returns
+  r, run, c, continue       Runs until a breakpoint is hit
+  u, until ADDR             Runs until ADDR is reached
+  d, disasm [ADDR] [COUNT]  Disassembles around PC, or from ADDR
+  i, regs                   Shows the CPU registers
+  io                        Shows the IO registers
+  set REG VALUE             Sets a register (e.g. set hl C000)
+  mem ADDR                  Shows a byte of memory
+  x, hexdump ADDR [LENGTH]  Shows a range of memory
+  w, write ADDR VALUE...    Writes bytes to memory
+  break ADDR [if COND]      Toggles a breakpoint (in the label's or BANK's bank,
+                             if given), optionally only breaking
+                             when COND holds (e.g. break 0150 if a==3)
+  breaks                    Lists breakpoints
+  delete [ADDR]             Removes a breakpoint, or all of them
+  watch ADDR[-END] [rwx]    Breaks after ADDR is read (r), written (w, the
+                             default) or executed (x)
+  watches                   Lists watchpoints
+  unwatch [ADDR]            Removes watchpoints covering ADDR, or all of them
+  search new [8|16]         Starts a RAM search for 8 or 16 bit values
+  search FILTER             Keeps values which are =, >, or < a number
+                             (e.g. search < 3), or are changed, unchanged,
+                             increased or decreased since the last search
+  search list               Shows the values which are left
+  search cheat ADDR VALUE   Shows GameShark codes setting a result to VALUE
+  cheat CODE                Enables a Game Genie or GameShark code
+  cheats                    Lists enabled cheats
+  uncheat                   Disables all cheats
+  q, quit                   Closes the emulator";
+
+/// Parses a hexadecimal number, optionally prefixed with `$` or `0x`.
+pub fn parse_number(text: &str) -> Result<u16, String> {
+    let digits = text.trim_start_matches('$').trim_start_matches("0x");
+    u16::from_str_radix(digits, 16).map_err(|_| format!("Invalid number: {}", text))
+}
+
+/// Returns the value of a register by name.
+pub fn get_register(regs: &Registers, name: &str) -> Option<u16> {
+    Some(match name {
+        "a" => regs.a as u16,
+        "f" => regs.f as u16,
+        "b" => regs.b as u16,
+        "c" => regs.c as u16,
+        "d" => regs.d as u16,
+        "e" => regs.e as u16,
+        "h" => regs.h as u16,
+        "l" => regs.l as u16,
+        "af" => regs.get_af(),
+        "bc" => regs.get_bc(),
+        "de" => regs.get_de(),
+        "hl" => regs.get_hl(),
+        "sp" => regs.sp,
+        "pc" => regs.pc,
+        _ => return None,
+    })
+}
+
+/// Sets a register by name. Returns false if there isn't a register by
+///  that name.
+fn set_register(regs: &mut Registers, name: &str, value: u16) -> bool {
+    match name {
+        "a" => regs.a = value as u8,
+        "f" => regs.f = value as u8 & 0xF0,
+        "b" => regs.b = value as u8,
+        "c" => regs.c = value as u8,
+        "d" => regs.d = value as u8,
+        "e" => regs.e = value as u8,
+        "h" => regs.h = value as u8,
+        "l" => regs.l = value as u8,
+        "af" => regs.set_af(value),
+        "bc" => regs.set_bc(value),
+        "de" => regs.set_de(value),
+        "hl" => regs.set_hl(value),
+        "sp" => regs.sp = value,
+        "pc" => regs.pc = value,
+        _ => return false,
+    }
+    true
+}
+
+/// Returns the next argument, or a error if there isn't one.
+fn next_arg<'a, I: Iterator<Item = &'a str>>(args: &mut I) -> Result<&'a str, String> {
+    args.next().ok_or_else(|| "Requires an argument.".to_string())
+}
+
+pub struct CommandLineDebugger {
+    pub enabled: bool,
+    pub shutdown: bool,
+    pub breakpoints: Vec<Breakpoint>,
+    /// Logs each instruction as it is executed
+    pub tracer: Option<Tracer<BufWriter<File>>>,
+    /// Takes over from the command line when debugging with GDB
+    pub gdb: Option<GdbStub>,
+    /// Labels to show in place of addresses
+    pub symbols: SymbolTable,
+    /// Breaks when this address is reached (for step over/run until)
+    temporary_breakpoint: Option<u16>,
+    /// The current RAM search, if one has been started
+    search: Option<RamSearch>,
+    /// Breaks once the stack pointer rises above this (for step out)
+    step_out_sp: Option<u16>,
+    /// Recently executed instructions, oldest first
+    history: VecDeque<u16>,
+    editor: Editor<()>,
+    history_file: Option<PathBuf>,
+}
+
+impl CommandLineDebugger {
+    /// Shows the state of the CPU.
+    fn print_registers(cpu: &CPU) {
+        let regs = &cpu.regs;
+        println!(
+            "af = {:04X}  bc = {:04X}  de = {:04X}  hl = {:04X}  sp = {:04X}  pc = {:04X}",
+            regs.get_af(),
+            regs.get_bc(),
+            regs.get_de(),
+            regs.get_hl(),
+            regs.sp,
+            regs.pc
+        );
+        println!(
+            "flags = {}{}{}{}  ime = {}  halted = {}  stopped = {}",
+            if regs.get_flag_z() { 'Z' } else { '-' },
+            if regs.get_flag_n() { 'N' } else { '-' },
+            if regs.get_flag_h() { 'H' } else { '-' },
+            if regs.get_flag_c() { 'C' } else { '-' },
+            cpu.interrupts_enabled as u8,
+            cpu.halted as u8,
+            cpu.stopped as u8
+        );
+    }
+
+    fn print_io_registers(cpu: &CPU) {
+        for row in IO_REGISTERS.chunks(4) {
+            for &(address, name) in row {
+                print!("{:>4} ({:04X}) = {:02X}    ", name, address, cpu.mem.read(address));
+            }
+            println!();
+        }
+    }
+
+    /// Parses a address, which can be a label, `BANK:ADDR` or a plain
+    ///  address. Returns the bank too, if one was given.
+    fn parse_location(&self, text: &str) -> Result<(Option<u8>, u16), String> {
+        if let Some(symbol) = self.symbols.find(text) {
+            return Ok((Some(symbol.bank), symbol.address));
+        }
+
+        match text.find(':') {
+            Some(position) => {
+                let bank = parse_number(&text[..position])?;
+                if bank > 0xFF {
+                    return Err(format!("Invalid bank: {}", &text[..position]));
+                }
+                Ok((Some(bank as u8), parse_number(&text[position + 1..])?))
+            }
+            None => Ok((None, parse_number(text)?)),
+        }
+    }
+
+    /// Parses a address, ignoring any bank.
+    fn parse_address(&self, text: &str) -> Result<u16, String> {
+        self.parse_location(text).map(|(_, address)| address)
+    }
+
+    /// Formats a address as `BANK:ADDR`, using the bank currently mapped in.
+    fn format_location(&self, cpu: &CPU, address: u16) -> String {
+        format!("{:02X}:{:04X}", cpu.mem.get_bank(address), address)
+    }
+
+    /// Formats a line of disassembly, with labels in place of addresses.
+    fn format_instruction(&self, cpu: &CPU, address: u16) -> String {
+        let instruction = disassemble(&cpu.mem, address);
+        format!(
+            "{}: {}",
+            self.format_location(cpu, address),
+            self.symbols.label_instruction(&cpu.mem, &instruction)
+        )
+    }
+
+    /// Shows the label at a address, if there is one.
+    fn print_label(&self, cpu: &CPU, address: u16) {
+        if let Some(label) = self.symbols.get_mapped_label(&cpu.mem, address) {
+            println!("{}:", label);
+        }
+    }
+
+    /// Disassembles `count` instructions from `address`.
+    fn print_disassembly(&self, cpu: &CPU, mut address: u16, count: usize) {
+        for _ in 0..count {
+            self.print_label(cpu, address);
+            let marker = if address == cpu.regs.pc { ">" } else { " " };
+            println!("{} {}", marker, self.format_instruction(cpu, address));
+            address = disassemble(&cpu.mem, address).get_next_address();
+        }
+    }
+
+    /// Shows recently executed instructions, followed by the upcoming ones.
+    fn print_context(&self, cpu: &CPU) {
+        for &address in &self.history {
+            println!("  {}", self.format_instruction(cpu, address));
+        }
+        self.print_disassembly(cpu, cpu.regs.pc, HISTORY_LENGTH + 1);
+    }
+
+    /// Describes a breakpoint, along with the label at its address.
+    fn describe_breakpoint(&self, breakpoint: &Breakpoint) -> String {
+        let label = breakpoint
+            .bank
+            .and_then(|bank| self.symbols.get_label(bank, breakpoint.address));
+
+        match label {
+            Some(label) => format!("{} ({})", breakpoint, label),
+            None => breakpoint.to_string(),
+        }
+    }
+
+    fn print_hexdump(cpu: &CPU, start: u16, length: u32) {
+        for line_start in (start as u32..start as u32 + length).step_by(16) {
+            let line_end = (line_start + 16).min(start as u32 + length).min(0x10000);
+            if line_start >= line_end {
+                break;
+            }
+
+            let bytes: Vec<u8> = (line_start..line_end)
+                .map(|address| cpu.mem.read(address as u16))
+                .collect();
+
+            let hex: Vec<String> = bytes.iter().map(|value| format!("{:02X}", value)).collect();
+            let ascii: String = bytes
+                .iter()
+                .map(|&value| {
+                    if (0x20..0x7F).contains(&value) {
+                        value as char
+                    } else {
+                        '.'
+                    }
+                })
+                .collect();
+
+            println!("{:04X}: {:<47}  |{}|", line_start, hex.join(" "), ascii);
+        }
+    }
+
+    /// Runs a RAM search subcommand.
+    fn search<'a, I: Iterator<Item = &'a str>>(
+        &mut self,
+        cpu: &CPU,
+        args: &mut I,
+    ) -> Result<(), String> {
+        let command = next_arg(args)?;
+
+        if command == "new" {
+            let size = match args.next() {
+                None | Some("8") => SearchSize::Byte,
+                Some("16") => SearchSize::Short,
+                Some(size) => return Err(format!("Invalid size: {}", size)),
+            };
+            let search = RamSearch::build(&cpu.mem, size);
+            println!("Searching {} values.", search.len());
+            self.search = Some(search);
+            return Ok(());
+        }
+
+        let filter = match command {
+            "=" | "==" => Filter::Equal(parse_number(next_arg(args)?)?),
+            ">" => Filter::Greater(parse_number(next_arg(args)?)?),
+            "<" => Filter::Less(parse_number(next_arg(args)?)?),
+            "changed" => Filter::Changed,
+            "unchanged" => Filter::Unchanged,
+            "increased" => Filter::Increased,
+            "decreased" => Filter::Decreased,
+            "list" | "cheat" => {
+                let search = match self.search {
+                    Some(ref search) => search,
+                    None => return Err("No search has been started.".to_string()),
+                };
+                if command == "list" {
+                    self.print_search_results(cpu, search);
+                    return Ok(());
+                }
+
+                let (bank, address) = self.parse_location(next_arg(args)?)?;
+                let value = parse_number(next_arg(args)?)?;
+                if search.get_size() == SearchSize::Byte && value > 0xFF {
+                    return Err(format!("Invalid value: {:X}", value));
+                }
+
+                let result = search
+                    .get_results(&cpu.mem)
+                    .find(|result| {
+                        result.location.address == address
+                            && bank.is_none_or(|bank| result.location.bank == bank)
+                    })
+                    .ok_or_else(|| "Not a search result.".to_string())?;
+                println!("{}", result.to_gameshark(value).join(" "));
+                return Ok(());
+            }
+            _ => return Err(format!("Unknown search filter: {}", command)),
+        };
+
+        match self.search {
+            Some(ref mut search) => println!("{} values left.", search.filter(&cpu.mem, filter)),
+            None => return Err("No search has been started.".to_string()),
+        }
+        Ok(())
+    }
+
+    /// Shows the first few values left in a RAM search.
+    fn print_search_results(&self, cpu: &CPU, search: &RamSearch) {
+        let width = match search.get_size() {
+            SearchSize::Byte => 2,
+            SearchSize::Short => 4,
+        };
+
+        for result in search.get_results(&cpu.mem).take(SEARCH_RESULTS_SHOWN) {
+            let label = self
+                .symbols
+                .get_label(result.location.bank, result.location.address)
+                .map(|label| format!(" ({})", label))
+                .unwrap_or_default();
+            println!(
+                "{}{} = {:0width$X} (was {:0width$X})",
+                result.location,
+                label,
+                result.value,
+                result.previous,
+                width = width
+            );
+        }
+
+        if search.len() > SEARCH_RESULTS_SHOWN {
+            println!("... and {} more", search.len() - SEARCH_RESULTS_SHOWN);
+        }
+    }
+
+    /// Runs a command. Returns true if emulation should continue.
+    fn execute(&mut self, cpu: &mut CPU, line: &str) -> Result<bool, String> {
+        let mut args = line.split_whitespace();
+
+        match args.next() {
+            None | Some("s") | Some("step") => return Ok(true),
+            Some("n") | Some("next") => {
+                let instruction = disassemble(&cpu.mem, cpu.regs.pc);
+                if instruction.is_call() {
+                    self.temporary_breakpoint = Some(instruction.get_next_address());
+                    self.enabled = false;
+                }
+                return Ok(true);
+            }
+            Some("finish") | Some("out") => {
+                self.step_out_sp = Some(cpu.regs.sp);
+                self.enabled = false;
+                return Ok(true);
+            }
+            Some("r") | Some("run") | Some("c") | Some("continue") => {
+                println!("Running...");
+                self.enabled = false;
+                return Ok(true);
+            }
+            Some("u") | Some("until") => {
+                self.temporary_breakpoint = Some(self.parse_address(next_arg(&mut args)?)?);
+                self.enabled = false;
+                return Ok(true);
+            }
+            Some("d") | Some("disasm") => match args.next() {
+                Some(address) => {
+                    let count = match args.next() {
+                        Some(count) => parse_number(count)? as usize,
+                        None => 10,
+                    };
+                    self.print_disassembly(cpu, self.parse_address(address)?, count);
+                }
+                None => self.print_context(cpu),
+            },
+            Some("i") | Some("regs") => Self::print_registers(cpu),
+            Some("io") => Self::print_io_registers(cpu),
+            Some("set") => {
+                let name = next_arg(&mut args)?.to_lowercase();
+                let value = self.parse_address(next_arg(&mut args)?)?;
+                if !set_register(&mut cpu.regs, &name, value) {
+                    return Err(format!("Unknown register: {}", name));
+                }
+            }
+            Some("mem") => {
+                let address = self.parse_address(next_arg(&mut args)?)?;
+                println!("{:04X} = {:02X}", address, cpu.mem.read(address));
+            }
+            Some("x") | Some("hexdump") | Some("mems") => {
+                let address = self.parse_address(next_arg(&mut args)?)?;
+                let length = match args.next() {
+                    Some(length) => parse_number(length)? as u32,
+                    None => 0x40,
+                };
+                Self::print_hexdump(cpu, address, length);
+            }
+            Some("w") | Some("write") => {
+                let mut address = self.parse_address(next_arg(&mut args)?)?;
+                let values = args.map(parse_number).collect::<Result<Vec<_>, _>>()?;
+                if values.is_empty() {
+                    return Err("Requires a value.".to_string());
+                }
+
+                for value in values {
+                    cpu.mem.write(address, value as u8);
+                    address = address.wrapping_add(1);
+                }
+            }
+            Some("break") => {
+                let (bank, address) = self.parse_location(next_arg(&mut args)?)?;
+                let condition = match args.next() {
+                    Some("if") => Some(args.collect::<Vec<_>>().join(" ")),
+                    Some(_) => return Err("Expected \"if\" before condition.".to_string()),
+                    None => None,
+                };
+
+                let existing = self
+                    .breakpoints
+                    .iter()
+                    .position(|b| b.address == address && b.bank == bank);
+                match (existing, condition) {
+                    (Some(position), None) => {
+                        let breakpoint = self.breakpoints.remove(position);
+                        println!("Removing breakpoint: {}", self.describe_breakpoint(&breakpoint));
+                    }
+                    (existing, condition) => {
+                        let breakpoint = Breakpoint::build(address, bank, condition.as_deref())?;
+                        println!("Adding breakpoint: {}", self.describe_breakpoint(&breakpoint));
+                        match existing {
+                            Some(position) => self.breakpoints[position] = breakpoint,
+                            None => self.breakpoints.push(breakpoint),
+                        }
+                    }
+                }
+            }
+            Some("breaks") => {
+                for breakpoint in &self.breakpoints {
+                    println!("{}", self.describe_breakpoint(breakpoint));
+                }
+            }
+            Some("delete") => match args.next() {
+                Some(address) => {
+                    let address = self.parse_address(address)?;
+                    self.breakpoints.retain(|b| b.address != address);
+                }
+                None => self.breakpoints.clear(),
+            },
+            Some("watch") => {
+                let range = next_arg(&mut args)?;
+                let (start, end) = match range.find('-') {
+                    Some(position) => (
+                        self.parse_address(&range[..position])?,
+                        self.parse_address(&range[position + 1..])?,
+                    ),
+                    None => {
+                        let address = self.parse_address(range)?;
+                        (address, address)
+                    }
+                };
+                if end < start {
+                    return Err("The end of the range is before the start.".to_string());
+                }
+
+                let flags = args.next().unwrap_or("w").to_lowercase();
+                if flags.is_empty() || flags.chars().any(|c| !"rwx".contains(c)) {
+                    return Err(format!("Unknown access type: {}", flags));
+                }
+
+                let watchpoint = Watchpoint {
+                    start,
+                    end,
+                    read: flags.contains('r'),
+                    write: flags.contains('w'),
+                    execute: flags.contains('x'),
+                };
+
+                if !cpu.mem.watchpoints.add(watchpoint) {
+                    return Err("Too many watchpoints.".to_string());
+                }
+                println!("Adding watchpoint: {}", watchpoint);
+            }
+            Some("watches") => {
+                for watchpoint in cpu.mem.watchpoints.iter() {
+                    println!("{}", watchpoint);
+                }
+            }
+            Some("unwatch") => match args.next() {
+                Some(address) => cpu.mem.watchpoints.remove(self.parse_address(address)?),
+                None => cpu.mem.watchpoints.clear(),
+            },
+            Some("search") => self.search(cpu, &mut args)?,
+            Some("cheat") => {
+                let code = args.collect::<Vec<_>>().join(" ");
+                if code.is_empty() {
+                    return Err("Requires a code.".to_string());
+                }
+                cpu.mem.rom.cheats.add(&code).map_err(|e| e.to_string())?;
+            }
+            Some("cheats") => {
+                for cheat in cpu.mem.rom.cheats.iter() {
+                    println!("{}", cheat);
+                }
+            }
+            Some("uncheat") => cpu.mem.rom.cheats.clear(),
+            Some("h") | Some("help") => println!("{}", HELP),
+            Some("q") | Some("quit") => {
+                println!("Closing...");
+                self.shutdown = true;
+                self.enabled = false;
+                return Ok(true);
+            }
+            Some(_) => return Err("Unknown command.".to_string()),
+        }
+
+        Ok(false)
+    }
+
+    pub fn build() -> CommandLineDebugger {
+        let mut editor = Editor::<()>::new();
+
+        let history_file =
+            env::var_os("HOME").map(|home| PathBuf::from(home).join(".oxidgb_history"));
+        if let Some(ref path) = history_file {
+            // There won't be a history file the first time
+            let _ = editor.load_history(path);
+        }
+
+        CommandLineDebugger {
+            enabled: true,
+            shutdown: false,
+            breakpoints: Vec::new(),
+            tracer: None,
+            gdb: None,
+            symbols: SymbolTable::default(),
+            search: None,
+            temporary_breakpoint: None,
+            step_out_sp: None,
+            history: VecDeque::with_capacity(HISTORY_LENGTH + 1),
+            editor,
+            history_file,
+        }
+    }
+}
+
+impl<'a> GameboyDebugger<'a> for CommandLineDebugger {
+    fn debug(&mut self, cpu: &mut CPU<'a>) {
+        if let Some(ref mut gdb) = self.gdb {
+            gdb.debug(cpu);
+            self.shutdown |= gdb.shutdown;
+        } else if !self.enabled && !self.shutdown {
+            let pc = cpu.regs.pc;
+
+            if let Some(breakpoint) = self.breakpoints.iter().find(|b| b.is_hit(cpu)) {
+                println!("Hit breakpoint: {}", self.describe_breakpoint(breakpoint));
+                self.enabled = true;
+            } else if self.temporary_breakpoint == Some(pc)
+                || self.step_out_sp.is_some_and(|sp| cpu.regs.sp > sp)
+            {
+                self.enabled = true;
+            }
+        }
+
+        if self.enabled {
+            self.temporary_breakpoint = None;
+            self.step_out_sp = None;
+
+            // So the trace can be inspected while paused
+            if let Some(ref mut tracer) = self.tracer {
+                let _ = tracer.flush();
+            }
+
+            self.print_label(cpu, cpu.regs.pc);
+            println!("{}", self.format_instruction(cpu, cpu.regs.pc));
+
+            loop {
+                match self.editor.readline("> ") {
+                    Ok(line) => {
+                        if !line.trim().is_empty() {
+                            self.editor.add_history_entry(&line);
+                            if let Some(ref path) = self.history_file {
+                                let _ = self.editor.save_history(path);
+                            }
+                        }
+
+                        match self.execute(cpu, &line) {
+                            Ok(true) => break,
+                            Ok(false) => {}
+                            Err(message) => println!("{}", message),
+                        }
+                    }
+                    Err(ReadlineError::Interrupted) | Err(ReadlineError::Eof) => {
+                        println!("Closing...");
+                        self.shutdown = true;
+                        self.enabled = false;
+                        break;
+                    }
+                    Err(err) => {
+                        println!("Error: {:?}", err);
+                        self.shutdown = true;
+                        self.enabled = false;
+                        break;
+                    }
+                }
+            }
+        }
+
+        if !self.shutdown {
+            if let Some(ref mut tracer) = self.tracer {
+                if let Err(e) = tracer.trace(cpu) {
+                    println!("Failed to write trace, stopping: {}", e);
+                    self.tracer = None;
+                }
+            }
+        }
+
+        if cpu.is_executing() {
+            self.history.push_back(cpu.regs.pc);
+            if self.history.len() > HISTORY_LENGTH {
+                self.history.pop_front();
+            }
+        }
+    }
+
+    fn watchpoint(&mut self, _cpu: &mut CPU<'a>, hit: WatchpointHit) {
+        if self.shutdown {
+            return;
+        }
+
+        println!(
+            "Hit watchpoint: {} {:04X} = {:02X} at {:04X}",
+            hit.access, hit.address, hit.value, hit.pc
+        );
+
+        match self.gdb {
+            Some(ref mut gdb) => gdb.interrupt(),
+            None => self.enabled = true,
+        }
+    }
+}
@@ -0,0 +1,293 @@
+/**
+ * display.rs
+ *
+ * A window which shows a RGB image, scaled to fill it. Used both for the
+ *  LCD and for debug views. Each window has its own OpenGL context, which is
+ *  made current before anything is drawn.
+**/
+use std::ffi::CStr;
+use std::mem;
+use std::mem::MaybeUninit;
+use std::ptr;
+
+use glutin::dpi::LogicalSize;
+use glutin::dpi::PhysicalSize;
+use glutin::event_loop::EventLoopWindowTarget;
+#[cfg(target_family = "windows")]
+use glutin::platform::windows::WindowBuilderExtWindows;
+use glutin::window::Window;
+use glutin::window::WindowBuilder;
+use glutin::PossiblyCurrent;
+use glutin::WindowedContext;
+
+pub struct Display {
+    /// Only taken while the context is being made current
+    context: Option<WindowedContext<PossiblyCurrent>>,
+    texture: gl::types::GLuint,
+    ebo: gl::types::GLuint,
+    width: usize,
+    height: usize,
+}
+
+impl Display {
+    /// Makes this window's context the current one, so it can be drawn to.
+    fn make_current(&mut self) -> &WindowedContext<PossiblyCurrent> {
+        let context = self.context.take().expect("Display has no context");
+        let context = match unsafe { context.make_current() } {
+            Ok(context) => context,
+            Err((context, e)) => {
+                error!("Failed to set context as current: {:?}", e);
+                context
+            }
+        };
+        self.context.insert(context)
+    }
+
+    pub fn window(&self) -> &Window {
+        self.context.as_ref().expect("Display has no context").window()
+    }
+
+    pub fn resize(&mut self, size: PhysicalSize<u32>) {
+        self.make_current().resize(size);
+    }
+
+    /// Draws a image of the size the display was built with.
+    pub fn draw(&mut self, pixels: &[u8]) {
+        self.make_current();
+
+        unsafe {
+            gl::Clear(gl::COLOR_BUFFER_BIT);
+
+            gl::ActiveTexture(gl::TEXTURE0);
+            gl::BindTexture(gl::TEXTURE_2D, self.texture);
+            gl::TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                gl::RGB as gl::types::GLint,
+                self.width as gl::types::GLsizei,
+                self.height as gl::types::GLsizei,
+                0,
+                gl::RGB,
+                gl::UNSIGNED_BYTE,
+                pixels.as_ptr() as *const _,
+            );
+
+            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.ebo);
+            gl::DrawElements(gl::TRIANGLES, 6, gl::UNSIGNED_INT, std::ptr::null::<_>());
+        }
+    }
+
+    /// Shows whatever has been drawn.
+    pub fn present(&mut self) {
+        self.make_current()
+            .swap_buffers()
+            .expect("Failed to swap buffers");
+    }
+
+    /// Opens a window for displaying images of the specified size, scaled up
+    ///  by `scale`.
+    pub fn build<T>(
+        events_loop: &EventLoopWindowTarget<T>,
+        title: &str,
+        width: usize,
+        height: usize,
+        scale: f64,
+        vsync: bool,
+    ) -> Display {
+        let window = WindowBuilder::new().with_title(title).with_inner_size(LogicalSize::new(
+            width as f64 * scale,
+            height as f64 * scale,
+        ));
+
+        // Work around COM support issue with audio
+        #[cfg(target_family = "windows")]
+        let window = window.with_drag_and_drop(false);
+
+        let context = glutin::ContextBuilder::new()
+            .with_vsync(vsync)
+            .build_windowed(window, events_loop)
+            .expect("Failed to build context");
+
+        let context = unsafe {
+            context
+                .make_current()
+                .expect("Failed to set context as current")
+        };
+
+        unsafe {
+            gl::load_with(|symbol| context.get_proc_address(symbol) as *const _);
+            gl::ClearColor(0.0, 0.0, 0.0, 1.0);
+        }
+
+        // Prepare OpenGL
+        let version = unsafe {
+            CStr::from_ptr(gl::GetString(gl::VERSION) as *const _)
+                .to_str()
+                .expect("Failed to convert to string")
+        };
+
+        info!("OpenGL version: {}", version);
+
+        let mut tex = MaybeUninit::uninit();
+        let mut ebo = MaybeUninit::uninit();
+
+        unsafe {
+            // Generate shaders
+            // Stolen from https://github.com/tomaka/glutin/blob/master/examples/support/mod.rs &
+            //             https://open.gl/content/code/c3_multitexture.txt
+            let vs = gl::CreateShader(gl::VERTEX_SHADER);
+            gl::ShaderSource(vs, 1, [VS_SRC.as_ptr() as *const _].as_ptr(), ptr::null());
+            gl::CompileShader(vs);
+
+            let fs = gl::CreateShader(gl::FRAGMENT_SHADER);
+            gl::ShaderSource(fs, 1, [FS_SRC.as_ptr() as *const _].as_ptr(), ptr::null());
+            gl::CompileShader(fs);
+
+            let program = gl::CreateProgram();
+            gl::AttachShader(program, vs);
+            gl::AttachShader(program, fs);
+            gl::BindFragDataLocation(program, 0, b"outColor\0".as_ptr() as *const _);
+            gl::LinkProgram(program);
+            gl::UseProgram(program);
+
+            gl::GenBuffers(1, ebo.as_mut_ptr());
+            gl::BindBuffer(gl::ARRAY_BUFFER, ebo.assume_init());
+            gl::BufferData(
+                gl::ARRAY_BUFFER,
+                (ELEMENTS.len() * mem::size_of::<u32>()) as gl::types::GLsizeiptr,
+                ELEMENTS.as_ptr() as *const _,
+                gl::STATIC_DRAW,
+            );
+
+            let mut vb = MaybeUninit::uninit();
+            gl::GenBuffers(1, vb.as_mut_ptr());
+            gl::BindBuffer(gl::ARRAY_BUFFER, vb.assume_init());
+            gl::BufferData(
+                gl::ARRAY_BUFFER,
+                (VERTEX_DATA.len() * mem::size_of::<f32>()) as gl::types::GLsizeiptr,
+                VERTEX_DATA.as_ptr() as *const _,
+                gl::STATIC_DRAW,
+            );
+
+            let mut vao = MaybeUninit::uninit();
+            gl::GenVertexArrays(1, vao.as_mut_ptr());
+            gl::BindVertexArray(vao.assume_init());
+
+            let pos_attrib = gl::GetAttribLocation(program, b"position\0".as_ptr() as *const _);
+            let color_attrib = gl::GetAttribLocation(program, b"color\0".as_ptr() as *const _);
+            let tex_attrib = gl::GetAttribLocation(program, b"texcoord\0".as_ptr() as *const _);
+            gl::VertexAttribPointer(
+                pos_attrib as gl::types::GLuint,
+                2,
+                gl::FLOAT,
+                0,
+                7 * mem::size_of::<f32>() as gl::types::GLsizei,
+                ptr::null(),
+            );
+            gl::VertexAttribPointer(
+                color_attrib as gl::types::GLuint,
+                3,
+                gl::FLOAT,
+                0,
+                7 * mem::size_of::<f32>() as gl::types::GLsizei,
+                (2 * mem::size_of::<f32>()) as *const () as *const _,
+            );
+            gl::VertexAttribPointer(
+                tex_attrib as gl::types::GLuint,
+                2,
+                gl::FLOAT,
+                0,
+                7 * mem::size_of::<f32>() as gl::types::GLsizei,
+                (5 * mem::size_of::<f32>()) as *const () as *const _,
+            );
+            gl::EnableVertexAttribArray(pos_attrib as gl::types::GLuint);
+            gl::EnableVertexAttribArray(color_attrib as gl::types::GLuint);
+            gl::EnableVertexAttribArray(tex_attrib as gl::types::GLuint);
+
+            // Generate texture (for us to dump into)
+            gl::GenTextures(1, tex.as_mut_ptr());
+            gl::ActiveTexture(gl::TEXTURE0);
+            gl::BindTexture(gl::TEXTURE_2D, tex.assume_init());
+
+            gl::Uniform1i(
+                gl::GetUniformLocation(program, b"tex\0".as_ptr() as *const _),
+                0,
+            );
+            gl::TexParameteri(
+                gl::TEXTURE_2D,
+                gl::TEXTURE_WRAP_S,
+                gl::CLAMP_TO_EDGE as gl::types::GLint,
+            );
+            gl::TexParameteri(
+                gl::TEXTURE_2D,
+                gl::TEXTURE_WRAP_T,
+                gl::CLAMP_TO_EDGE as gl::types::GLint,
+            );
+            gl::TexParameteri(
+                gl::TEXTURE_2D,
+                gl::TEXTURE_MIN_FILTER,
+                gl::NEAREST as gl::types::GLint,
+            );
+            gl::TexParameteri(
+                gl::TEXTURE_2D,
+                gl::TEXTURE_MAG_FILTER,
+                gl::NEAREST as gl::types::GLint,
+            );
+        }
+
+        unsafe {
+            Display {
+                context: Some(context),
+                texture: tex.assume_init(),
+                ebo: ebo.assume_init(),
+                width,
+                height,
+            }
+        }
+    }
+}
+
+// OpenGL resources
+static VERTEX_DATA: [f32; 28] = [
+    // X    Y    R    G    B    U    V
+    -1.0, 1.0, 1.0, 1.0, 1.0, 0.0, 0.0, // Top-left
+    1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 0.0, // Top-right
+    1.0, -1.0, 1.0, 1.0, 1.0, 1.0, 1.0, // Bottom-right
+    -1.0, -1.0, 1.0, 1.0, 1.0, 0.0, 1.0, // Bottom-left
+];
+
+static ELEMENTS: [u32; 6] = [0, 1, 2, 2, 3, 0];
+
+const VS_SRC: &[u8] = b"
+    #version 150 core
+
+    in vec2 position;
+    in vec3 color;
+    in vec2 texcoord;
+
+    out vec3 Color;
+    out vec2 Texcoord;
+
+    void main()
+    {
+        Color = color;
+        Texcoord = texcoord;
+        gl_Position = vec4(position, 0.0, 1.0);
+    }
+\0";
+
+const FS_SRC: &[u8] = b"
+    #version 150 core
+
+    in vec3 Color;
+    in vec2 Texcoord;
+
+    out vec4 outColor;
+
+    uniform sampler2D tex;
+
+    void main()
+    {
+        outColor = texture(tex, Texcoord) * vec4(Color, 1.0);
+    }
+\0";
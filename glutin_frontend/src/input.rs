@@ -0,0 +1,413 @@
+/**
+ * input.rs
+ *
+ * Maps keyboard keys and gamepad buttons to Gameboy buttons (and emulator
+ *  hotkeys). Bindings are read from `input.toml` in the user's config
+ *  directory, with anything left out of it keeping its default binding
+ *  (unless the key or button is bound to something else there):
+ *
+ *  stick_threshold = 0.5
+ *  turbo_period = 4
+ *
+ *  [keyboard]
+ *  a = "X"
+ *  turbo_a = "C"
+ *
+ *  [gamepad]
+ *  a = "East"
+ *  fast_forward = "RightTrigger2"
+**/
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use glutin::event::ElementState;
+use glutin::event::VirtualKeyCode;
+
+#[cfg(feature = "gamepad")]
+use gilrs::Axis;
+#[cfg(feature = "gamepad")]
+use gilrs::Button;
+#[cfg(feature = "gamepad")]
+use gilrs::EventType;
+#[cfg(feature = "gamepad")]
+use gilrs::Gilrs;
+
+use oxidgb_core::input::GameboyButton;
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Action {
+    Up,
+    Down,
+    Left,
+    Right,
+    A,
+    B,
+    Select,
+    Start,
+    /// Repeatedly presses A while held
+    TurboA,
+    TurboB,
+    FastForward,
+    Rewind,
+}
+
+impl Action {
+    fn get_button(&self) -> Option<GameboyButton> {
+        match *self {
+            Action::Up => Some(GameboyButton::UP),
+            Action::Down => Some(GameboyButton::DOWN),
+            Action::Left => Some(GameboyButton::LEFT),
+            Action::Right => Some(GameboyButton::RIGHT),
+            Action::A | Action::TurboA => Some(GameboyButton::A),
+            Action::B | Action::TurboB => Some(GameboyButton::B),
+            Action::Select => Some(GameboyButton::SELECT),
+            Action::Start => Some(GameboyButton::START),
+            Action::FastForward | Action::Rewind => None,
+        }
+    }
+
+    fn is_turbo(&self) -> bool {
+        *self == Action::TurboA || *self == Action::TurboB
+    }
+}
+
+/// What is holding down a action, so that everything held by a gamepad can
+///  be released when it is disconnected.
+#[derive(Copy, Clone, PartialEq)]
+enum Source {
+    Keyboard,
+    #[cfg(feature = "gamepad")]
+    Gamepad(usize),
+    /// The D-pad, as pushed by a gamepad's analog stick
+    #[cfg(feature = "gamepad")]
+    Stick(usize),
+}
+
+/// A table of bindings in the config file.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileBindings<T> {
+    up: Option<T>,
+    down: Option<T>,
+    left: Option<T>,
+    right: Option<T>,
+    a: Option<T>,
+    b: Option<T>,
+    select: Option<T>,
+    start: Option<T>,
+    turbo_a: Option<T>,
+    turbo_b: Option<T>,
+    fast_forward: Option<T>,
+    rewind: Option<T>,
+}
+
+impl<T> FileBindings<T> {
+    /// Returns the actions which have been bound.
+    fn into_bindings(self) -> impl Iterator<Item = (Action, T)> {
+        vec![
+            (Action::Up, self.up),
+            (Action::Down, self.down),
+            (Action::Left, self.left),
+            (Action::Right, self.right),
+            (Action::A, self.a),
+            (Action::B, self.b),
+            (Action::Select, self.select),
+            (Action::Start, self.start),
+            (Action::TurboA, self.turbo_a),
+            (Action::TurboB, self.turbo_b),
+            (Action::FastForward, self.fast_forward),
+            (Action::Rewind, self.rewind),
+        ]
+        .into_iter()
+        .filter_map(|(action, binding)| binding.map(|binding| (action, binding)))
+    }
+}
+
+impl<T: PartialEq> FileBindings<T> {
+    /// Overrides existing bindings with these. Anything else bound to the
+    ///  same key or button is unbound, so each key only does one thing.
+    fn apply_to(self, bindings: &mut HashMap<Action, T>) {
+        for (action, binding) in self.into_bindings() {
+            bindings.retain(|_, bound| *bound != binding);
+            bindings.insert(action, binding);
+        }
+    }
+}
+
+/// The contents of the config file, where anything missing is left as the
+///  default.
+#[derive(Deserialize)]
+struct ConfigFile {
+    keyboard: Option<FileBindings<VirtualKeyCode>>,
+    #[cfg(feature = "gamepad")]
+    gamepad: Option<FileBindings<Button>>,
+    stick_threshold: Option<f32>,
+    turbo_period: Option<u32>,
+}
+
+pub struct InputConfig {
+    pub keyboard: HashMap<Action, VirtualKeyCode>,
+    #[cfg(feature = "gamepad")]
+    pub gamepad: HashMap<Action, Button>,
+    /// How far a analog stick needs to be pushed to press the D-pad
+    pub stick_threshold: f32,
+    /// How many frames turbo buttons are pressed, and then released, for
+    pub turbo_period: u32,
+}
+
+impl InputConfig {
+    /// Returns where bindings are read from.
+    pub fn get_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("oxidgb").join("input.toml"))
+    }
+
+    /// Loads bindings from the config file, if there is one.
+    pub fn load() -> Result<InputConfig, String> {
+        let path = match InputConfig::get_path() {
+            Some(path) if path.exists() => path,
+            _ => return Ok(InputConfig::default()),
+        };
+
+        let text = std::fs::read_to_string(&path).map_err(|e| e.to_string())?;
+        let config = InputConfig::parse(&text)?;
+        info!("Loaded key bindings from {}", path.display());
+
+        Ok(config)
+    }
+
+    /// Parses the contents of a config file, on top of the default bindings.
+    fn parse(text: &str) -> Result<InputConfig, String> {
+        let mut config = InputConfig::default();
+        let file: ConfigFile = toml::from_str(text).map_err(|e| e.to_string())?;
+
+        if let Some(keyboard) = file.keyboard {
+            keyboard.apply_to(&mut config.keyboard);
+        }
+        #[cfg(feature = "gamepad")]
+        if let Some(gamepad) = file.gamepad {
+            gamepad.apply_to(&mut config.gamepad);
+        }
+        if let Some(threshold) = file.stick_threshold {
+            // Otherwise a centred stick would hold opposite directions at once
+            if !(threshold > 0.0 && threshold <= 1.0) {
+                return Err(format!("stick_threshold must be in (0, 1], not {}", threshold));
+            }
+            config.stick_threshold = threshold;
+        }
+        if let Some(period) = file.turbo_period {
+            config.turbo_period = period.max(1);
+        }
+
+        Ok(config)
+    }
+}
+
+impl Default for InputConfig {
+    fn default() -> InputConfig {
+        InputConfig {
+            keyboard: [
+                (Action::Up, VirtualKeyCode::Up),
+                (Action::Down, VirtualKeyCode::Down),
+                (Action::Left, VirtualKeyCode::Left),
+                (Action::Right, VirtualKeyCode::Right),
+                (Action::A, VirtualKeyCode::X),
+                (Action::B, VirtualKeyCode::Z),
+                (Action::Select, VirtualKeyCode::A),
+                (Action::Start, VirtualKeyCode::S),
+                (Action::TurboA, VirtualKeyCode::C),
+                (Action::TurboB, VirtualKeyCode::V),
+                (Action::FastForward, VirtualKeyCode::Tab),
+                (Action::Rewind, VirtualKeyCode::Back),
+            ]
+            .iter()
+            .cloned()
+            .collect(),
+            // Laid out like a SNES pad, so A is on the right
+            #[cfg(feature = "gamepad")]
+            gamepad: [
+                (Action::Up, Button::DPadUp),
+                (Action::Down, Button::DPadDown),
+                (Action::Left, Button::DPadLeft),
+                (Action::Right, Button::DPadRight),
+                (Action::A, Button::East),
+                (Action::B, Button::South),
+                (Action::Select, Button::Select),
+                (Action::Start, Button::Start),
+                (Action::TurboA, Button::North),
+                (Action::TurboB, Button::West),
+                (Action::FastForward, Button::RightTrigger2),
+                (Action::Rewind, Button::LeftTrigger2),
+            ]
+            .iter()
+            .cloned()
+            .collect(),
+            stick_threshold: 0.5,
+            turbo_period: 4,
+        }
+    }
+}
+
+pub struct Input {
+    config: InputConfig,
+    held: Vec<(Source, Action)>,
+    /// Counts frames, to time turbo buttons
+    frame: u32,
+    /// Not available if the platform's gamepad API couldn't be opened
+    #[cfg(feature = "gamepad")]
+    gilrs: Option<Gilrs>,
+}
+
+impl Input {
+    fn press(&mut self, source: Source, action: Action, pressed: bool) {
+        let entry = (source, action);
+        let position = self.held.iter().position(|&held| held == entry);
+
+        match (pressed, position) {
+            (true, None) => self.held.push(entry),
+            (false, Some(position)) => {
+                self.held.remove(position);
+            }
+            _ => {}
+        }
+    }
+
+    pub fn is_held(&self, action: Action) -> bool {
+        self.held.iter().any(|&(_, held)| held == action)
+    }
+
+    /// Handles a key being pressed or released.
+    pub fn handle_key(&mut self, key: VirtualKeyCode, state: ElementState) {
+        let bound = self.config.keyboard.iter().find(|&(_, &bound)| bound == key);
+        if let Some((&action, _)) = bound {
+            self.press(Source::Keyboard, action, state == ElementState::Pressed);
+        }
+    }
+
+    /// Handles any gamepads which have been connected, disconnected or used
+    ///  since this was last called.
+    #[cfg(feature = "gamepad")]
+    pub fn poll_gamepads(&mut self) {
+        let mut events = Vec::new();
+        if let Some(ref mut gilrs) = self.gilrs {
+            while let Some(event) = gilrs.next_event() {
+                events.push(event);
+            }
+        }
+
+        for event in events {
+            let id: usize = event.id.into();
+
+            match event.event {
+                EventType::Connected => {
+                    if let Some(ref gilrs) = self.gilrs {
+                        info!("Gamepad connected: {}", gilrs.gamepad(event.id).name());
+                    }
+                }
+                EventType::Disconnected => {
+                    info!("Gamepad disconnected");
+                    self.held.retain(|&(source, _)| {
+                        source != Source::Gamepad(id) && source != Source::Stick(id)
+                    });
+                }
+                EventType::ButtonPressed(button, _) | EventType::ButtonReleased(button, _) => {
+                    let pressed = matches!(event.event, EventType::ButtonPressed(..));
+                    let bound = self.config.gamepad.iter().find(|&(_, &bound)| bound == button);
+                    if let Some((&action, _)) = bound {
+                        self.press(Source::Gamepad(id), action, pressed);
+                    }
+                }
+                EventType::AxisChanged(Axis::LeftStickX, value, _) => {
+                    let threshold = self.config.stick_threshold;
+                    self.press(Source::Stick(id), Action::Left, value <= -threshold);
+                    self.press(Source::Stick(id), Action::Right, value >= threshold);
+                }
+                EventType::AxisChanged(Axis::LeftStickY, value, _) => {
+                    // Up is positive
+                    let threshold = self.config.stick_threshold;
+                    self.press(Source::Stick(id), Action::Down, value <= -threshold);
+                    self.press(Source::Stick(id), Action::Up, value >= threshold);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Returns the Gameboy buttons to hold down for the next frame.
+    pub fn get_buttons(&mut self) -> Vec<GameboyButton> {
+        self.frame = self.frame.wrapping_add(1);
+        let turbo_pressed = (self.frame / self.config.turbo_period).is_multiple_of(2);
+
+        let mut buttons = Vec::new();
+        for &(_, action) in &self.held {
+            if action.is_turbo() && !turbo_pressed {
+                continue;
+            }
+
+            if let Some(button) = action.get_button() {
+                if !buttons.contains(&button) {
+                    buttons.push(button);
+                }
+            }
+        }
+
+        buttons
+    }
+
+    pub fn build(config: InputConfig) -> Input {
+        #[cfg(feature = "gamepad")]
+        let gilrs = match Gilrs::new() {
+            Ok(gilrs) => {
+                for (_, gamepad) in gilrs.gamepads() {
+                    info!("Found gamepad: {}", gamepad.name());
+                }
+                Some(gilrs)
+            }
+            Err(e) => {
+                warn!("Gamepads are unavailable: {}", e);
+                None
+            }
+        };
+
+        Input {
+            config,
+            held: Vec::new(),
+            frame: 0,
+            #[cfg(feature = "gamepad")]
+            gilrs,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_bindings_unbind_conflicting_defaults() {
+        let config = InputConfig::parse("[keyboard]\na = \"Z\"").unwrap();
+
+        assert!(config.keyboard[&Action::A] == VirtualKeyCode::Z);
+        assert!(!config.keyboard.contains_key(&Action::B));
+        assert!(config.keyboard[&Action::TurboA] == VirtualKeyCode::C);
+    }
+
+    #[test]
+    fn file_bindings_swap_keys() {
+        let config = InputConfig::parse("[keyboard]\na = \"Z\"\nb = \"X\"").unwrap();
+
+        assert!(config.keyboard[&Action::A] == VirtualKeyCode::Z);
+        assert!(config.keyboard[&Action::B] == VirtualKeyCode::X);
+        assert_eq!(config.keyboard.len(), 12);
+    }
+
+    #[test]
+    fn rejects_stick_thresholds_out_of_range() {
+        for threshold in &["0.0", "-0.5", "1.5", "nan"] {
+            let text = format!("stick_threshold = {}", threshold);
+            assert!(InputConfig::parse(&text).is_err(), "{}", threshold);
+        }
+
+        let config = InputConfig::parse("stick_threshold = 1.0").unwrap();
+        assert_eq!(config.stick_threshold, 1.0);
+    }
+}
@@ -0,0 +1,130 @@
+/**
+ * link.rs
+ *
+ * Connects the link ports of two Oxidgb processes over a TCP socket. Unlike
+ *  the core's in-process link cable, the two ends aren't run in lockstep, so
+ *  the Gameboy driving the clock waits (for a bounded time) for the other
+ *  end to respond.
+**/
+use std::io::Read;
+use std::io::Write;
+use std::net::TcpListener;
+use std::net::TcpStream;
+use std::sync::mpsc::channel;
+use std::sync::mpsc::Receiver;
+use std::sync::mpsc::RecvTimeoutError;
+use std::thread;
+use std::time::Duration;
+use std::time::Instant;
+
+use oxidgb_core::serial::SerialDevice;
+
+/// A byte clocked out by the sending end.
+const PACKET_CLOCK: u8 = 0;
+/// The byte shifted back in response to a clocked byte.
+const PACKET_REPLY: u8 = 1;
+
+/// How long to wait for the other end to respond before giving up on it.
+const REPLY_TIMEOUT: Duration = Duration::from_millis(100);
+
+pub struct SocketLink {
+    stream: TcpStream,
+    packets: Receiver<[u8; 2]>,
+    /// A byte clocked by the other end, found while discarding stale replies
+    pending_clock: Option<u8>,
+}
+
+impl SocketLink {
+    /// Waits for another instance to connect on the specified address.
+    pub fn listen(addr: &str) -> std::io::Result<SocketLink> {
+        let listener = TcpListener::bind(addr)?;
+        info!("Waiting for link connection on {}", addr);
+        let (stream, peer) = listener.accept()?;
+        info!("Link connected to {}", peer);
+        SocketLink::build(stream)
+    }
+
+    /// Connects to a listening instance.
+    pub fn connect(addr: &str) -> std::io::Result<SocketLink> {
+        let stream = TcpStream::connect(addr)?;
+        info!("Link connected to {}", addr);
+        SocketLink::build(stream)
+    }
+
+    fn build(stream: TcpStream) -> std::io::Result<SocketLink> {
+        stream.set_nodelay(true)?;
+
+        // Read packets in the background so that polling never blocks
+        let mut reader = stream.try_clone()?;
+        let (sender, packets) = channel();
+        thread::spawn(move || {
+            let mut packet = [0u8; 2];
+            while reader.read_exact(&mut packet).is_ok() {
+                if sender.send(packet).is_err() {
+                    break;
+                }
+            }
+            warn!("Link disconnected");
+        });
+
+        Ok(SocketLink {
+            stream,
+            packets,
+            pending_clock: None,
+        })
+    }
+
+    fn send(&mut self, kind: u8, value: u8) {
+        if let Err(e) = self.stream.write_all(&[kind, value]) {
+            warn!("Failed to send over link: {}", e);
+        }
+    }
+}
+
+impl SerialDevice for SocketLink {
+    fn exchange(&mut self, out: u8) -> u8 {
+        // Replies from a timed out exchange arrive late, and would otherwise
+        //  be taken as the reply to this byte
+        while let Ok(packet) = self.packets.try_recv() {
+            if packet[0] == PACKET_CLOCK {
+                self.pending_clock = Some(packet[1]);
+            }
+        }
+
+        self.send(PACKET_CLOCK, out);
+
+        // Both ends are driving the clock - each sees the other's byte
+        if let Some(value) = self.pending_clock.take() {
+            return value;
+        }
+
+        let deadline = Instant::now() + REPLY_TIMEOUT;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match self.packets.recv_timeout(remaining) {
+                Ok([PACKET_REPLY, value]) | Ok([PACKET_CLOCK, value]) => return value,
+                Ok(_) => continue,
+                Err(RecvTimeoutError::Timeout) | Err(RecvTimeoutError::Disconnected) => {
+                    return 0xFF
+                }
+            }
+        }
+    }
+
+    fn poll_external(&mut self, out: u8) -> Option<u8> {
+        if let Some(value) = self.pending_clock.take() {
+            self.send(PACKET_REPLY, out);
+            return Some(value);
+        }
+
+        while let Ok(packet) = self.packets.try_recv() {
+            // Replies arriving here are left over from a timed out exchange
+            if packet[0] == PACKET_CLOCK {
+                self.send(PACKET_REPLY, out);
+                return Some(packet[1]);
+            }
+        }
+
+        None
+    }
+}
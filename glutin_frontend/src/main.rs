@@ -8,28 +8,41 @@ extern crate glutin;
 extern crate libc;
 
 extern crate clap;
+extern crate dirs;
+#[cfg(feature = "gamepad")]
+extern crate gilrs;
 extern crate native_dialog;
 #[cfg(feature = "debugger")]
 extern crate rustyline;
 
 #[macro_use]
 extern crate log;
+#[macro_use]
+extern crate serde;
+extern crate toml;
 
 extern crate oxidgb_core;
+extern crate oxidgb_frontend_common;
 
 #[cfg(feature = "enable_sound")]
 extern crate rodio;
 
+mod battery;
 #[cfg(feature = "debugger")]
 mod debugger;
-mod logging;
+mod display;
+mod input;
+mod link;
+mod printer;
+mod savestate;
+mod viewer;
 
-use std::ffi::CStr;
-use std::mem;
-use std::mem::MaybeUninit;
+#[cfg(feature = "debugger")]
+use std::fs::File;
+#[cfg(feature = "debugger")]
+use std::io::BufWriter;
 use std::path::{Path, PathBuf};
 use std::process::exit;
-use std::ptr;
 use std::time::Duration;
 use std::time::Instant;
 
@@ -37,13 +50,35 @@ use clap::App;
 use clap::Arg;
 
 use oxidgb_core::cpu::CPU;
-use oxidgb_core::input::GameboyButton;
 use oxidgb_core::mem::GBMemory;
+use oxidgb_core::model::Model;
+use oxidgb_core::rewind::Rewind;
 use oxidgb_core::rom::get_rom_size;
 use oxidgb_core::rom::GameROM;
+use oxidgb_core::serial::SerialLogger;
+#[cfg(feature = "debugger")]
+use oxidgb_core::symbols::SymbolTable;
+#[cfg(feature = "debugger")]
+use oxidgb_core::trace::TraceFormat;
 
+use oxidgb_frontend_common::logging::setup_logging;
+#[cfg(feature = "debugger")]
+use oxidgb_frontend_common::trace::Tracer;
+
+use battery::BatterySave;
+#[cfg(feature = "debugger")]
+use debugger::gdb::GdbStub;
 #[cfg(feature = "debugger")]
 use debugger::CommandLineDebugger;
+use display::Display;
+use input::Action;
+use input::Input;
+use input::InputConfig;
+use link::SocketLink;
+use printer::PrinterOutput;
+use savestate::SaveStates;
+use viewer::ViewerKind;
+use viewer::Viewers;
 
 #[cfg(feature = "enable_sound")]
 use rodio::buffer::SamplesBuffer;
@@ -52,10 +87,8 @@ use rodio::queue::queue;
 #[cfg(feature = "enable_sound")]
 use rodio::Sink;
 
-use glutin::event::{ElementState, Event, VirtualKeyCode, WindowEvent};
+use glutin::event::{ElementState, Event, ModifiersState, WindowEvent};
 use glutin::event_loop::ControlFlow;
-#[cfg(target_family = "windows")]
-use glutin::platform::windows::WindowBuilderExtWindows;
 
 fn main() {
     // Parse arguments
@@ -70,6 +103,63 @@ fn main() {
                 .help("Loads the specified ROM")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("boot-rom")
+                .short("b")
+                .long("boot-rom")
+                .value_name("FILE")
+                .help("Runs the specified boot ROM before the game")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("model")
+                .short("m")
+                .long("model")
+                .value_name("MODEL")
+                .help("Selects the hardware to emulate")
+                .possible_values(&["dmg0", "dmg", "mgb", "sgb", "cgb", "agb"])
+                .default_value("dmg")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("serial-log")
+                .long("serial-log")
+                .help("Logs data sent over the link port (e.g. test ROM output)"),
+        )
+        .arg(
+            Arg::with_name("printer")
+                .short("p")
+                .long("printer")
+                .value_name("DIRECTORY")
+                .help("Connects a Gameboy Printer, saving printed images to the directory")
+                .takes_value(true)
+                .conflicts_with_all(&["serial-log", "link-listen", "link-connect"]),
+        )
+        .arg(
+            Arg::with_name("link-listen")
+                .long("link-listen")
+                .value_name("ADDRESS")
+                .help("Waits for another instance to connect a link cable (e.g. 0.0.0.0:5738)")
+                .takes_value(true)
+                .conflicts_with_all(&["link-connect", "serial-log"]),
+        )
+        .arg(
+            Arg::with_name("link-connect")
+                .long("link-connect")
+                .value_name("ADDRESS")
+                .help("Connects a link cable to another instance")
+                .takes_value(true)
+                .conflicts_with("serial-log"),
+        )
+        .arg(
+            Arg::with_name("cheat")
+                .long("cheat")
+                .value_name("CODE")
+                .help("Enables a Game Genie (ABC-DEF-GHI) or GameShark (01VVLLHH) code")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        )
         .arg(
             Arg::with_name("verbose")
                 .short("v")
@@ -77,12 +167,45 @@ fn main() {
                 .help("Enables verbose logging"),
         );
 
+    #[cfg(feature = "debugger")]
+    let app = app
+        .arg(
+            Arg::with_name("trace")
+                .long("trace")
+                .value_name("FILE")
+                .help("Logs every executed instruction to the file")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("trace-format")
+                .long("trace-format")
+                .value_name("FORMAT")
+                .help("Selects the format of the trace log")
+                .possible_values(&["doctor", "bgb"])
+                .default_value("doctor")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("gdb")
+                .long("gdb")
+                .value_name("ADDRESS")
+                .help("Waits for GDB to connect on the address (e.g. 127.0.0.1:2345)")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("symbols")
+                .long("symbols")
+                .value_name("FILE")
+                .help("Loads labels from a RGBDS symbol file (defaults to the ROM's .sym file)")
+                .takes_value(true),
+        );
+
     let args = app.get_matches();
 
     let enable_verbose = args.is_present("verbose");
 
     // Set up logger
-    logging::setup_logging(enable_verbose).expect("Failed to setup logger");
+    setup_logging(enable_verbose).expect("Failed to setup logger");
 
     info!("Oxidgb v0.1");
 
@@ -120,14 +243,83 @@ fn main() {
         warn!("File size is not equal to what ROM declares!");
     }
 
-    let rom = GameROM::build(data);
+    let mut rom = GameROM::build(data);
+
+    let save_states = SaveStates::build(rom_path);
+
+    let mut battery_save = BatterySave::build(rom_path, &rom);
+    if let Some(ref save) = battery_save {
+        save.load(&mut rom);
+    }
+
+    for code in args.values_of("cheat").into_iter().flatten() {
+        rom.cheats
+            .add(code)
+            .unwrap_or_else(|e| panic!("Invalid cheat {}: {}", code, e));
+    }
+
+    let boot_rom = args
+        .value_of("boot-rom")
+        .map(|path| std::fs::read(path).expect("Failed to read boot ROM"));
+
+    let model = Model::from_name(args.value_of("model").unwrap()).expect("Invalid model");
 
     // Build memory
-    let memory = GBMemory::build(rom);
+    let mut memory = GBMemory::build(model, rom, boot_rom);
+
+    if args.is_present("serial-log") {
+        memory.set_serial_device(Box::new(SerialLogger::build()));
+    }
+
+    let link = if let Some(addr) = args.value_of("link-listen") {
+        Some(SocketLink::listen(addr))
+    } else {
+        args.value_of("link-connect").map(SocketLink::connect)
+    };
+
+    if let Some(link) = link {
+        memory.set_serial_device(Box::new(link.expect("Failed to set up link cable")));
+    }
+
+    let mut printer_output = args
+        .value_of("printer")
+        .map(|dir| PrinterOutput::build(PathBuf::from(dir)));
+
+    if let Some(ref output) = printer_output {
+        memory.set_serial_device(Box::new(output.printer.clone()));
+    }
 
     #[cfg(feature = "debugger")]
     let mut debugger = CommandLineDebugger::build();
 
+    #[cfg(feature = "debugger")]
+    if let Some(path) = args.value_of("trace") {
+        let format = TraceFormat::from_name(args.value_of("trace-format").unwrap())
+            .expect("Invalid trace format");
+        let file = File::create(path).expect("Failed to create trace log");
+        debugger.tracer = Some(Tracer::build(BufWriter::new(file), format));
+    }
+
+    #[cfg(feature = "debugger")]
+    {
+        let symbols_path = match args.value_of("symbols") {
+            Some(path) => Some(PathBuf::from(path)),
+            None => Some(rom_path.with_extension("sym")).filter(|path| path.exists()),
+        };
+
+        if let Some(path) = symbols_path {
+            let text = std::fs::read_to_string(&path).expect("Failed to read symbol file");
+            debugger.symbols = SymbolTable::parse(&text);
+            info!("Loaded {} symbols from {}", debugger.symbols.len(), path.display());
+        }
+    }
+
+    #[cfg(feature = "debugger")]
+    if let Some(addr) = args.value_of("gdb") {
+        debugger.gdb = Some(GdbStub::build(addr).expect("Failed to listen for GDB"));
+        debugger.enabled = false;
+    }
+
     // Build CPU
     let mut cpu = CPU::build(memory);
 
@@ -153,250 +345,134 @@ fn main() {
     };
 
     let events_loop = glutin::event_loop::EventLoop::new();
-    let mut window = glutin::window::WindowBuilder::new()
-        .with_title("Oxidgb")
-        .with_inner_size(glutin::dpi::LogicalSize::new(160 * 2, 144 * 2));
-
-    // Work around COM support issue with audio
-    #[cfg(target_family = "windows")]
-    {
-        window = window.with_drag_and_drop(false);
-    }
-
-    let context = glutin::ContextBuilder::new()
-        .with_vsync(true)
-        .build_windowed(window, &events_loop)
-        .expect("Failed to build context");
-
-    let context = unsafe {
-        context
-            .make_current()
-            .expect("Failed to set context as current")
-    };
-
-    unsafe {
-        gl::load_with(|symbol| context.get_proc_address(symbol) as *const _);
-        gl::ClearColor(0.0, 0.0, 0.0, 1.0);
-    }
-
-    // Prepare OpenGL
-    let version = unsafe {
-        CStr::from_ptr(gl::GetString(gl::VERSION) as *const _)
-            .to_str()
-            .expect("Failed to convert to string")
-    };
-
-    info!("OpenGL version: {}", version);
-
-    let mut tex = MaybeUninit::uninit();
-    let mut ebo = MaybeUninit::uninit();
-
-    unsafe {
-        // Generate shaders
-        // Stolen from https://github.com/tomaka/glutin/blob/master/examples/support/mod.rs &
-        //             https://open.gl/content/code/c3_multitexture.txt
-        let vs = gl::CreateShader(gl::VERTEX_SHADER);
-        gl::ShaderSource(vs, 1, [VS_SRC.as_ptr() as *const _].as_ptr(), ptr::null());
-        gl::CompileShader(vs);
-
-        let fs = gl::CreateShader(gl::FRAGMENT_SHADER);
-        gl::ShaderSource(fs, 1, [FS_SRC.as_ptr() as *const _].as_ptr(), ptr::null());
-        gl::CompileShader(fs);
-
-        let program = gl::CreateProgram();
-        gl::AttachShader(program, vs);
-        gl::AttachShader(program, fs);
-        gl::BindFragDataLocation(program, 0, b"outColor\0".as_ptr() as *const _);
-        gl::LinkProgram(program);
-        gl::UseProgram(program);
-
-        gl::GenBuffers(1, ebo.as_mut_ptr());
-        gl::BindBuffer(gl::ARRAY_BUFFER, ebo.assume_init());
-        gl::BufferData(
-            gl::ARRAY_BUFFER,
-            (ELEMENTS.len() * mem::size_of::<u32>()) as gl::types::GLsizeiptr,
-            ELEMENTS.as_ptr() as *const _,
-            gl::STATIC_DRAW,
-        );
-
-        let mut vb = MaybeUninit::uninit();
-        gl::GenBuffers(1, vb.as_mut_ptr());
-        gl::BindBuffer(gl::ARRAY_BUFFER, vb.assume_init());
-        gl::BufferData(
-            gl::ARRAY_BUFFER,
-            (VERTEX_DATA.len() * mem::size_of::<f32>()) as gl::types::GLsizeiptr,
-            VERTEX_DATA.as_ptr() as *const _,
-            gl::STATIC_DRAW,
-        );
-
-        let mut vao = MaybeUninit::uninit();
-        gl::GenVertexArrays(1, vao.as_mut_ptr());
-        gl::BindVertexArray(vao.assume_init());
-
-        let pos_attrib = gl::GetAttribLocation(program, b"position\0".as_ptr() as *const _);
-        let color_attrib = gl::GetAttribLocation(program, b"color\0".as_ptr() as *const _);
-        let tex_attrib = gl::GetAttribLocation(program, b"texcoord\0".as_ptr() as *const _);
-        gl::VertexAttribPointer(
-            pos_attrib as gl::types::GLuint,
-            2,
-            gl::FLOAT,
-            0,
-            7 * mem::size_of::<f32>() as gl::types::GLsizei,
-            ptr::null(),
-        );
-        gl::VertexAttribPointer(
-            color_attrib as gl::types::GLuint,
-            3,
-            gl::FLOAT,
-            0,
-            7 * mem::size_of::<f32>() as gl::types::GLsizei,
-            (2 * mem::size_of::<f32>()) as *const () as *const _,
-        );
-        gl::VertexAttribPointer(
-            tex_attrib as gl::types::GLuint,
-            2,
-            gl::FLOAT,
-            0,
-            7 * mem::size_of::<f32>() as gl::types::GLsizei,
-            (5 * mem::size_of::<f32>()) as *const () as *const _,
-        );
-        gl::EnableVertexAttribArray(pos_attrib as gl::types::GLuint);
-        gl::EnableVertexAttribArray(color_attrib as gl::types::GLuint);
-        gl::EnableVertexAttribArray(tex_attrib as gl::types::GLuint);
-
-        // Generate texture (for us to dump into)
-        gl::GenTextures(1, tex.as_mut_ptr());
-        gl::ActiveTexture(gl::TEXTURE0);
-        gl::BindTexture(gl::TEXTURE_2D, tex.assume_init());
-
-        gl::Uniform1i(
-            gl::GetUniformLocation(program, b"tex\0".as_ptr() as *const _),
-            0,
-        );
-        gl::TexParameteri(
-            gl::TEXTURE_2D,
-            gl::TEXTURE_WRAP_S,
-            gl::CLAMP_TO_EDGE as gl::types::GLint,
-        );
-        gl::TexParameteri(
-            gl::TEXTURE_2D,
-            gl::TEXTURE_WRAP_T,
-            gl::CLAMP_TO_EDGE as gl::types::GLint,
-        );
-        gl::TexParameteri(
-            gl::TEXTURE_2D,
-            gl::TEXTURE_MIN_FILTER,
-            gl::NEAREST as gl::types::GLint,
-        );
-        gl::TexParameteri(
-            gl::TEXTURE_2D,
-            gl::TEXTURE_MAG_FILTER,
-            gl::NEAREST as gl::types::GLint,
-        );
-    }
+    let mut display = Display::build(&events_loop, "Oxidgb", 160, 144, 2.0, true);
 
     // Update input
-    let mut gb_buttons = Vec::new();
+    let config = InputConfig::load().unwrap_or_else(|e| {
+        error!("Failed to load key bindings: {}", e);
+        InputConfig::default()
+    });
+    let mut controls = Input::build(config);
     let mut fast_forward = false;
+    let mut rewind = Rewind::build(REWIND_INTERVAL, REWIND_CAPACITY);
+    let mut modifiers = ModifiersState::empty();
+
+    let mut viewers = Viewers::build();
 
     let mut loop_time_remaining = Instant::now();
     let mut update_submitted = false;
 
-    events_loop.run(move |event, _, control_flow| {
+    events_loop.run(move |event, target, control_flow| {
         *control_flow = ControlFlow::Poll;
 
         let max_frame = Duration::from_nanos(16742706);
 
         match event {
-            Event::LoopDestroyed => return,
+            Event::LoopDestroyed => {
+                if let Some(ref mut save) = battery_save {
+                    save.flush(&mut cpu.mem.rom);
+                }
+
+                #[cfg(feature = "debugger")]
+                if let Some(ref mut tracer) = debugger.tracer {
+                    if let Err(e) = tracer.flush() {
+                        error!("Failed to write trace: {}", e);
+                    }
+                }
+                return;
+            }
+            Event::WindowEvent { event, window_id } if window_id != display.window().id() => {
+                viewers.handle_event(window_id, &event);
+            }
             Event::WindowEvent { event, .. } => match event {
-                WindowEvent::Resized(physical_size) => context.resize(physical_size),
+                WindowEvent::Resized(physical_size) => display.resize(physical_size),
                 WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
+                WindowEvent::ModifiersChanged(state) => modifiers = state,
                 WindowEvent::KeyboardInput { input, .. } => {
                     if let Some(key) = input.virtual_keycode {
-                        let key = match key {
-                            VirtualKeyCode::Up => GameboyButton::UP,
-                            VirtualKeyCode::Down => GameboyButton::DOWN,
-                            VirtualKeyCode::Left => GameboyButton::LEFT,
-                            VirtualKeyCode::Right => GameboyButton::RIGHT,
-                            VirtualKeyCode::X => GameboyButton::A,
-                            VirtualKeyCode::Z => GameboyButton::B,
-                            VirtualKeyCode::A => GameboyButton::SELECT,
-                            VirtualKeyCode::S => GameboyButton::START,
-                            VirtualKeyCode::Tab => {
-                                match input.state {
-                                    ElementState::Pressed => {
-                                        fast_forward = true;
-                                    }
-                                    _ => {
-                                        fast_forward = false;
-                                        // Reset the loop time
-                                        loop_time_remaining = Instant::now();
-                                    }
+                        if let Some(slot) = savestate::get_slot(key) {
+                            if input.state == ElementState::Pressed {
+                                let result = if modifiers.shift() {
+                                    // History from before the load no longer leads here
+                                    save_states.load(slot, &mut cpu).map(|()| rewind.clear())
+                                } else {
+                                    save_states.save(slot, &cpu)
+                                };
+
+                                if let Err(e) = result {
+                                    error!("Save state slot {}: {}", slot, e);
                                 }
-                                return;
-                            }
-                            _ => {
-                                return;
                             }
-                        };
+                            return;
+                        }
 
-                        match input.state {
-                            ElementState::Pressed => {
-                                if !gb_buttons.contains(&key) {
-                                    gb_buttons.push(key);
-                                }
-                            }
-                            ElementState::Released => {
-                                if let Some(pos) = gb_buttons.iter().position(|x| *x == key) {
-                                    gb_buttons.remove(pos);
-                                }
+                        if let Some(kind) = ViewerKind::from_key(key) {
+                            if input.state == ElementState::Pressed {
+                                viewers.toggle(target, kind);
                             }
+                            return;
                         }
+
+                        controls.handle_key(key, input.state);
                     }
                 }
                 _ => (),
             },
+            Event::RedrawRequested(window_id) if window_id != display.window().id() => {
+                viewers.redraw(window_id);
+            }
             Event::RedrawRequested(_) => {
                 if cpu.mem.gpu.is_enabled() && update_submitted {
-                    unsafe {
-                        gl::Clear(gl::COLOR_BUFFER_BIT);
-
-                        gl::ActiveTexture(gl::TEXTURE0);
-                        gl::BindTexture(gl::TEXTURE_2D, tex.assume_init());
-                        gl::TexImage2D(
-                            gl::TEXTURE_2D,
-                            0,
-                            gl::RGB as gl::types::GLint,
-                            160,
-                            144,
-                            0,
-                            gl::RGB,
-                            gl::UNSIGNED_BYTE,
-                            cpu.mem.gpu.pixel_data.as_ptr() as *const _,
-                        );
-
-                        gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, ebo.assume_init());
-                        gl::DrawElements(gl::TRIANGLES, 6, gl::UNSIGNED_INT, std::ptr::null::<_>());
-                    }
+                    display.draw(&cpu.mem.gpu.pixel_data);
                 }
 
-                context.swap_buffers().unwrap();
+                display.present();
                 update_submitted = false;
             }
             Event::MainEventsCleared => {
+                #[cfg(feature = "gamepad")]
+                controls.poll_gamepads();
+
+                let fast_forward_held = controls.is_held(Action::FastForward);
+                if fast_forward && !fast_forward_held {
+                    // Reset the loop time
+                    loop_time_remaining = Instant::now();
+                }
+                fast_forward = fast_forward_held;
+
                 let mut requested_redraw = false;
                 let mut fast_forward_loops = 0;
 
                 while (!fast_forward && loop_time_remaining + max_frame <= Instant::now())
                     || (fast_forward && fast_forward_loops < 4)
                 {
-                    cpu.mem.set_input(&gb_buttons);
+                    if controls.is_held(Action::Rewind) {
+                        // Step back a snapshot each frame
+                        rewind.rewind(&mut cpu, REWIND_INTERVAL);
+                    } else {
+                        cpu.mem.set_input(&controls.get_buttons());
+
+                        #[cfg(feature = "debugger")]
+                        {
+                            cpu.run(&mut debugger);
+                            if debugger.shutdown {
+                                *control_flow = ControlFlow::Exit;
+                                return;
+                            }
+                        }
+                        #[cfg(not(feature = "debugger"))]
+                        cpu.run();
+
+                        rewind.capture(&cpu);
+                    }
+
+                    if let Some(ref mut save) = battery_save {
+                        save.on_frame(&mut cpu.mem.rom);
+                    }
 
-                    #[cfg(feature = "debugger")]
-                    cpu.run(&mut debugger);
-                    #[cfg(not(feature = "debugger"))]
-                    cpu.run();
+                    if let Some(ref mut output) = printer_output {
+                        output.save_images();
+                    }
 
                     // Hard sync sleep
                     if !fast_forward {
@@ -422,7 +498,7 @@ fn main() {
                     }
 
                     if !requested_redraw {
-                        context.window().request_redraw();
+                        display.window().request_redraw();
                         requested_redraw = true;
                     }
 
@@ -432,6 +508,10 @@ fn main() {
                         fast_forward_loops += 1;
                     }
                 }
+
+                if requested_redraw {
+                    viewers.update(&cpu.mem.gpu);
+                }
             }
             _ => (),
         }
@@ -442,47 +522,7 @@ fn main() {
     });
 }
 
-// OpenGL resources
-static VERTEX_DATA: [f32; 28] = [
-    // X    Y    R    G    B    U    V
-    -1.0, 1.0, 1.0, 1.0, 1.0, 0.0, 0.0, // Top-left
-    1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 0.0, // Top-right
-    1.0, -1.0, 1.0, 1.0, 1.0, 1.0, 1.0, // Bottom-right
-    -1.0, -1.0, 1.0, 1.0, 1.0, 0.0, 1.0, // Bottom-left
-];
-
-static ELEMENTS: [u32; 6] = [0, 1, 2, 2, 3, 0];
-
-const VS_SRC: &[u8] = b"
-    #version 150 core
-
-    in vec2 position;
-    in vec3 color;
-    in vec2 texcoord;
-
-    out vec3 Color;
-    out vec2 Texcoord;
-
-    void main()
-    {
-        Color = color;
-        Texcoord = texcoord;
-        gl_Position = vec4(position, 0.0, 1.0);
-    }
-\0";
-
-const FS_SRC: &[u8] = b"
-    #version 150 core
-
-    in vec3 Color;
-    in vec2 Texcoord;
-
-    out vec4 outColor;
-
-    uniform sampler2D tex;
-
-    void main()
-    {
-        outColor = texture(tex, Texcoord) * vec4(Color, 1.0);
-    }
-\0";
+/// How often (in frames) to snapshot the emulator for rewinding.
+const REWIND_INTERVAL: u32 = 2;
+/// How many snapshots to keep for rewinding (20 seconds worth).
+const REWIND_CAPACITY: usize = 600;
@@ -0,0 +1,69 @@
+/**
+ * printer.rs
+ *
+ * Saves images from the emulated Gameboy Printer as PNG files.
+**/
+extern crate chrono;
+extern crate png;
+
+use std::cell::RefCell;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+use oxidgb_core::printer::PrintedImage;
+use oxidgb_core::printer::Printer;
+
+pub struct PrinterOutput {
+    pub printer: Rc<RefCell<Printer>>,
+    directory: PathBuf,
+    saved_count: usize,
+}
+
+impl PrinterOutput {
+    /// Saves any images which have been printed since this was last called.
+    pub fn save_images(&mut self) {
+        let images = self.printer.borrow_mut().take_images();
+
+        for image in images {
+            self.saved_count += 1;
+            let path = self.directory.join(format!(
+                "print-{}-{}.png",
+                chrono::Local::now().format("%Y%m%d-%H%M%S"),
+                self.saved_count
+            ));
+
+            match save_png(&path, &image) {
+                Ok(()) => info!("Saved printed image to {}", path.display()),
+                Err(e) => error!("Failed to save printed image: {}", e),
+            }
+        }
+    }
+
+    pub fn build(directory: PathBuf) -> PrinterOutput {
+        PrinterOutput {
+            printer: Rc::new(RefCell::new(Printer::build())),
+            directory,
+            saved_count: 0,
+        }
+    }
+}
+
+/// Writes a image out as a greyscale PNG.
+fn save_png(path: &PathBuf, image: &PrintedImage) -> Result<(), png::EncodingError> {
+    let file = File::create(path)?;
+
+    let mut encoder = png::Encoder::new(
+        BufWriter::new(file),
+        image.width as u32,
+        image.height as u32,
+    );
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let pixels: Vec<u8> = image.pixels.iter().map(|shade| 255 - shade * 85).collect();
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&pixels)
+}
@@ -0,0 +1,117 @@
+/**
+ * savestate.rs
+ *
+ * Numbered save state slots, stored next to the ROM. Each state starts with
+ *  a header identifying the ROM it was made for, along with a thumbnail of
+ *  the screen at the time.
+**/
+use std::path::Path;
+use std::path::PathBuf;
+
+use glutin::event::VirtualKeyCode;
+
+use oxidgb_core::cpu::CPU;
+use oxidgb_core::gpu::PITCH;
+use oxidgb_core::state::load_state;
+use oxidgb_core::state::save_state;
+
+const MAGIC: &[u8; 4] = b"OXGS";
+const VERSION: u32 = 2;
+
+const THUMBNAIL_WIDTH: usize = 80;
+const THUMBNAIL_HEIGHT: usize = 72;
+const HEADER_SIZE: usize = 16 + THUMBNAIL_WIDTH * THUMBNAIL_HEIGHT * PITCH;
+
+pub struct SaveStates {
+    rom_path: PathBuf,
+}
+
+impl SaveStates {
+    fn get_path(&self, slot: u8) -> PathBuf {
+        self.rom_path.with_extension(format!("ss{}", slot))
+    }
+
+    /// Saves the current state to a slot.
+    pub fn save(&self, slot: u8, cpu: &CPU) -> Result<(), String> {
+        let mut data = Vec::with_capacity(HEADER_SIZE);
+        data.extend_from_slice(MAGIC);
+        data.extend_from_slice(&VERSION.to_le_bytes());
+        data.extend_from_slice(&get_rom_checksum(cpu).to_le_bytes());
+        data.extend_from_slice(&(THUMBNAIL_WIDTH as u16).to_le_bytes());
+        data.extend_from_slice(&(THUMBNAIL_HEIGHT as u16).to_le_bytes());
+
+        // Take every other pixel of every other line
+        let pixels = &cpu.mem.gpu.pixel_data;
+        for y in 0..THUMBNAIL_HEIGHT {
+            for x in 0..THUMBNAIL_WIDTH {
+                let pos = (y * 2 * 160 + x * 2) * PITCH;
+                data.extend_from_slice(&pixels[pos..pos + PITCH]);
+            }
+        }
+
+        data.extend_from_slice(&save_state(cpu));
+
+        let path = self.get_path(slot);
+        std::fs::write(&path, data).map_err(|e| e.to_string())?;
+        info!("Saved state to {}", path.display());
+
+        Ok(())
+    }
+
+    /// Loads the state in a slot, if it was made for the running ROM. Any
+    ///  device connected to the link port stays connected.
+    pub fn load(&self, slot: u8, cpu: &mut CPU) -> Result<(), String> {
+        let path = self.get_path(slot);
+        let data = std::fs::read(&path).map_err(|e| e.to_string())?;
+
+        if data.len() < HEADER_SIZE || &data[0..4] != MAGIC {
+            return Err("Not a save state".to_string());
+        }
+
+        let version = read_u32(&data[4..8]);
+        if version != VERSION {
+            return Err(format!("Unsupported save state version: {}", version));
+        }
+
+        if read_u32(&data[8..12]) != get_rom_checksum(cpu) {
+            return Err("Save state was made for a different ROM".to_string());
+        }
+
+        load_state(cpu, &data[HEADER_SIZE..]).map_err(|e| e.to_string())?;
+
+        info!("Loaded state from {}", path.display());
+
+        Ok(())
+    }
+
+    pub fn build(rom_path: &Path) -> SaveStates {
+        SaveStates {
+            rom_path: rom_path.to_path_buf(),
+        }
+    }
+}
+
+/// Returns the save slot a function key is bound to.
+pub fn get_slot(key: VirtualKeyCode) -> Option<u8> {
+    match key {
+        VirtualKeyCode::F1 => Some(1),
+        VirtualKeyCode::F2 => Some(2),
+        VirtualKeyCode::F3 => Some(3),
+        VirtualKeyCode::F4 => Some(4),
+        VirtualKeyCode::F5 => Some(5),
+        VirtualKeyCode::F6 => Some(6),
+        VirtualKeyCode::F7 => Some(7),
+        VirtualKeyCode::F8 => Some(8),
+        VirtualKeyCode::F9 => Some(9),
+        _ => None,
+    }
+}
+
+/// Identifies the running ROM using the checksums in its header.
+fn get_rom_checksum(cpu: &CPU) -> u32 {
+    ((cpu.mem.rom.get_global_checksum() as u32) << 8) | cpu.mem.rom.get_header_checksum() as u32
+}
+
+fn read_u32(data: &[u8]) -> u32 {
+    u32::from_le_bytes([data[0], data[1], data[2], data[3]])
+}
@@ -0,0 +1,440 @@
+/**
+ * viewer.rs
+ *
+ * Debug windows showing the contents of VRAM and OAM: every tile, the two
+ *  background maps (with the area shown by the background and the window)
+ *  and a table of sprites. Toggled with F10-F12, and refreshed every frame.
+**/
+use glutin::event::ElementState;
+use glutin::event::VirtualKeyCode;
+use glutin::event::WindowEvent;
+use glutin::event_loop::EventLoopWindowTarget;
+use glutin::window::WindowId;
+
+use oxidgb_core::gpu::GPU;
+use oxidgb_core::gpu::PITCH;
+
+use display::Display;
+
+/// The number of tiles in VRAM.
+const TILE_COUNT: usize = 384;
+/// Tiles per row in the tile viewer.
+const TILES_PER_ROW: usize = 16;
+/// Where the two background maps start in VRAM.
+const MAP_OFFSETS: [usize; 2] = [0x1800, 0x1C00];
+/// The gap between the two maps in the map viewer.
+const MAP_GAP: usize = 8;
+/// Sprites per column in the OAM viewer.
+const SPRITES_PER_COLUMN: usize = 20;
+const SPRITE_COLUMN_WIDTH: usize = 128;
+const SPRITE_ROW_HEIGHT: usize = 18;
+
+const BACKGROUND_COLOUR: [u8; 3] = [48, 48, 48];
+const PREVIEW_COLOUR: [u8; 3] = [24, 24, 24];
+const TEXT_COLOUR: [u8; 3] = [255, 255, 255];
+const VIEWPORT_COLOUR: [u8; 3] = [255, 0, 0];
+const WINDOW_COLOUR: [u8; 3] = [0, 128, 255];
+
+/// Palettes the tile viewer can show tiles with. Tiles aren't tied to a
+///  palette, so this is cycled through with space.
+const TILE_PALETTES: [&str; 4] = ["BGP", "OBP0", "OBP1", "Raw"];
+
+/// A 3x5 font, for the sprite table. Each row is 3 bits, left to right.
+const FONT: [(char, [u8; 5]); 23] = [
+    ('0', [0b111, 0b101, 0b101, 0b101, 0b111]),
+    ('1', [0b010, 0b110, 0b010, 0b010, 0b111]),
+    ('2', [0b111, 0b001, 0b111, 0b100, 0b111]),
+    ('3', [0b111, 0b001, 0b111, 0b001, 0b111]),
+    ('4', [0b101, 0b101, 0b111, 0b001, 0b001]),
+    ('5', [0b111, 0b100, 0b111, 0b001, 0b111]),
+    ('6', [0b111, 0b100, 0b111, 0b101, 0b111]),
+    ('7', [0b111, 0b001, 0b001, 0b001, 0b001]),
+    ('8', [0b111, 0b101, 0b111, 0b101, 0b111]),
+    ('9', [0b111, 0b101, 0b111, 0b001, 0b111]),
+    ('A', [0b010, 0b101, 0b111, 0b101, 0b101]),
+    ('B', [0b110, 0b101, 0b110, 0b101, 0b110]),
+    ('C', [0b011, 0b100, 0b100, 0b100, 0b011]),
+    ('D', [0b110, 0b101, 0b101, 0b101, 0b110]),
+    ('E', [0b111, 0b100, 0b110, 0b100, 0b111]),
+    ('F', [0b111, 0b100, 0b110, 0b100, 0b100]),
+    ('H', [0b101, 0b101, 0b111, 0b101, 0b101]),
+    ('P', [0b110, 0b101, 0b110, 0b100, 0b100]),
+    ('T', [0b111, 0b010, 0b010, 0b010, 0b010]),
+    ('V', [0b101, 0b101, 0b101, 0b101, 0b010]),
+    ('X', [0b101, 0b101, 0b010, 0b101, 0b101]),
+    ('Y', [0b101, 0b101, 0b010, 0b010, 0b010]),
+    ('-', [0b000, 0b000, 0b111, 0b000, 0b000]),
+];
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum ViewerKind {
+    Tiles,
+    Maps,
+    Oam,
+}
+
+impl ViewerKind {
+    /// Returns the viewer toggled by a key, if any.
+    pub fn from_key(key: VirtualKeyCode) -> Option<ViewerKind> {
+        match key {
+            VirtualKeyCode::F10 => Some(ViewerKind::Tiles),
+            VirtualKeyCode::F11 => Some(ViewerKind::Maps),
+            VirtualKeyCode::F12 => Some(ViewerKind::Oam),
+            _ => None,
+        }
+    }
+
+    fn get_title(&self) -> &'static str {
+        match *self {
+            ViewerKind::Tiles => "Tiles",
+            ViewerKind::Maps => "Background Maps",
+            ViewerKind::Oam => "OAM",
+        }
+    }
+
+    /// Returns the size of the image, and how much to scale it up by.
+    fn get_size(&self) -> (usize, usize, f64) {
+        match *self {
+            ViewerKind::Tiles => (TILES_PER_ROW * 8, TILE_COUNT / TILES_PER_ROW * 8, 3.0),
+            ViewerKind::Maps => (256 * 2 + MAP_GAP, 256, 2.0),
+            ViewerKind::Oam => (
+                SPRITE_COLUMN_WIDTH * 2,
+                SPRITES_PER_COLUMN * SPRITE_ROW_HEIGHT,
+                2.0,
+            ),
+        }
+    }
+}
+
+/// A image being drawn into.
+struct Canvas {
+    pixels: Vec<u8>,
+    width: usize,
+    height: usize,
+}
+
+impl Canvas {
+    fn set_pixel(&mut self, x: usize, y: usize, colour: [u8; 3]) {
+        if x < self.width && y < self.height {
+            let pos = (y * self.width + x) * PITCH;
+            self.pixels[pos..pos + PITCH].copy_from_slice(&colour);
+        }
+    }
+
+    fn fill(&mut self, x: usize, y: usize, width: usize, height: usize, colour: [u8; 3]) {
+        for y in y..y + height {
+            for x in x..x + width {
+                self.set_pixel(x, y, colour);
+            }
+        }
+    }
+
+    /// Draws the outline of a rectangle on a 256x256 map starting at
+    ///  `origin`, wrapping around its edges as the hardware does.
+    fn outline_wrapped(
+        &mut self,
+        origin: usize,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+        colour: [u8; 3],
+    ) {
+        for i in 0..width {
+            let x = origin + (x + i) % 256;
+            self.set_pixel(x, y % 256, colour);
+            self.set_pixel(x, (y + height - 1) % 256, colour);
+        }
+        for i in 0..height {
+            let y = (y + i) % 256;
+            self.set_pixel(origin + x % 256, y, colour);
+            self.set_pixel(origin + (x + width - 1) % 256, y, colour);
+        }
+    }
+
+    fn text(&mut self, x: usize, y: usize, text: &str) {
+        for (i, c) in text.chars().enumerate() {
+            if let Some(&(_, rows)) = FONT.iter().find(|&&(glyph, _)| glyph == c) {
+                for (row, bits) in rows.iter().enumerate() {
+                    for column in 0..3 {
+                        if (bits >> (2 - column)) & 0x1 == 1 {
+                            self.set_pixel(x + i * 4 + column, y + row, TEXT_COLOUR);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Draws a tile, optionally leaving colour 0 transparent.
+    #[allow(clippy::too_many_arguments)]
+    fn tile(
+        &mut self,
+        gpu: &GPU,
+        tile: usize,
+        palette: u8,
+        x: usize,
+        y: usize,
+        flip_x: bool,
+        flip_y: bool,
+        transparent: bool,
+    ) {
+        for row in 0..8 {
+            let source_row = if flip_y { 7 - row } else { row };
+            let low = gpu.vram[tile * 16 + source_row * 2];
+            let high = gpu.vram[tile * 16 + source_row * 2 + 1];
+
+            for column in 0..8 {
+                let bit = if flip_x { column } else { 7 - column };
+                let colour = ((high >> bit) & 0x1) << 1 | ((low >> bit) & 0x1);
+                if transparent && colour == 0 {
+                    continue;
+                }
+                self.set_pixel(x + column, y + row, get_colour(gpu, palette, colour));
+            }
+        }
+    }
+}
+
+/// Returns the colour shown for a colour number through a palette register.
+fn get_colour(gpu: &GPU, palette: u8, colour: u8) -> [u8; 3] {
+    let shade = ((palette >> (colour * 2)) & 0x3) as usize;
+    let mut rgb = [0; 3];
+    rgb.copy_from_slice(&gpu.palette[shade * PITCH..shade * PITCH + PITCH]);
+    rgb
+}
+
+/// Returns the tile a background map entry refers to, depending on which
+///  addressing mode LCDC selects.
+fn get_map_tile(lcdc: u8, entry: u8) -> usize {
+    if (lcdc >> 4) & 0x1 == 1 {
+        entry as usize
+    } else {
+        (256 + entry as i8 as isize) as usize
+    }
+}
+
+pub struct Viewer {
+    kind: ViewerKind,
+    display: Display,
+    canvas: Canvas,
+    /// The index into `TILE_PALETTES` used by the tile viewer
+    tile_palette: usize,
+}
+
+impl Viewer {
+    fn draw_tiles(&mut self, gpu: &GPU) {
+        let palette = match self.tile_palette {
+            0 => gpu.bgp,
+            1 => gpu.obp0,
+            2 => gpu.obp1,
+            _ => 0b11100100,
+        };
+
+        for tile in 0..TILE_COUNT {
+            let x = (tile % TILES_PER_ROW) * 8;
+            let y = (tile / TILES_PER_ROW) * 8;
+            self.canvas.tile(gpu, tile, palette, x, y, false, false, false);
+        }
+    }
+
+    fn draw_maps(&mut self, gpu: &GPU) {
+        self.canvas.fill(256, 0, MAP_GAP, 256, BACKGROUND_COLOUR);
+
+        for (map, &offset) in MAP_OFFSETS.iter().enumerate() {
+            let origin = map * (256 + MAP_GAP);
+            for entry in 0..32 * 32 {
+                let tile = get_map_tile(gpu.lcdc, gpu.vram[offset + entry]);
+                let x = origin + (entry % 32) * 8;
+                let y = (entry / 32) * 8;
+                self.canvas.tile(gpu, tile, gpu.bgp, x, y, false, false, false);
+            }
+        }
+
+        // The area of the window shown, from the top left of its map
+        let window_x = (gpu.wx as usize).saturating_sub(7);
+        if (gpu.lcdc >> 5) & 0x1 == 1 && window_x < 160 && gpu.wy < 144 {
+            let origin = ((gpu.lcdc >> 6) & 0x1) as usize * (256 + MAP_GAP);
+            let width = 160 - window_x;
+            let height = 144 - gpu.wy as usize;
+            self.canvas
+                .outline_wrapped(origin, 0, 0, width, height, WINDOW_COLOUR);
+        }
+
+        // The area of the background shown
+        let origin = ((gpu.lcdc >> 3) & 0x1) as usize * (256 + MAP_GAP);
+        self.canvas.outline_wrapped(
+            origin,
+            gpu.scx as usize,
+            gpu.scy as usize,
+            160,
+            144,
+            VIEWPORT_COLOUR,
+        );
+    }
+
+    fn draw_oam(&mut self, gpu: &GPU) {
+        let (width, height) = (self.canvas.width, self.canvas.height);
+        self.canvas.fill(0, 0, width, height, BACKGROUND_COLOUR);
+
+        let tall = (gpu.lcdc >> 2) & 0x1 == 1;
+
+        for sprite in 0..40 {
+            let x = (sprite / SPRITES_PER_COLUMN) * SPRITE_COLUMN_WIDTH;
+            let y = (sprite % SPRITES_PER_COLUMN) * SPRITE_ROW_HEIGHT + 1;
+
+            let sprite_y = gpu.oam[sprite * 4];
+            let sprite_x = gpu.oam[sprite * 4 + 1];
+            let tile = gpu.oam[sprite * 4 + 2];
+            let attributes = gpu.oam[sprite * 4 + 3];
+
+            let priority = (attributes >> 7) & 0x1 == 1;
+            let flip_y = (attributes >> 6) & 0x1 == 1;
+            let flip_x = (attributes >> 5) & 0x1 == 1;
+            let high_palette = (attributes >> 4) & 0x1 == 1;
+            let palette = if high_palette { gpu.obp1 } else { gpu.obp0 };
+
+            self.canvas.text(x + 1, y + 5, &format!("{:02X}", sprite));
+
+            // Preview, with 8x16 sprites flipping as a whole
+            self.canvas.fill(x + 10, y, 8, 16, PREVIEW_COLOUR);
+            if tall {
+                let (top, bottom) = if flip_y { (1, 0) } else { (0, 1) };
+                let tile = (tile & 0xFE) as usize;
+                self.canvas
+                    .tile(gpu, tile + top, palette, x + 10, y, flip_x, flip_y, true);
+                self.canvas
+                    .tile(gpu, tile + bottom, palette, x + 10, y + 8, flip_x, flip_y, true);
+            } else {
+                self.canvas
+                    .tile(gpu, tile as usize, palette, x + 10, y, flip_x, flip_y, true);
+            }
+
+            let flags = format!(
+                "{}{}{}{}",
+                if priority { 'P' } else { '-' },
+                if flip_x { 'H' } else { '-' },
+                if flip_y { 'V' } else { '-' },
+                if high_palette { '1' } else { '0' },
+            );
+            self.canvas.text(
+                x + 21,
+                y + 5,
+                &format!("Y{:02X} X{:02X} T{:02X} {}", sprite_y, sprite_x, tile, flags),
+            );
+        }
+    }
+
+    fn update_title(&self) {
+        let title = match self.kind {
+            ViewerKind::Tiles => format!(
+                "{} ({}, space to change)",
+                self.kind.get_title(),
+                TILE_PALETTES[self.tile_palette]
+            ),
+            _ => self.kind.get_title().to_string(),
+        };
+        self.display.window().set_title(&title);
+    }
+
+    /// Redraws the viewer from the current state of the GPU.
+    pub fn update(&mut self, gpu: &GPU) {
+        match self.kind {
+            ViewerKind::Tiles => self.draw_tiles(gpu),
+            ViewerKind::Maps => self.draw_maps(gpu),
+            ViewerKind::Oam => self.draw_oam(gpu),
+        }
+        self.redraw();
+    }
+
+    /// Shows the last thing drawn again.
+    fn redraw(&mut self) {
+        self.display.draw(&self.canvas.pixels);
+        self.display.present();
+    }
+
+    pub fn build<T>(events_loop: &EventLoopWindowTarget<T>, kind: ViewerKind) -> Viewer {
+        let (width, height, scale) = kind.get_size();
+
+        let viewer = Viewer {
+            kind,
+            display: Display::build(events_loop, kind.get_title(), width, height, scale, false),
+            canvas: Canvas {
+                pixels: vec![0; width * height * PITCH],
+                width,
+                height,
+            },
+            tile_palette: 0,
+        };
+        viewer.update_title();
+        viewer
+    }
+}
+
+/// The viewer windows which are currently open.
+pub struct Viewers {
+    open: Vec<Viewer>,
+}
+
+impl Viewers {
+    /// Opens a viewer, or closes it if it is already open.
+    pub fn toggle<T>(&mut self, events_loop: &EventLoopWindowTarget<T>, kind: ViewerKind) {
+        match self.open.iter().position(|viewer| viewer.kind == kind) {
+            Some(position) => {
+                self.open.remove(position);
+            }
+            None => self.open.push(Viewer::build(events_loop, kind)),
+        }
+    }
+
+    /// Handles a event for one of the viewer windows.
+    pub fn handle_event(&mut self, window: WindowId, event: &WindowEvent) {
+        let position = match self
+            .open
+            .iter()
+            .position(|viewer| viewer.display.window().id() == window)
+        {
+            Some(position) => position,
+            None => return,
+        };
+
+        let viewer = &mut self.open[position];
+        match *event {
+            WindowEvent::CloseRequested => {
+                self.open.remove(position);
+            }
+            WindowEvent::Resized(size) => viewer.display.resize(size),
+            WindowEvent::KeyboardInput { input, .. }
+                if viewer.kind == ViewerKind::Tiles
+                    && input.state == ElementState::Pressed
+                    && input.virtual_keycode == Some(VirtualKeyCode::Space) =>
+            {
+                viewer.tile_palette = (viewer.tile_palette + 1) % TILE_PALETTES.len();
+                viewer.update_title();
+            }
+            _ => {}
+        }
+    }
+
+    /// Redraws a viewer window, if the system asks for it.
+    pub fn redraw(&mut self, window: WindowId) {
+        if let Some(viewer) = self
+            .open
+            .iter_mut()
+            .find(|viewer| viewer.display.window().id() == window)
+        {
+            viewer.redraw();
+        }
+    }
+
+    /// Redraws all open viewers from the current state of the GPU.
+    pub fn update(&mut self, gpu: &GPU) {
+        for viewer in &mut self.open {
+            viewer.update(gpu);
+        }
+    }
+
+    pub fn build() -> Viewers {
+        Viewers { open: Vec::new() }
+    }
+}
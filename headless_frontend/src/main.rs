@@ -0,0 +1,334 @@
+/**
+ * main.rs
+ *
+ * The main entry-point for the headless frontend, which runs games without a
+ *  display for automated testing.
+**/
+extern crate clap;
+extern crate png;
+
+#[macro_use]
+extern crate log;
+
+extern crate oxidgb_core;
+extern crate oxidgb_frontend_common;
+
+mod script;
+
+use std::cell::RefCell;
+use std::fs::File;
+use std::io::BufWriter;
+use std::process::exit;
+use std::rc::Rc;
+
+use clap::App;
+use clap::Arg;
+
+use oxidgb_core::cpu::CPU;
+use oxidgb_core::mem::GBMemory;
+use oxidgb_core::model::Model;
+use oxidgb_core::rom::GameROM;
+use oxidgb_core::serial::SerialLogger;
+#[cfg(feature = "trace")]
+use oxidgb_core::trace::TraceFormat;
+
+use oxidgb_frontend_common::logging::setup_logging;
+#[cfg(feature = "trace")]
+use oxidgb_frontend_common::trace::TraceLog;
+#[cfg(feature = "trace")]
+use oxidgb_frontend_common::trace::Tracer;
+
+use script::InputEvent;
+use script::StopCondition;
+
+fn main() {
+    // Parse arguments
+    let app = App::new("Oxidgb Headless")
+        .about("Runs Gameboy games without a display, for automated testing")
+        .version("v0.1")
+        .arg(
+            Arg::with_name("rom")
+                .value_name("ROM")
+                .help("The ROM to run")
+                .required(true),
+        )
+        .arg(
+            Arg::with_name("boot-rom")
+                .short("b")
+                .long("boot-rom")
+                .value_name("FILE")
+                .help("Runs the specified boot ROM before the game")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("model")
+                .short("m")
+                .long("model")
+                .value_name("MODEL")
+                .help("Selects the hardware to emulate")
+                .possible_values(&["dmg0", "dmg", "mgb", "sgb", "cgb", "agb"])
+                .default_value("dmg")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("frames")
+                .short("f")
+                .long("frames")
+                .value_name("COUNT")
+                .help("The number of frames to run for (or to wait for a condition)")
+                .default_value("600")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("until-pc")
+                .long("until-pc")
+                .value_name("ADDRESS")
+                .help("Stops once execution reaches the address (in hex)")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name("until-serial")
+                .long("until-serial")
+                .value_name("TEXT")
+                .help("Stops once the serial output contains the text")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name("until-mem")
+                .long("until-mem")
+                .value_name("ADDRESS=VALUE")
+                .help("Stops once the memory address holds the value (both in hex)")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name("input")
+                .short("i")
+                .long("input")
+                .value_name("FRAME=BUTTONS")
+                .help("Holds buttons from the frame onwards, e.g. 120=start+a (120= releases)")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name("input-file")
+                .long("input-file")
+                .value_name("FILE")
+                .help("Reads inputs (one FRAME=BUTTONS per line) from a file")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("screenshot")
+                .short("s")
+                .long("screenshot")
+                .value_name("FILE")
+                .help("Saves the final frame as a PNG")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("print-serial")
+                .long("print-serial")
+                .help("Prints everything sent over the serial port once finished"),
+        )
+        .arg(
+            Arg::with_name("verbose")
+                .short("v")
+                .long("verbose")
+                .help("Enables verbose logging"),
+        );
+
+    #[cfg(feature = "trace")]
+    let app = app
+        .arg(
+            Arg::with_name("trace")
+                .long("trace")
+                .value_name("FILE")
+                .help("Logs every executed instruction to the file")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("trace-format")
+                .long("trace-format")
+                .value_name("FORMAT")
+                .help("Selects the format of the trace log")
+                .possible_values(&["doctor", "bgb"])
+                .default_value("doctor")
+                .takes_value(true),
+        );
+
+    let args = app.get_matches();
+
+    setup_logging(args.is_present("verbose")).expect("Failed to setup logger");
+
+    let max_frames: u32 = match args.value_of("frames").unwrap().parse() {
+        Ok(frames) => frames,
+        Err(_) => fail("Invalid frame count"),
+    };
+
+    // Parse scripted inputs
+    let mut input_specs: Vec<String> = args
+        .values_of("input")
+        .map(|values| values.map(String::from).collect())
+        .unwrap_or_default();
+
+    if let Some(path) = args.value_of("input-file") {
+        let contents = std::fs::read_to_string(path)
+            .unwrap_or_else(|e| fail(&format!("Failed to read input file: {}", e)));
+        input_specs.extend(
+            contents
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(String::from),
+        );
+    }
+
+    let mut inputs = input_specs
+        .iter()
+        .map(|spec| InputEvent::parse(spec))
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap_or_else(|e| fail(&e));
+    inputs.sort_by_key(|event| event.frame);
+
+    // Parse stop conditions
+    let mut conditions = Vec::new();
+    for spec in args.values_of("until-pc").into_iter().flatten() {
+        conditions.push(StopCondition::parse_pc(spec).unwrap_or_else(|e| fail(&e)));
+    }
+    for spec in args.values_of("until-serial").into_iter().flatten() {
+        conditions.push(StopCondition::Serial(spec.to_string()));
+    }
+    for spec in args.values_of("until-mem").into_iter().flatten() {
+        conditions.push(StopCondition::parse_memory(spec).unwrap_or_else(|e| fail(&e)));
+    }
+
+    // Load the game
+    let data = std::fs::read(args.value_of("rom").unwrap())
+        .unwrap_or_else(|e| fail(&format!("Failed to read ROM: {}", e)));
+    let rom = GameROM::build(data);
+
+    let boot_rom = args.value_of("boot-rom").map(|path| {
+        std::fs::read(path).unwrap_or_else(|e| fail(&format!("Failed to read boot ROM: {}", e)))
+    });
+
+    let model = Model::from_name(args.value_of("model").unwrap()).expect("Invalid model");
+
+    let mut memory = GBMemory::build(model, rom, boot_rom);
+
+    let serial = Rc::new(RefCell::new(SerialLogger::build()));
+    memory.set_serial_device(Box::new(serial.clone()));
+
+    let mut cpu = CPU::build(memory);
+
+    #[cfg(feature = "trace")]
+    let mut trace_log = TraceLog(args.value_of("trace").map(|path| {
+        let format = TraceFormat::from_name(args.value_of("trace-format").unwrap())
+            .expect("Invalid trace format");
+        let file = File::create(path)
+            .unwrap_or_else(|e| fail(&format!("Failed to create trace log: {}", e)));
+        Tracer::build(BufWriter::new(file), format)
+    }));
+
+    // Run until the frame limit or a condition is met
+    let mut frame = 0;
+    let mut next_input = 0;
+    let mut serial_length = 0;
+    let mut serial_output = String::new();
+    let mut condition_met = None;
+
+    while frame < max_frames {
+        while next_input < inputs.len() && inputs[next_input].frame <= frame {
+            cpu.mem.set_input(&inputs[next_input].buttons);
+            next_input += 1;
+        }
+
+        #[cfg(feature = "trace")]
+        let frame_done = cpu.tick(&mut trace_log);
+        #[cfg(not(feature = "trace"))]
+        let frame_done = cpu.tick();
+
+        // Only rebuild the serial output when something new has been sent
+        {
+            let serial = serial.borrow();
+            if serial.output.len() != serial_length {
+                serial_length = serial.output.len();
+                serial_output = String::from_utf8_lossy(&serial.output).into_owned();
+            }
+        }
+
+        condition_met = conditions
+            .iter()
+            .position(|condition| condition.is_met(&cpu, &serial_output));
+        if condition_met.is_some() {
+            break;
+        }
+
+        if frame_done || cpu.is_frame_timed_out() {
+            cpu.cycle_counter = 0;
+            frame += 1;
+        }
+    }
+
+    #[cfg(feature = "trace")]
+    if let Some(ref mut tracer) = trace_log.0 {
+        if let Err(e) = tracer.flush() {
+            fail(&format!("Failed to write trace: {}", e));
+        }
+    }
+
+    // Report results
+    println!("Frames: {}", frame);
+    println!("Framebuffer hash: {:016x}", hash(&cpu.mem.gpu.pixel_data));
+
+    if let Some(path) = args.value_of("screenshot") {
+        if let Err(e) = save_png(path, &cpu.mem.gpu.pixel_data) {
+            fail(&format!("Failed to save screenshot: {}", e));
+        }
+    }
+
+    if args.is_present("print-serial") {
+        println!("Serial output:");
+        println!("{}", serial_output);
+    }
+
+    if !conditions.is_empty() {
+        match condition_met {
+            Some(index) => println!("Condition {} met", index + 1),
+            None => {
+                println!("No condition met within {} frames", max_frames);
+                exit(1);
+            }
+        }
+    }
+}
+
+/// Reports a error and exits.
+fn fail(message: &str) -> ! {
+    error!("{}", message);
+    exit(2);
+}
+
+/// Hashes the framebuffer (using 64-bit FNV-1a) for comparing runs.
+fn hash(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf29ce484222325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Saves the framebuffer as a PNG.
+fn save_png(path: &str, pixels: &[u8]) -> Result<(), png::EncodingError> {
+    let file = File::create(path)?;
+
+    let mut encoder = png::Encoder::new(BufWriter::new(file), 160, 144);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(pixels)
+}
@@ -0,0 +1,96 @@
+/**
+ * script.rs
+ *
+ * Parses scripted inputs and the conditions to stop emulation at.
+**/
+use oxidgb_core::cpu::CPU;
+use oxidgb_core::input::GameboyButton;
+
+/// Buttons to hold from a given frame onwards.
+pub struct InputEvent {
+    pub frame: u32,
+    pub buttons: Vec<GameboyButton>,
+}
+
+impl InputEvent {
+    /// Parses a event in the form `FRAME=BUTTON+BUTTON...`, e.g. `120=start+a`.
+    ///  No buttons (`120=`) releases everything.
+    pub fn parse(spec: &str) -> Result<InputEvent, String> {
+        let mut parts = spec.splitn(2, '=');
+        let frame = parts
+            .next()
+            .unwrap()
+            .trim()
+            .parse()
+            .map_err(|_| format!("Invalid frame in input \"{}\"", spec))?;
+        let buttons = parts
+            .next()
+            .ok_or_else(|| format!("Missing '=' in input \"{}\"", spec))?;
+
+        let buttons = buttons
+            .split('+')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(parse_button)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(InputEvent { frame, buttons })
+    }
+}
+
+fn parse_button(name: &str) -> Result<GameboyButton, String> {
+    match name.to_lowercase().as_str() {
+        "a" => Ok(GameboyButton::A),
+        "b" => Ok(GameboyButton::B),
+        "left" => Ok(GameboyButton::LEFT),
+        "right" => Ok(GameboyButton::RIGHT),
+        "up" => Ok(GameboyButton::UP),
+        "down" => Ok(GameboyButton::DOWN),
+        "start" => Ok(GameboyButton::START),
+        "select" => Ok(GameboyButton::SELECT),
+        _ => Err(format!("Unknown button \"{}\"", name)),
+    }
+}
+
+/// A condition which ends emulation once met.
+pub enum StopCondition {
+    /// The CPU is about to execute the instruction at this address.
+    Pc(u16),
+    /// The serial output contains this text.
+    Serial(String),
+    /// A memory address holds this value.
+    Memory(u16, u8),
+}
+
+impl StopCondition {
+    pub fn parse_pc(spec: &str) -> Result<StopCondition, String> {
+        Ok(StopCondition::Pc(parse_hex_u16(spec)?))
+    }
+
+    /// Parses a memory condition in the form `ADDRESS=VALUE` (both in hex).
+    pub fn parse_memory(spec: &str) -> Result<StopCondition, String> {
+        let mut parts = spec.splitn(2, '=');
+        let address = parse_hex_u16(parts.next().unwrap())?;
+        let value = parts
+            .next()
+            .ok_or_else(|| format!("Missing '=' in memory condition \"{}\"", spec))?;
+        let value = u8::from_str_radix(value.trim().trim_start_matches("0x"), 16)
+            .map_err(|_| format!("Invalid value \"{}\"", value))?;
+
+        Ok(StopCondition::Memory(address, value))
+    }
+
+    /// Checks if this condition has been met.
+    pub fn is_met(&self, cpu: &CPU, serial_output: &str) -> bool {
+        match self {
+            StopCondition::Pc(address) => cpu.regs.pc == *address,
+            StopCondition::Serial(text) => serial_output.contains(text.as_str()),
+            StopCondition::Memory(address, value) => cpu.mem.read(*address) == *value,
+        }
+    }
+}
+
+fn parse_hex_u16(value: &str) -> Result<u16, String> {
+    u16::from_str_radix(value.trim().trim_start_matches("0x"), 16)
+        .map_err(|_| format!("Invalid address \"{}\"", value))
+}
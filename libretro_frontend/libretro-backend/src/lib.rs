@@ -215,6 +215,8 @@ pub trait Core: Default {
     fn on_unserialize(&mut self, _buffer: &[u8]) -> bool {
         false
     }
+    fn on_cheat_reset(&mut self) {}
+    fn on_cheat_set(&mut self, _index: u32, _is_enabled: bool, _code: &str) {}
 }
 
 static mut ENVIRONMENT_CALLBACK: Option<libretro_sys::EnvironmentFn> = None;
@@ -465,14 +467,22 @@ impl<B: Core> Retro<B> {
         self.core.on_unserialize(buffer)
     }
 
-    pub fn on_cheat_reset(&mut self) {}
+    pub fn on_cheat_reset(&mut self) {
+        self.core.on_cheat_reset()
+    }
 
     pub fn on_cheat_set(
         &mut self,
-        _index: libc::c_uint,
-        _is_enabled: bool,
-        _code: *const libc::c_char,
+        index: libc::c_uint,
+        is_enabled: bool,
+        code: *const libc::c_char,
     ) {
+        if code.is_null() {
+            return;
+        }
+
+        let code = unsafe { CStr::from_ptr(code) }.to_string_lossy();
+        self.core.on_cheat_set(index as u32, is_enabled, &code)
     }
 
     pub fn on_unload_game(&mut self) {
@@ -9,26 +9,26 @@ extern crate libretro_backend;
 extern crate log;
 
 extern crate oxidgb_core;
-
-extern crate bincode;
-extern crate serde;
-
-mod logging;
+extern crate oxidgb_frontend_common;
 
 use libretro_backend::*;
 
 use oxidgb_core::cpu::CPU;
 use oxidgb_core::input::GameboyButton;
 use oxidgb_core::mem::GBMemory;
+use oxidgb_core::model::Model;
 use oxidgb_core::rom::get_rom_size;
 use oxidgb_core::rom::GameROM;
+use oxidgb_core::state::load_state;
+use oxidgb_core::state::save_state;
+
+use oxidgb_frontend_common::logging::setup_logging;
 
 use std::path::Path;
 
 use std::fs;
 use std::fs::File;
 
-use std::io::Cursor;
 use std::io::Read;
 
 struct OxidgbEmulator<'a> {
@@ -65,7 +65,7 @@ impl libretro_backend::Core for OxidgbEmulator<'_> {
     fn on_load_game(&mut self, game_data: GameData) -> LoadGameResult {
         // Set up logger
         // TODO: gate is_verbose
-        logging::setup_logging(true).unwrap();
+        setup_logging(true).unwrap();
 
         let rom = if let Some(data) = game_data.data() {
             GameROM::build(data.to_owned())
@@ -101,14 +101,15 @@ impl libretro_backend::Core for OxidgbEmulator<'_> {
             unreachable!();
         };
 
-        let memory = GBMemory::build(rom);
+        let memory = GBMemory::build(Model::Dmg, rom, None);
 
         let cpu = CPU::build(memory);
 
         self.game_data = Some(game_data);
-        self.cpu = Some(cpu);
+        // States are a fixed size for a given cartridge
+        self.serialized_size = save_state(&cpu).len();
 
-        self.serialized_size = bincode::serialized_size(&self.cpu).unwrap() as _;
+        self.cpu = Some(cpu);
 
         let info = AudioVideoInfo::new()
             .video(160, 144, 60.0, PixelFormat::ARGB8888)
@@ -194,8 +195,7 @@ impl libretro_backend::Core for OxidgbEmulator<'_> {
         let memory = cpu.mem;
 
         // Take ROM and run
-        let rom = memory.rom;
-        let memory = GBMemory::build(rom);
+        let memory = GBMemory::build(memory.model, memory.rom, memory.boot_rom);
         let cpu = CPU::build(memory);
         self.cpu = Some(cpu);
     }
@@ -205,21 +205,57 @@ impl libretro_backend::Core for OxidgbEmulator<'_> {
     }
 
     fn on_serialize(&mut self, buffer: &mut [u8]) -> bool {
-        match bincode::serialize_into(&mut Cursor::new(buffer), &self.cpu) {
-            Ok(v) => v,
-            Err(v) => panic!("Error while serializing: {:?}", v),
+        let state = match self.cpu {
+            Some(ref cpu) => save_state(cpu),
+            None => return false,
+        };
+
+        if state.len() > buffer.len() {
+            error!("Save state buffer is too small");
+            return false;
         }
 
+        buffer[..state.len()].copy_from_slice(&state);
+
         true
     }
 
     fn on_unserialize(&mut self, buffer: &[u8]) -> bool {
-        self.cpu = match bincode::deserialize_from(&mut Cursor::new(buffer)) {
-            Ok(v) => v,
-            Err(v) => panic!("Error while unserializing: {:?}", v),
+        let cpu = match self.cpu {
+            Some(ref mut cpu) => cpu,
+            None => return false,
         };
 
-        true
+        // Ignore any padding the frontend has added
+        let size = self.serialized_size.min(buffer.len());
+
+        match load_state(cpu, &buffer[..size]) {
+            Ok(()) => true,
+            Err(e) => {
+                error!("Failed to load state: {}", e);
+                false
+            }
+        }
+    }
+
+    fn on_cheat_reset(&mut self) {
+        if let Some(ref mut cpu) = self.cpu {
+            cpu.mem.rom.cheats.clear();
+        }
+    }
+
+    fn on_cheat_set(&mut self, _index: u32, is_enabled: bool, code: &str) {
+        // Frontends reset and then set every enabled cheat whenever the list
+        //  changes, so disabled cheats never need removing
+        if !is_enabled {
+            return;
+        }
+
+        if let Some(ref mut cpu) = self.cpu {
+            if let Err(e) = cpu.mem.rom.cheats.add(code) {
+                error!("Failed to add cheat {}: {}", code, e);
+            }
+        }
     }
 
     fn save_memory(&mut self) -> Option<&mut [u8]> {